@@ -0,0 +1 @@
+CREATE DATABASE todo_app;
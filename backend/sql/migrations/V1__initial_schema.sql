@@ -1,5 +1,6 @@
-CREATE DATABASE todo_app;
-\c todo_app
+-- The schema of the original setup script, from before migrations. It is written so that it also applies
+-- to a database that was set up with that script: its tables and views are kept as they are.
+-- Everything added since is in the later migrations.
 
 -- Table Structure
 -- Primary Key
@@ -7,15 +8,13 @@ CREATE DATABASE todo_app;
 -- Creator User Id (if applicable)
 -- Everything else
 
-drop table if exists goal cascade;
-create table goal(
+create table if not exists goal(
   goal_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null
 );
 
-drop table if exists time_utility_function cascade;
-create table time_utility_function(
+create table if not exists time_utility_function(
   time_utility_function_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -26,8 +25,7 @@ create table time_utility_function(
 );
 
 -- invariant: goal_id is valid
-drop table if exists goal_data cascade;
-create table goal_data(
+create table if not exists goal_data(
   goal_data_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -38,7 +36,7 @@ create table goal_data(
   status bigint not null -- enum
 );
 
-create view recent_goal_data as
+create or replace view recent_goal_data as
   select gd.* from goal_data gd
   inner join (
    select max(goal_data_id) id 
@@ -47,8 +45,7 @@ create view recent_goal_data as
   ) maxids
   on maxids.id = gd.goal_data_id;
 
-drop table if exists goal_event cascade;
-create table goal_event(
+create table if not exists goal_event(
   goal_event_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -58,7 +55,7 @@ create table goal_event(
   active bool not null
 );
 
-create view recent_goal_event as
+create or replace view recent_goal_event as
   select ge.* from goal_event ge
   inner join (
    select max(goal_event_id) id 
@@ -68,8 +65,7 @@ create view recent_goal_event as
   on maxids.id = ge.goal_event_id;
 
 -- represents a goal that must have status SUCCESS 
-drop table if exists goal_dependency cascade;
-create table goal_dependency(
+create table if not exists goal_dependency(
   goal_dependency_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -78,7 +74,7 @@ create table goal_dependency(
   active bool not null
 );
 
-create view recent_goal_dependency as
+create or replace view recent_goal_dependency as
   select gd.* from goal_dependency gd
   inner join (
    select max(goal_dependency_id) id 
@@ -89,8 +85,7 @@ create view recent_goal_dependency as
 
 
 -- Maybe compiled functions
-drop table if exists user_generated_code cascade;
-create table user_generated_code(
+create table if not exists user_generated_code(
   user_generated_code_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -101,15 +96,13 @@ create table user_generated_code(
 
 -- how words trigger goal generation:
 -- when we see a pattern, we invoke the 
-drop table if exists goal_template cascade;
-create table goal_template(
+create table if not exists goal_template(
   goal_template_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null
 );
 
-drop table if exists goal_template_data cascade;
-create table goal_template_data(
+create table if not exists goal_template_data(
   goal_template_data_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -121,7 +114,7 @@ create table goal_template_data(
   active bool not null
 );
 
-create view recent_goal_template_data as
+create or replace view recent_goal_template_data as
   select gtd.* from goal_template_data gtd
   inner join (
    select max(goal_template_data_id) id 
@@ -130,8 +123,7 @@ create view recent_goal_template_data as
   ) maxids
   on maxids.id = gtd.goal_template_data_id;
 
-drop table if exists goal_template_pattern cascade;
-create table goal_template_pattern(
+create table if not exists goal_template_pattern(
   goal_template_pattern_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -140,7 +132,7 @@ create table goal_template_pattern(
   active bool not null
 );
 
-create view recent_goal_template_pattern as
+create or replace view recent_goal_template_pattern as
   select gtp.* from goal_template_pattern gtp
   inner join (
    select max(goal_template_pattern_id) id 
@@ -150,15 +142,13 @@ create view recent_goal_template_pattern as
   on maxids.id = gtp.goal_template_pattern_id;
 
 -- a named entity is basically a tag, we use it for searching for objects
-drop table if exists named_entity cascade;  
-create table named_entity(
+create table if not exists named_entity(
   named_entity_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null
 );
 
-drop table if exists named_entity_data cascade;
-create table named_entity_data(
+create table if not exists named_entity_data(
   named_entity_data_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -168,7 +158,7 @@ create table named_entity_data(
   active bool not null
 );
 
-create view recent_named_entity_data as
+create or replace view recent_named_entity_data as
   select ned.* from named_entity_data ned
   inner join (
    select max(named_entity_data_id) id 
@@ -178,8 +168,7 @@ create view recent_named_entity_data as
   on maxids.id = ned.named_entity_data_id;
 
 -- different names to call entities
-drop table if exists named_entity_pattern cascade;
-create table named_entity_pattern(
+create table if not exists named_entity_pattern(
   named_entity_pattern_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -188,7 +177,7 @@ create table named_entity_pattern(
   active bool not null
 );
 
-create view recent_named_entity_pattern as
+create or replace view recent_named_entity_pattern as
   select nep.* from named_entity_pattern nep
   inner join (
    select max(named_entity_pattern_id) id 
@@ -198,8 +187,7 @@ create view recent_named_entity_pattern as
   on maxids.id = nep.named_entity_pattern_id;
 
 -- joining named entity to goal
-drop table if exists goal_entity_tag cascade;
-create table goal_entity_tag(
+create table if not exists goal_entity_tag(
   goal_entity_tag_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -208,7 +196,7 @@ create table goal_entity_tag(
   active bool not null
 );
 
-create view recent_goal_entity_tag as
+create or replace view recent_goal_entity_tag as
   select get.* from goal_entity_tag get
   inner join (
    select max(goal_entity_tag_id) id 
@@ -218,15 +206,13 @@ create view recent_goal_entity_tag as
   on maxids.id = get.goal_entity_tag_id;
 
 
-drop table if exists external_event cascade;
-create table external_event(
+create table if not exists external_event(
   external_event_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null
 );
 
-drop table if exists external_event_data cascade;
-create table external_event_data(
+create table if not exists external_event_data(
   external_event_data_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
@@ -237,7 +223,7 @@ create table external_event_data(
   active bool not null
 );
 
-create view recent_external_event_data as
+create or replace view recent_external_event_data as
   select eed.* from external_event_data eed
   inner join (
    select max(external_event_data_id) id 
@@ -245,4 +231,3 @@ create view recent_external_event_data as
    group by external_event_id
   ) maxids
   on maxids.id = eed.external_event_data_id;
-
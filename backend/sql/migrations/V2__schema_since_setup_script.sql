@@ -0,0 +1,387 @@
+-- Everything added to the schema since the original setup script, for new databases as well as the ones V1 adopted.
+-- Existing tables get the new columns, with a default for the rows they already have.
+
+-- for sync and paging, which read a user's rows in id order
+create index if not exists goal_creator_user_id on goal(creator_user_id, goal_id);
+create index if not exists time_utility_function_creator_user_id on time_utility_function(creator_user_id, time_utility_function_id);
+create index if not exists goal_data_creator_user_id on goal_data(creator_user_id, goal_data_id);
+create index if not exists goal_event_creator_user_id on goal_event(creator_user_id, goal_event_id);
+create index if not exists goal_dependency_creator_user_id on goal_dependency(creator_user_id, goal_dependency_id);
+create index if not exists user_generated_code_creator_user_id on user_generated_code(creator_user_id, user_generated_code_id);
+create index if not exists goal_template_creator_user_id on goal_template(creator_user_id, goal_template_id);
+create index if not exists goal_template_data_creator_user_id on goal_template_data(creator_user_id, goal_template_data_id);
+create index if not exists goal_template_pattern_creator_user_id on goal_template_pattern(creator_user_id, goal_template_pattern_id);
+create index if not exists named_entity_creator_user_id on named_entity(creator_user_id, named_entity_id);
+create index if not exists named_entity_data_creator_user_id on named_entity_data(creator_user_id, named_entity_data_id);
+create index if not exists named_entity_pattern_creator_user_id on named_entity_pattern(creator_user_id, named_entity_pattern_id);
+create index if not exists goal_entity_tag_creator_user_id on goal_entity_tag(creator_user_id, goal_entity_tag_id);
+create index if not exists external_event_creator_user_id on external_event(creator_user_id, external_event_id);
+create index if not exists external_event_data_creator_user_id on external_event_data(creator_user_id, external_event_data_id);
+
+-- if true, other users may browse and fork this template
+alter table goal_template_data add column if not exists published bool not null default false;
+alter table goal_template_data alter column published drop default;
+
+-- the view's columns were fixed when it was created, this picks up published
+create or replace view recent_goal_template_data as
+  select gtd.* from goal_template_data gtd
+  inner join (
+   select max(goal_template_data_id) id 
+   from goal_template_data 
+   group by goal_template_id
+  ) maxids
+  on maxids.id = gtd.goal_template_data_id;
+
+alter table external_event add column if not exists source text; -- NULLABLE, where the event was synced from (ex: a calendar url)
+alter table external_event add column if not exists external_uid text; -- NULLABLE, the id of the event in the source
+alter table external_event add column if not exists resource_name text; -- NULLABLE, the name a caldav client created it under
+
+create unique index if not exists external_event_creator_user_id_source_external_uid_key on external_event(creator_user_id, source, external_uid);
+
+-- a secret url where a user's schedule can be subscribed to as an iCalendar feed
+-- rotating the token inserts a new row, only the most recent one per user is valid
+create table if not exists calendar_feed(
+  calendar_feed_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  token_hash text not null unique, -- sha256 of the token, the token itself is only shown once
+  active bool not null
+);
+
+create or replace view recent_calendar_feed as
+  select cf.* from calendar_feed cf
+  inner join (
+   select max(calendar_feed_id) id 
+   from calendar_feed 
+   group by creator_user_id
+  ) maxids
+  on maxids.id = cf.calendar_feed_id;
+
+-- a remote CalDAV collection whose events are periodically pulled into external_event
+-- changing the credentials or unsubscribing inserts a new row for the same url
+create table if not exists caldav_subscription(
+  caldav_subscription_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  url text not null,
+  username text not null,
+  encrypted_password bytea not null, -- sealed with the server's secret key, never returned by the api
+  active bool not null
+);
+
+create index if not exists caldav_subscription_creator_user_id on caldav_subscription(creator_user_id, caldav_subscription_id);
+
+create or replace view recent_caldav_subscription as
+  select cs.* from caldav_subscription cs
+  inner join (
+   select max(caldav_subscription_id) id 
+   from caldav_subscription 
+   group by creator_user_id, url
+  ) maxids
+  on maxids.id = cs.caldav_subscription_id;
+
+-- the outcome of each time a subscription was synced (or failed to)
+-- syncs where the remote collection reported no changes are not recorded
+create table if not exists caldav_sync(
+  caldav_sync_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  caldav_subscription_id bigint not null references caldav_subscription(caldav_subscription_id),
+  ctag text, -- NULLABLE, if the server doesn't support ctags
+  sync_token text, -- NULLABLE, if the server doesn't support sync tokens
+  event_count bigint not null,
+  error text -- NULLABLE, only set if the sync failed
+);
+
+create index if not exists caldav_sync_creator_user_id on caldav_sync(creator_user_id, caldav_sync_id);
+
+create or replace view recent_caldav_sync as
+  select cs.* from caldav_sync cs
+  inner join (
+   select max(caldav_sync_id) id 
+   from caldav_sync 
+   group by caldav_subscription_id
+  ) maxids
+  on maxids.id = cs.caldav_sync_id;
+
+-- a url that is POSTed to whenever one of the chosen kinds of change happens
+create table if not exists webhook(
+  webhook_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  url text not null,
+  secret text not null, -- never returned by the api, payloads are signed with it
+  event_kinds bigint[] not null, -- enum
+  active bool not null -- also turned off by the server after repeated failures
+);
+
+create index if not exists webhook_creator_user_id on webhook(creator_user_id, webhook_id);
+
+create or replace view recent_webhook as
+  select w.* from webhook w
+  inner join (
+   select max(webhook_id) id 
+   from webhook 
+   group by creator_user_id, url
+  ) maxids
+  on maxids.id = w.webhook_id;
+
+-- a change waiting to be delivered to a webhook
+-- it is written in the same transaction as the change, so it only exists if the change was committed
+create table if not exists webhook_event(
+  webhook_event_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  webhook_id bigint not null references webhook(webhook_id),
+  kind bigint not null, -- enum
+  payload text not null -- json
+);
+
+create index if not exists webhook_event_creation_time on webhook_event(creation_time);
+
+-- every attempt to deliver a webhook event
+create table if not exists webhook_delivery(
+  webhook_delivery_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  webhook_event_id bigint not null references webhook_event(webhook_event_id),
+  status_code bigint, -- NULLABLE, if there was no response
+  error text -- NULLABLE, only set if the delivery failed
+);
+
+create index if not exists webhook_delivery_creator_user_id on webhook_delivery(creator_user_id, webhook_delivery_id);
+
+create index if not exists webhook_delivery_webhook_event_id on webhook_delivery(webhook_event_id);
+
+-- when and how a user wants to be reminded
+create table if not exists reminder_setting(
+  reminder_setting_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  goal_event_offset bigint, -- NULLABLE, how long before a goal event starts to remind
+  utility_threshold bigint, -- NULLABLE, remind before a goal's utility drops below this
+  utility_offset bigint, -- NULLABLE, set together with utility_threshold
+  channels bigint[] not null -- enum
+);
+
+create index if not exists reminder_setting_creator_user_id on reminder_setting(creator_user_id, reminder_setting_id);
+
+create or replace view recent_reminder_setting as
+  select rs.* from reminder_setting rs
+  inner join (
+   select max(reminder_setting_id) id 
+   from reminder_setting 
+   group by creator_user_id
+  ) maxids
+  on maxids.id = rs.reminder_setting_id;
+
+-- a reminder that is due at fire_time, for something that happens at due_time
+-- the scheduler may try to queue the same reminder many times, the unique index keeps one of them
+create table if not exists reminder(
+  reminder_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  kind bigint not null, -- enum
+  goal_id bigint not null references goal(goal_id),
+  goal_event_id bigint references goal_event(goal_event_id), -- NULLABLE, only for goal event reminders
+  due_time bigint not null,
+  fire_time bigint not null
+);
+
+create index if not exists reminder_creator_user_id on reminder(creator_user_id, reminder_id);
+
+create unique index if not exists reminder_unique on reminder(kind, goal_id, coalesce(goal_event_id, 0), due_time, fire_time);
+create index if not exists reminder_fire_time on reminder(fire_time);
+
+-- every step of sending a reminder through a channel
+-- an attempt is claimed with a SENDING row before the channel is called, and then gets a SENT or FAILED row
+-- the unique index means an attempt can only be claimed once, so a reminder is never sent twice
+create table if not exists reminder_delivery(
+  reminder_delivery_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  reminder_id bigint not null references reminder(reminder_id),
+  channel bigint not null, -- enum
+  attempt bigint not null,
+  status bigint not null, -- enum
+  error text -- NULLABLE, only set if the delivery failed
+);
+
+create index if not exists reminder_delivery_creator_user_id on reminder_delivery(creator_user_id, reminder_delivery_id);
+
+create unique index if not exists reminder_delivery_claim on reminder_delivery(reminder_id, channel, attempt) where status = 0;
+create index if not exists reminder_delivery_reminder_id on reminder_delivery(reminder_id);
+
+-- where a user's mail goes, and when they get their daily agenda
+create table if not exists mail_setting(
+  mail_setting_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  address text not null,
+  digest_time bigint, -- NULLABLE, milliseconds after local midnight to send the digest at, no digest if null
+  tz text not null -- the timezone the digest's day is in
+);
+
+create index if not exists mail_setting_creator_user_id on mail_setting(creator_user_id, mail_setting_id);
+
+create or replace view recent_mail_setting as
+  select ms.* from mail_setting ms
+  inner join (
+   select max(mail_setting_id) id 
+   from mail_setting 
+   group by creator_user_id
+  ) maxids
+  on maxids.id = ms.mail_setting_id;
+
+-- the daily digests that were sent, written before sending so that a day's digest goes out at most once
+create table if not exists mail_digest(
+  mail_digest_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  digest_date text not null -- the local date, like 2023-01-05
+);
+
+create unique index if not exists mail_digest_creator_user_id_digest_date on mail_digest(creator_user_id, digest_date);
+
+-- every row inserted into one of a user's tables, in the order they were inserted.
+-- written by triggers, and announced on the change_event channel with the user's id as the payload,
+-- so that every backend instance can tell its open change streams to catch up.
+-- streams resume from the id of the last event they saw.
+create table if not exists change_event(
+  change_event_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  table_name text not null, -- the table the row was inserted into
+  row_id bigint not null -- the id of the inserted row
+);
+
+create index if not exists change_event_creator_user_id on change_event(creator_user_id, change_event_id);
+create index if not exists change_event_creation_time on change_event(creation_time);
+
+-- how far each user's change events have been pruned.
+-- users' ids are interleaved, so a gap in a user's ids doesn't mean anything was pruned, this does.
+-- one row per user, updated in place, as it is bookkeeping rather than the user's data
+create table if not exists change_event_pruned(
+  creator_user_id bigint primary key,
+  change_event_id bigint not null -- the newest of the user's events that was deleted
+);
+
+-- an insert holds the user's lock shared until it commits, and the row only gets its id once it holds the lock.
+-- so the user's inserts don't wait for each other, but whoever takes the lock exclusively (see account_service::lock)
+-- knows that none of them is in progress, and that the rows committed after it lets go get greater ids.
+-- that is how sync and change streams find high-water marks that no row committed later can fall below
+create or replace function claim_id() returns trigger as $$
+begin
+  perform pg_advisory_xact_lock_shared(NEW.creator_user_id);
+  NEW := jsonb_populate_record(NEW, jsonb_build_object(
+    TG_TABLE_NAME || '_id',
+    nextval(pg_get_serial_sequence(TG_TABLE_NAME, TG_TABLE_NAME || '_id'))
+  ));
+  return NEW;
+end;
+$$ language plpgsql;
+
+create or replace function record_change() returns trigger as $$
+begin
+  insert into change_event(creator_user_id, table_name, row_id)
+  values (NEW.creator_user_id, TG_TABLE_NAME, (to_jsonb(NEW) ->> (TG_TABLE_NAME || '_id'))::bigint);
+  -- only delivered once the transaction commits
+  perform pg_notify('change_event', NEW.creator_user_id::text);
+  return NEW;
+end;
+$$ language plpgsql;
+
+-- inserts into the user's tables count as changes whether the user or a worker made them (like reminder and caldav_sync).
+-- the delivery logs of webhooks, reminders and digests aren't the user's data, so they don't count as changes
+create or replace trigger goal_change after insert on goal for each row execute function record_change();
+create or replace trigger time_utility_function_change after insert on time_utility_function for each row execute function record_change();
+create or replace trigger goal_data_change after insert on goal_data for each row execute function record_change();
+create or replace trigger goal_event_change after insert on goal_event for each row execute function record_change();
+create or replace trigger goal_dependency_change after insert on goal_dependency for each row execute function record_change();
+create or replace trigger user_generated_code_change after insert on user_generated_code for each row execute function record_change();
+create or replace trigger goal_template_change after insert on goal_template for each row execute function record_change();
+create or replace trigger goal_template_data_change after insert on goal_template_data for each row execute function record_change();
+create or replace trigger goal_template_pattern_change after insert on goal_template_pattern for each row execute function record_change();
+create or replace trigger named_entity_change after insert on named_entity for each row execute function record_change();
+create or replace trigger named_entity_data_change after insert on named_entity_data for each row execute function record_change();
+create or replace trigger named_entity_pattern_change after insert on named_entity_pattern for each row execute function record_change();
+create or replace trigger goal_entity_tag_change after insert on goal_entity_tag for each row execute function record_change();
+create or replace trigger external_event_change after insert on external_event for each row execute function record_change();
+create or replace trigger external_event_data_change after insert on external_event_data for each row execute function record_change();
+create or replace trigger calendar_feed_change after insert on calendar_feed for each row execute function record_change();
+create or replace trigger caldav_subscription_change after insert on caldav_subscription for each row execute function record_change();
+create or replace trigger caldav_sync_change after insert on caldav_sync for each row execute function record_change();
+create or replace trigger webhook_change after insert on webhook for each row execute function record_change();
+create or replace trigger reminder_setting_change after insert on reminder_setting for each row execute function record_change();
+create or replace trigger reminder_change after insert on reminder for each row execute function record_change();
+create or replace trigger mail_setting_change after insert on mail_setting for each row execute function record_change();
+
+-- the same tables, as the ids of their rows and of their change events are both read up to high-water marks
+create or replace trigger goal_claim_id before insert on goal for each row execute function claim_id();
+create or replace trigger time_utility_function_claim_id before insert on time_utility_function for each row execute function claim_id();
+create or replace trigger goal_data_claim_id before insert on goal_data for each row execute function claim_id();
+create or replace trigger goal_event_claim_id before insert on goal_event for each row execute function claim_id();
+create or replace trigger goal_dependency_claim_id before insert on goal_dependency for each row execute function claim_id();
+create or replace trigger user_generated_code_claim_id before insert on user_generated_code for each row execute function claim_id();
+create or replace trigger goal_template_claim_id before insert on goal_template for each row execute function claim_id();
+create or replace trigger goal_template_data_claim_id before insert on goal_template_data for each row execute function claim_id();
+create or replace trigger goal_template_pattern_claim_id before insert on goal_template_pattern for each row execute function claim_id();
+create or replace trigger named_entity_claim_id before insert on named_entity for each row execute function claim_id();
+create or replace trigger named_entity_data_claim_id before insert on named_entity_data for each row execute function claim_id();
+create or replace trigger named_entity_pattern_claim_id before insert on named_entity_pattern for each row execute function claim_id();
+create or replace trigger goal_entity_tag_claim_id before insert on goal_entity_tag for each row execute function claim_id();
+create or replace trigger external_event_claim_id before insert on external_event for each row execute function claim_id();
+create or replace trigger external_event_data_claim_id before insert on external_event_data for each row execute function claim_id();
+create or replace trigger calendar_feed_claim_id before insert on calendar_feed for each row execute function claim_id();
+create or replace trigger caldav_subscription_claim_id before insert on caldav_subscription for each row execute function claim_id();
+create or replace trigger caldav_sync_claim_id before insert on caldav_sync for each row execute function claim_id();
+create or replace trigger webhook_claim_id before insert on webhook for each row execute function claim_id();
+create or replace trigger reminder_setting_claim_id before insert on reminder_setting for each row execute function claim_id();
+create or replace trigger reminder_claim_id before insert on reminder for each row execute function claim_id();
+create or replace trigger mail_setting_claim_id before insert on mail_setting for each row execute function claim_id();
+
+-- the responses to requests that were sent with an Idempotency-Key header, so that a retried request isn't handled twice.
+-- unlike the other tables, rows are updated and deleted: the response is filled in once the request was handled,
+-- the row is deleted if handling it failed, and rows are pruned after a day
+create table if not exists idempotency_key(
+  idempotency_key_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  idempotency_key text not null, -- chosen by the client
+  request_hash text not null, -- sha256 of the path and the request without its api key
+  response text -- NULLABLE, the json response, null while the request is being handled
+);
+
+create unique index if not exists idempotency_key_creator_user_id_idempotency_key on idempotency_key(creator_user_id, idempotency_key);
+create index if not exists idempotency_key_creation_time on idempotency_key(creation_time);
+
+-- users for running without the auth service, see local_auth.
+-- a user's local_user_id is the creator_user_id of everything they make
+create table if not exists local_user(
+  local_user_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  email text not null unique,
+  password_hash text not null -- argon2, in PHC string format
+);
+
+-- api keys of local users
+-- cancelling a key inserts a new inactive row for it, only the most recent row per key counts
+create table if not exists local_api_key(
+  local_api_key_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null references local_user(local_user_id),
+  key_hash text not null, -- sha256 of the key, the key itself is only shown once
+  duration bigint not null, -- how long after creation_time the key is valid, in milliseconds
+  active bool not null
+);
+
+create index if not exists local_api_key_key_hash on local_api_key(key_hash, local_api_key_id);
+
+create or replace view recent_local_api_key as
+  select lak.* from local_api_key lak
+  inner join (
+   select max(local_api_key_id) id
+   from local_api_key
+   group by key_hash
+  ) maxids
+  on maxids.id = lak.local_api_key_id;
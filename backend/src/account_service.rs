@@ -0,0 +1,701 @@
+// Exports everything a user owns as one versioned JSON document, and imports it back.
+// The document mirrors the database schema, so it is defined here rather than in the api crate.
+// Every revision is included, not just the recent ones, so the append-only history survives a move.
+
+use super::db_types::*;
+use futures_util::{pin_mut, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{GenericClient, IsolationLevel};
+use warp::hyper::body::{Bytes, Sender};
+
+pub static ACCOUNT_FORMAT: &str = "todo-app-account";
+// bump this whenever the shape of the document changes
+pub static ACCOUNT_VERSION: i64 = 1;
+
+// rows are flushed to the client in chunks of about this size
+static CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+  pub format: String,
+  pub version: i64,
+  pub export_time: i64,
+  pub creator_user_id: i64,
+  #[serde(flatten)]
+  pub tables: Tables,
+}
+
+// the rows of each of the user's tables, also used by sync.
+// the tables are listed in the order they are imported in, so that references always point backwards
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Tables {
+  pub user_generated_code: Vec<UserGeneratedCode>,
+  pub time_utility_function: Vec<TimeUtilityFunction>,
+  pub goal: Vec<Goal>,
+  pub goal_data: Vec<GoalData>,
+  pub goal_event: Vec<GoalEvent>,
+  pub goal_dependency: Vec<GoalDependency>,
+  pub goal_template: Vec<GoalTemplate>,
+  pub goal_template_data: Vec<GoalTemplateData>,
+  pub goal_template_pattern: Vec<GoalTemplatePattern>,
+  pub named_entity: Vec<NamedEntity>,
+  pub named_entity_data: Vec<NamedEntityData>,
+  pub named_entity_pattern: Vec<NamedEntityPattern>,
+  pub goal_entity_tag: Vec<GoalEntityTag>,
+  pub external_event: Vec<ExternalEvent>,
+  pub external_event_data: Vec<ExternalEventData>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountExportProps {
+  pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountImportProps {
+  pub account: Account,
+  pub api_key: String,
+}
+
+// takes the user's lock until the end of the transaction.
+// their inserts hold it shared until they commit (see claim_id in the schema), so once we have it
+// none of their writes is in progress, and none can be made in the meantime but our own
+pub async fn lock(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+  con
+    .execute("SELECT pg_advisory_xact_lock($1)", &[&creator_user_id])
+    .await?;
+  Ok(())
+}
+
+// the greatest id each of the tables has given out, read once none of the user's writes is in progress.
+// rows of the user that are committed later get greater ids, so reading up to the marks can't miss one.
+// the user's lock is only held while the marks are read, so con must not already be in a transaction
+pub async fn high_water_marks(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  tables: &[&str],
+) -> Result<Vec<i64>, tokio_postgres::Error> {
+  let mut tx = con.transaction().await?;
+  lock(&mut tx, creator_user_id).await?;
+  let marks = tx
+    .query(
+      "SELECT coalesce(pg_sequence_last_value(pg_get_serial_sequence(t, t || '_id')::regclass), 0)
+       FROM unnest($1::text[]) WITH ORDINALITY AS tables(t, i)
+       ORDER BY i
+      ",
+      &[&tables],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.get(0))
+    .collect();
+  tx.commit().await?;
+
+  Ok(marks)
+}
+
+// the table name in camelCase, like the fields of Tables
+pub fn table_key(table: &str) -> String {
+  let mut key = String::new();
+  for (i, word) in table.split('_').enumerate() {
+    let mut chars = word.chars();
+    if let (true, Some(c)) = (i > 0, chars.next()) {
+      key.push(c.to_ascii_uppercase());
+    }
+    key.push_str(chars.as_str());
+  }
+  key
+}
+
+// writes the rows of one table into the document, oldest first
+async fn export_table<T>(
+  con: &impl GenericClient,
+  out: &mut Sender,
+  buf: &mut String,
+  table: &str,
+  creator_user_id: i64,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+  T: From<tokio_postgres::Row> + Serialize,
+{
+  buf.push_str(&format!(",\"{}\":[", table_key(table)));
+
+  let rows = con
+    .query_raw(
+      format!(
+        "SELECT * FROM {} WHERE creator_user_id = $1 ORDER BY {}_id",
+        table, table
+      )
+      .as_str(),
+      [&creator_user_id as &(dyn ToSql + Sync)],
+    )
+    .await?;
+  pin_mut!(rows);
+
+  let mut first = true;
+  while let Some(row) = rows.try_next().await? {
+    if !first {
+      buf.push(',');
+    }
+    first = false;
+    buf.push_str(&serde_json::to_string(&T::from(row))?);
+
+    if buf.len() > CHUNK_SIZE {
+      out.send_data(Bytes::from(std::mem::take(buf))).await?;
+    }
+  }
+
+  buf.push(']');
+  Ok(())
+}
+
+// streams the document to the client table by table, so the whole account never sits in memory
+// everything is read from a single snapshot, so the document is consistent
+pub async fn export(
+  con: &mut tokio_postgres::Client,
+  creator_user_id: i64,
+  export_time: i64,
+  out: &mut Sender,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+  let sp = con
+    .build_transaction()
+    .isolation_level(IsolationLevel::RepeatableRead)
+    .read_only(true)
+    .start()
+    .await?;
+
+  let mut buf = format!(
+    "{{\"format\":\"{}\",\"version\":{},\"exportTime\":{},\"creatorUserId\":{}",
+    ACCOUNT_FORMAT, ACCOUNT_VERSION, export_time, creator_user_id
+  );
+
+  let b = &mut buf;
+  let u = creator_user_id;
+  export_table::<UserGeneratedCode>(&sp, out, b, "user_generated_code", u).await?;
+  export_table::<TimeUtilityFunction>(&sp, out, b, "time_utility_function", u).await?;
+  export_table::<Goal>(&sp, out, b, "goal", u).await?;
+  export_table::<GoalData>(&sp, out, b, "goal_data", u).await?;
+  export_table::<GoalEvent>(&sp, out, b, "goal_event", u).await?;
+  export_table::<GoalDependency>(&sp, out, b, "goal_dependency", u).await?;
+  export_table::<GoalTemplate>(&sp, out, b, "goal_template", u).await?;
+  export_table::<GoalTemplateData>(&sp, out, b, "goal_template_data", u).await?;
+  export_table::<GoalTemplatePattern>(&sp, out, b, "goal_template_pattern", u).await?;
+  export_table::<NamedEntity>(&sp, out, b, "named_entity", u).await?;
+  export_table::<NamedEntityData>(&sp, out, b, "named_entity_data", u).await?;
+  export_table::<NamedEntityPattern>(&sp, out, b, "named_entity_pattern", u).await?;
+  export_table::<GoalEntityTag>(&sp, out, b, "goal_entity_tag", u).await?;
+  export_table::<ExternalEvent>(&sp, out, b, "external_event", u).await?;
+  export_table::<ExternalEventData>(&sp, out, b, "external_event_data", u).await?;
+
+  buf.push('}');
+  out.send_data(Bytes::from(buf)).await?;
+
+  sp.commit().await?;
+
+  Ok(())
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+  // the document is malformed, or refers to rows it doesn't contain
+  Invalid(String),
+  Postgres(tokio_postgres::Error),
+}
+
+impl From<tokio_postgres::Error> for ImportError {
+  fn from(e: tokio_postgres::Error) -> ImportError {
+    ImportError::Postgres(e)
+  }
+}
+
+// maps the ids of the exporting instance to the ids of this one
+#[derive(Default)]
+struct IdMap(HashMap<i64, i64>);
+
+impl IdMap {
+  fn get(&self, table: &str, id: i64) -> Result<i64, ImportError> {
+    self
+      .0
+      .get(&id)
+      .copied()
+      .ok_or_else(|| ImportError::Invalid(format!("unknown {}_id {}", table, id)))
+  }
+}
+
+// collects the ids of a table, rejecting any that appear twice
+fn unique_ids<T>(
+  table: &str,
+  rows: &[T],
+  id: impl Fn(&T) -> i64,
+) -> Result<HashSet<i64>, ImportError> {
+  let mut ids = HashSet::new();
+  for x in rows {
+    if !ids.insert(id(x)) {
+      return Err(ImportError::Invalid(format!(
+        "duplicate {}_id {}",
+        table,
+        id(x)
+      )));
+    }
+  }
+  Ok(ids)
+}
+
+fn check_ref(table: &str, ids: &HashSet<i64>, id: i64) -> Result<(), ImportError> {
+  match ids.contains(&id) {
+    true => Ok(()),
+    false => Err(ImportError::Invalid(format!("unknown {}_id {}", table, id))),
+  }
+}
+
+// checks the whole document before anything is inserted,
+// so that a bad document is rejected as such instead of failing halfway through
+fn validate(t: &Tables) -> Result<(), ImportError> {
+  let user_generated_code = unique_ids("user_generated_code", &t.user_generated_code, |x| {
+    x.user_generated_code_id
+  })?;
+  let time_utility_function = unique_ids("time_utility_function", &t.time_utility_function, |x| {
+    x.time_utility_function_id
+  })?;
+  let goal = unique_ids("goal", &t.goal, |x| x.goal_id)?;
+  let goal_template = unique_ids("goal_template", &t.goal_template, |x| x.goal_template_id)?;
+  let named_entity = unique_ids("named_entity", &t.named_entity, |x| x.named_entity_id)?;
+  let external_event = unique_ids("external_event", &t.external_event, |x| x.external_event_id)?;
+  unique_ids("goal_data", &t.goal_data, |x| x.goal_data_id)?;
+  unique_ids("goal_event", &t.goal_event, |x| x.goal_event_id)?;
+  unique_ids("goal_dependency", &t.goal_dependency, |x| {
+    x.goal_dependency_id
+  })?;
+  unique_ids("goal_template_data", &t.goal_template_data, |x| {
+    x.goal_template_data_id
+  })?;
+  unique_ids("goal_template_pattern", &t.goal_template_pattern, |x| {
+    x.goal_template_pattern_id
+  })?;
+  unique_ids("named_entity_data", &t.named_entity_data, |x| {
+    x.named_entity_data_id
+  })?;
+  unique_ids("named_entity_pattern", &t.named_entity_pattern, |x| {
+    x.named_entity_pattern_id
+  })?;
+  unique_ids("goal_entity_tag", &t.goal_entity_tag, |x| {
+    x.goal_entity_tag_id
+  })?;
+  unique_ids("external_event_data", &t.external_event_data, |x| {
+    x.external_event_data_id
+  })?;
+
+  for x in t.time_utility_function.iter() {
+    if x.start_times.is_empty() || x.start_times.len() != x.utils.len() {
+      return Err(ImportError::Invalid(format!(
+        "time_utility_function {} needs as many start_times as utils, and at least one",
+        x.time_utility_function_id
+      )));
+    }
+  }
+  for x in t.goal_data.iter() {
+    check_ref("goal", &goal, x.goal_id)?;
+    check_ref(
+      "time_utility_function",
+      &time_utility_function,
+      x.time_utility_function_id,
+    )?;
+  }
+  for x in t.goal_event.iter() {
+    check_ref("goal", &goal, x.goal_id)?;
+  }
+  for x in t.goal_dependency.iter() {
+    check_ref("goal", &goal, x.goal_id)?;
+    check_ref("goal", &goal, x.dependent_goal_id)?;
+  }
+  for x in t.goal_template_data.iter() {
+    check_ref("goal_template", &goal_template, x.goal_template_id)?;
+    check_ref(
+      "user_generated_code",
+      &user_generated_code,
+      x.user_generated_code_id,
+    )?;
+  }
+  for x in t.goal_template_pattern.iter() {
+    check_ref("goal_template", &goal_template, x.goal_template_id)?;
+  }
+  for x in t.named_entity_data.iter() {
+    check_ref("named_entity", &named_entity, x.named_entity_id)?;
+  }
+  for x in t.named_entity_pattern.iter() {
+    check_ref("named_entity", &named_entity, x.named_entity_id)?;
+  }
+  for x in t.goal_entity_tag.iter() {
+    check_ref("named_entity", &named_entity, x.named_entity_id)?;
+    check_ref("goal", &goal, x.goal_id)?;
+  }
+  for x in t.external_event_data.iter() {
+    check_ref("external_event", &external_event, x.external_event_id)?;
+  }
+
+  // the database allows each uid only once per source
+  let mut uids = HashSet::new();
+  for x in t.external_event.iter() {
+    if let (Some(source), Some(uid)) = (&x.source, &x.external_uid) {
+      if !uids.insert((source, uid)) {
+        return Err(ImportError::Invalid(format!(
+          "duplicate external_uid {} from {}",
+          uid, source
+        )));
+      }
+    }
+  }
+  Ok(())
+}
+
+// inserts one row, returning the new id
+async fn insert(
+  con: &mut impl GenericClient,
+  sql: &str,
+  params: &[&(dyn ToSql + Sync)],
+) -> Result<i64, ImportError> {
+  Ok(con.query_one(sql, params).await?.get(0))
+}
+
+// Recreates every row of the document for the given user, with fresh ids.
+// Rows keep their creation times and are inserted in their original order,
+// so the recent_* views pick out the same revisions as on the exporting instance.
+// Importing is additive: rows are added next to whatever the user already has.
+// The exception are external events with a source and uid the user already has (synced or caldav events),
+// those keep the existing event and its revisions, and the document's copies are skipped.
+// Returns how many rows were imported into each table.
+pub async fn import(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  account: Account,
+) -> Result<BTreeMap<&'static str, usize>, ImportError> {
+  if account.format != ACCOUNT_FORMAT {
+    return Err(ImportError::Invalid(format!(
+      "unknown format: {}",
+      account.format
+    )));
+  }
+  if account.version != ACCOUNT_VERSION {
+    return Err(ImportError::Invalid(format!(
+      "unsupported version: {}",
+      account.version
+    )));
+  }
+
+  validate(&account.tables)?;
+
+  let mut tables = account.tables;
+  let u = creator_user_id;
+  let mut counts = BTreeMap::new();
+
+  tables
+    .user_generated_code
+    .sort_by_key(|x| x.user_generated_code_id);
+  let mut user_generated_code_ids = IdMap::default();
+  for x in tables.user_generated_code.iter() {
+    let id = insert(
+      con,
+      "INSERT INTO user_generated_code(creation_time, creator_user_id, source_code, source_lang, wasm_cache)
+       VALUES($1, $2, $3, $4, $5) RETURNING user_generated_code_id",
+      &[&x.creation_time, &u, &x.source_code, &x.source_lang, &x.wasm_cache],
+    )
+    .await?;
+    user_generated_code_ids
+      .0
+      .insert(x.user_generated_code_id, id);
+  }
+  counts.insert("userGeneratedCode", tables.user_generated_code.len());
+
+  tables
+    .time_utility_function
+    .sort_by_key(|x| x.time_utility_function_id);
+  let mut time_utility_function_ids = IdMap::default();
+  for x in tables.time_utility_function.iter() {
+    let id = insert(
+      con,
+      "INSERT INTO time_utility_function(creation_time, creator_user_id, start_times, utils)
+       VALUES($1, $2, $3, $4) RETURNING time_utility_function_id",
+      &[&x.creation_time, &u, &x.start_times, &x.utils],
+    )
+    .await?;
+    time_utility_function_ids
+      .0
+      .insert(x.time_utility_function_id, id);
+  }
+  counts.insert("timeUtilityFunction", tables.time_utility_function.len());
+
+  tables.goal.sort_by_key(|x| x.goal_id);
+  let mut goal_ids = IdMap::default();
+  for x in tables.goal.iter() {
+    let id = insert(
+      con,
+      "INSERT INTO goal(creation_time, creator_user_id)
+       VALUES($1, $2) RETURNING goal_id",
+      &[&x.creation_time, &u],
+    )
+    .await?;
+    goal_ids.0.insert(x.goal_id, id);
+  }
+  counts.insert("goal", tables.goal.len());
+
+  tables.goal_data.sort_by_key(|x| x.goal_data_id);
+  for x in tables.goal_data.iter() {
+    insert(
+      con,
+      "INSERT INTO goal_data(creation_time, creator_user_id, goal_id, name, duration_estimate, time_utility_function_id, status)
+       VALUES($1, $2, $3, $4, $5, $6, $7) RETURNING goal_data_id",
+      &[
+        &x.creation_time,
+        &u,
+        &goal_ids.get("goal", x.goal_id)?,
+        &x.name,
+        &x.duration_estimate,
+        &time_utility_function_ids.get("time_utility_function", x.time_utility_function_id)?,
+        &(x.status.clone() as i64),
+      ],
+    )
+    .await?;
+  }
+  counts.insert("goalData", tables.goal_data.len());
+
+  tables.goal_event.sort_by_key(|x| x.goal_event_id);
+  for x in tables.goal_event.iter() {
+    insert(
+      con,
+      "INSERT INTO goal_event(creation_time, creator_user_id, goal_id, start_time, end_time, active)
+       VALUES($1, $2, $3, $4, $5, $6) RETURNING goal_event_id",
+      &[
+        &x.creation_time,
+        &u,
+        &goal_ids.get("goal", x.goal_id)?,
+        &x.start_time,
+        &x.end_time,
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("goalEvent", tables.goal_event.len());
+
+  tables.goal_dependency.sort_by_key(|x| x.goal_dependency_id);
+  for x in tables.goal_dependency.iter() {
+    insert(
+      con,
+      "INSERT INTO goal_dependency(creation_time, creator_user_id, goal_id, dependent_goal_id, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING goal_dependency_id",
+      &[
+        &x.creation_time,
+        &u,
+        &goal_ids.get("goal", x.goal_id)?,
+        &goal_ids.get("goal", x.dependent_goal_id)?,
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("goalDependency", tables.goal_dependency.len());
+
+  tables.goal_template.sort_by_key(|x| x.goal_template_id);
+  let mut goal_template_ids = IdMap::default();
+  for x in tables.goal_template.iter() {
+    let id = insert(
+      con,
+      "INSERT INTO goal_template(creation_time, creator_user_id)
+       VALUES($1, $2) RETURNING goal_template_id",
+      &[&x.creation_time, &u],
+    )
+    .await?;
+    goal_template_ids.0.insert(x.goal_template_id, id);
+  }
+  counts.insert("goalTemplate", tables.goal_template.len());
+
+  tables
+    .goal_template_data
+    .sort_by_key(|x| x.goal_template_data_id);
+  for x in tables.goal_template_data.iter() {
+    insert(
+      con,
+      "INSERT INTO goal_template_data(creation_time, creator_user_id, goal_template_id, name, utility, duration_estimate, user_generated_code_id, published, active)
+       VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING goal_template_data_id",
+      &[
+        &x.creation_time,
+        &u,
+        &goal_template_ids.get("goal_template", x.goal_template_id)?,
+        &x.name,
+        &x.utility,
+        &x.duration_estimate,
+        &user_generated_code_ids.get("user_generated_code", x.user_generated_code_id)?,
+        &x.published,
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("goalTemplateData", tables.goal_template_data.len());
+
+  tables
+    .goal_template_pattern
+    .sort_by_key(|x| x.goal_template_pattern_id);
+  for x in tables.goal_template_pattern.iter() {
+    insert(
+      con,
+      "INSERT INTO goal_template_pattern(creation_time, creator_user_id, goal_template_id, pattern, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING goal_template_pattern_id",
+      &[
+        &x.creation_time,
+        &u,
+        &goal_template_ids.get("goal_template", x.goal_template_id)?,
+        &x.pattern,
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("goalTemplatePattern", tables.goal_template_pattern.len());
+
+  tables.named_entity.sort_by_key(|x| x.named_entity_id);
+  let mut named_entity_ids = IdMap::default();
+  for x in tables.named_entity.iter() {
+    let id = insert(
+      con,
+      "INSERT INTO named_entity(creation_time, creator_user_id)
+       VALUES($1, $2) RETURNING named_entity_id",
+      &[&x.creation_time, &u],
+    )
+    .await?;
+    named_entity_ids.0.insert(x.named_entity_id, id);
+  }
+  counts.insert("namedEntity", tables.named_entity.len());
+
+  tables
+    .named_entity_data
+    .sort_by_key(|x| x.named_entity_data_id);
+  for x in tables.named_entity_data.iter() {
+    insert(
+      con,
+      "INSERT INTO named_entity_data(creation_time, creator_user_id, named_entity_id, name, kind, active)
+       VALUES($1, $2, $3, $4, $5, $6) RETURNING named_entity_data_id",
+      &[
+        &x.creation_time,
+        &u,
+        &named_entity_ids.get("named_entity", x.named_entity_id)?,
+        &x.name,
+        &(x.kind.clone() as i64),
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("namedEntityData", tables.named_entity_data.len());
+
+  tables
+    .named_entity_pattern
+    .sort_by_key(|x| x.named_entity_pattern_id);
+  for x in tables.named_entity_pattern.iter() {
+    insert(
+      con,
+      "INSERT INTO named_entity_pattern(creation_time, creator_user_id, named_entity_id, pattern, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING named_entity_pattern_id",
+      &[
+        &x.creation_time,
+        &u,
+        &named_entity_ids.get("named_entity", x.named_entity_id)?,
+        &x.pattern,
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("namedEntityPattern", tables.named_entity_pattern.len());
+
+  tables.goal_entity_tag.sort_by_key(|x| x.goal_entity_tag_id);
+  for x in tables.goal_entity_tag.iter() {
+    insert(
+      con,
+      "INSERT INTO goal_entity_tag(creation_time, creator_user_id, named_entity_id, goal_id, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING goal_entity_tag_id",
+      &[
+        &x.creation_time,
+        &u,
+        &named_entity_ids.get("named_entity", x.named_entity_id)?,
+        &goal_ids.get("goal", x.goal_id)?,
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("goalEntityTag", tables.goal_entity_tag.len());
+
+  tables.external_event.sort_by_key(|x| x.external_event_id);
+  let mut external_event_ids = IdMap::default();
+  // events the user already had, their revisions aren't imported again
+  let mut existing_external_event_ids = HashSet::new();
+  for x in tables.external_event.iter() {
+    let id = con
+      .query_opt(
+        "INSERT INTO external_event(creation_time, creator_user_id, source, external_uid, resource_name)
+         VALUES($1, $2, $3, $4, $5)
+         ON CONFLICT (creator_user_id, source, external_uid) DO NOTHING
+         RETURNING external_event_id",
+        &[&x.creation_time, &u, &x.source, &x.external_uid, &x.resource_name],
+      )
+      .await?
+      .map(|row| row.get(0));
+    let id = match id {
+      Some(id) => id,
+      None => {
+        existing_external_event_ids.insert(x.external_event_id);
+        con
+          .query_one(
+            "SELECT external_event_id FROM external_event
+             WHERE creator_user_id=$1 AND source=$2 AND external_uid=$3",
+            &[&u, &x.source, &x.external_uid],
+          )
+          .await?
+          .get(0)
+      }
+    };
+    external_event_ids.0.insert(x.external_event_id, id);
+  }
+  counts.insert(
+    "externalEvent",
+    tables.external_event.len() - existing_external_event_ids.len(),
+  );
+
+  tables
+    .external_event_data
+    .sort_by_key(|x| x.external_event_data_id);
+  tables
+    .external_event_data
+    .retain(|x| !existing_external_event_ids.contains(&x.external_event_id));
+  for x in tables.external_event_data.iter() {
+    insert(
+      con,
+      "INSERT INTO external_event_data(creation_time, creator_user_id, external_event_id, name, start_time, end_time, active)
+       VALUES($1, $2, $3, $4, $5, $6, $7) RETURNING external_event_data_id",
+      &[
+        &x.creation_time,
+        &u,
+        &external_event_ids.get("external_event", x.external_event_id)?,
+        &x.name,
+        &x.start_time,
+        &x.end_time,
+        &x.active,
+      ],
+    )
+    .await?;
+  }
+  counts.insert("externalEventData", tables.external_event_data.len());
+
+  Ok(counts)
+}
@@ -1,249 +1,494 @@
+use super::auth::Auth;
+use super::caldav;
+use super::change_stream;
 use super::handlers;
+use super::idempotency;
+use super::page;
 use super::utils;
 use super::Config;
 use super::Db;
 use super::SERVICE_NAME;
-use auth_service_api::client::AuthService;
 use std::convert::Infallible;
 use std::future::Future;
+use todo_app_service_api::request;
 use todo_app_service_api::response;
 use todo_app_service_api::response::TodoAppError;
-use warp::http::StatusCode;
+use warp::filters::BoxedFilter;
+use warp::http::{HeaderValue, StatusCode};
 use warp::Filter;
 
 /// Helper to combine the multiple filters together with Filter::or, possibly boxing the types in
 /// the process. This greatly helps the build times for `ipfs-http`.
 /// https://github.com/seanmonstar/warp/issues/507#issuecomment-615974062
+// every route is turned into a plain response first,
+// otherwise the nested reply types grow with each route until the compiler gives up
 macro_rules! combine {
-  ($x:expr, $($y:expr),+) => {{
-      let filter = ($x).boxed();
-      $( let filter = (filter.or($y)).boxed(); )+
-      filter
+  ($($x:expr),+) => {{
+      balance(vec![$( ($x).map(warp::Reply::into_response).boxed() ),+])
   }}
 }
 
+type Route = BoxedFilter<(warp::reply::Response,)>;
+
+// joins the routes into a balanced tree of ors, keeping their order.
+// a request is polled through every level it passes on the stack, so chaining the routes one after another
+// nested as many levels as there are routes, which overflowed the stack of debug builds
+fn balance(mut routes: Vec<Route>) -> Route {
+    while routes.len() > 1 {
+        let mut pairs = routes.into_iter();
+        let mut joined = Vec::new();
+        while let Some(first) = pairs.next() {
+            joined.push(match pairs.next() {
+                Some(second) => first.or(second).unify().boxed(),
+                None => first,
+            });
+        }
+        routes = joined;
+    }
+    routes.pop().unwrap()
+}
+
 /// The function that will show all ones to call
 pub fn api(
     config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
+    changes: change_stream::Changes,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone {
     // public API
     combine!(
         info_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "info"),
             handlers::api_info,
         ),
         adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
+            warp::path!("public" / "local_auth" / "user" / "new"),
+            handlers::local_user_new,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "local_auth" / "api_key" / "new_valid"),
+            handlers::local_api_key_new_valid,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "local_auth" / "api_key" / "new_cancel"),
+            handlers::local_api_key_new_cancel,
+        ),
+        stream_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "account" / "export"),
+            handlers::account_export,
+        ),
+        sse_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            changes,
+            warp::path!("public" / "change" / "stream"),
+            handlers::change_stream,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "account" / "import"),
+            handlers::account_import,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "sync"),
+            handlers::sync,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "calendar_feed" / "new"),
+            handlers::calendar_feed_new,
+        ),
+        ics_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "calendar_feed" / String),
+            handlers::calendar_feed_ics,
+        ),
+        caldav_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "caldav" / ..),
+            handlers::caldav,
+        ),
+        new_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "caldav_subscription" / "new"),
+            handlers::caldav_subscription_new,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "caldav_subscription" / "view"),
+            handlers::caldav_subscription_view,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "caldav_sync" / "view"),
+            handlers::caldav_sync_view,
+        ),
+        new_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "webhook" / "new"),
+            handlers::webhook_new,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "webhook" / "view"),
+            handlers::webhook_view,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "webhook_delivery" / "view"),
+            handlers::webhook_delivery_view,
+        ),
+        new_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "reminder_setting" / "new"),
+            handlers::reminder_setting_new,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "reminder_setting" / "view"),
+            handlers::reminder_setting_view,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "reminder" / "view"),
+            handlers::reminder_view,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "reminder_delivery" / "view"),
+            handlers::reminder_delivery_view,
+        ),
+        new_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "mail_setting" / "new"),
+            handlers::mail_setting_new,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "mail_setting" / "view"),
+            handlers::mail_setting_view,
+        ),
+        new_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
             warp::path!("public" / "goal" / "new"),
             handlers::goal_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_data" / "new"),
             handlers::goal_data_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_dependency" / "new"),
             handlers::goal_dependency_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_entity_tag" / "new"),
             handlers::goal_entity_tag_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_event" / "new"),
             handlers::goal_event_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_template" / "new"),
             handlers::goal_template_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_template_data" / "new"),
             handlers::goal_template_data_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_template_pattern" / "new"),
             handlers::goal_template_pattern_new,
         ),
         adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
+            warp::path!("public" / "goal" / "import_todo_txt"),
+            handlers::goal_import_todo_txt,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "goal" / "export_todo_txt"),
+            handlers::goal_export_todo_txt,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "goal" / "import_outline"),
+            handlers::goal_import_outline,
+        ),
+        csv_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "goal_data" / "export_csv"),
+            handlers::goal_data_export_csv,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "goal_template" / "preview"),
+            handlers::goal_template_preview,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "goal_template" / "fork"),
+            handlers::goal_template_fork,
+        ),
+        new_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
             warp::path!("public" / "named_entity" / "new"),
             handlers::named_entity_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "named_entity_data" / "new"),
             handlers::named_entity_data_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "named_entity_pattern" / "new"),
             handlers::named_entity_pattern_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "user_generated_code" / "new"),
             handlers::user_generated_code_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "time_utility_function" / "new"),
             handlers::time_utility_function_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "external_event" / "new"),
             handlers::external_event_new,
         ),
-        adapter(
+        new_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "external_event_data" / "new"),
             handlers::external_event_data_new,
         ),
         adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
+            warp::path!("public" / "external_event" / "import_ics"),
+            handlers::external_event_import_ics,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "external_event" / "upsert"),
+            handlers::external_event_upsert,
+        ),
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
             warp::path!("public" / "goal" / "view"),
             handlers::goal_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_data" / "view"),
             handlers::goal_data_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "external_event" / "view"),
             handlers::external_event_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_event" / "view"),
             handlers::goal_event_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_entity_tag" / "view"),
             handlers::goal_entity_tag_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_dependency" / "view"),
             handlers::goal_dependency_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "named_entity" / "view"),
             handlers::named_entity_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "named_entity_data" / "view"),
             handlers::named_entity_data_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "named_entity_pattern" / "view"),
             handlers::named_entity_pattern_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_template" / "view"),
             handlers::goal_template_view,
         ),
-        adapter(
+        page_adapter(
+            config.clone(),
+            db.clone(),
+            auth.clone(),
+            warp::path!("public" / "goal_template" / "published_view"),
+            handlers::goal_template_published_view,
+        ),
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_template_data" / "view"),
             handlers::goal_template_data_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "goal_template_pattern" / "view"),
             handlers::goal_template_pattern_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "external_event_data" / "view"),
             handlers::external_event_data_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "time_utility_function" / "view"),
             handlers::time_utility_function_view,
         ),
-        adapter(
+        page_adapter(
             config.clone(),
             db.clone(),
-            auth_service.clone(),
+            auth.clone(),
             warp::path!("public" / "user_generated_code" / "view"),
             handlers::user_generated_code_view,
         )
@@ -261,9 +506,9 @@ fn with_helper<T: Clone + Send>(t: T) -> impl Filter<Extract = (T,), Error = Inf
 fn adapter<PropsType, ResponseType, F>(
     config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
-    handler: fn(Config, Db, AuthService, PropsType) -> F,
+    handler: fn(Config, Db, Auth, PropsType) -> F,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
 where
     F: Future<Output = Result<ResponseType, TodoAppError>> + Send,
@@ -273,24 +518,104 @@ where
     filter
         .and(with_helper(config))
         .and(with_helper(db))
-        .and(with_helper(auth_service))
+        .and(with_helper(auth))
         .and(warp::body::json())
-        .and_then(move |config, db, auth_service, props| async move {
-            handler(config, db, auth_service, props)
+        .and_then(move |config, db, auth, props| async move {
+            handler(config, db, auth, props)
                 .await
                 .map_err(todo_app_error)
         })
         .map(|x| warp::reply::json(&x))
 }
 
+// this function adapts a handler that creates something to a warp filter
+// it accepts an initial path filter
+// a request with an Idempotency-Key header is only handled once, retries get the first response (see idempotency)
+fn new_adapter<PropsType, ResponseType, F>(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
+    handler: fn(Config, Db, Auth, PropsType) -> F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Future<Output = Result<ResponseType, TodoAppError>> + Send,
+    PropsType: Send + serde::ser::Serialize + serde::de::DeserializeOwned,
+    ResponseType: Send + serde::ser::Serialize + serde::de::DeserializeOwned,
+{
+    filter
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>("idempotency-key"))
+        .and(with_helper(config))
+        .and(with_helper(db))
+        .and(with_helper(auth))
+        .and(warp::body::json())
+        .and_then(
+            move |path: warp::path::FullPath, idempotency_key, config, db, auth, props| async move {
+                idempotency::handle(
+                    config,
+                    db,
+                    auth,
+                    path.as_str(),
+                    idempotency_key,
+                    props,
+                    handler,
+                )
+                .await
+                .map_err(todo_app_error)
+            },
+        )
+        .map(|x| warp::reply::json(&x))
+}
+
+// this function adapts a handler that returns one page of a view to a warp filter
+// it accepts an initial path filter
+// the body is the same json list as an unpaged view, the cursor of the next page is in a header
+fn page_adapter<PropsType, ItemType, F>(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
+    handler: fn(Config, Db, Auth, PropsType) -> F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Future<Output = Result<page::Page<ItemType>, TodoAppError>> + Send,
+    PropsType: Send + serde::de::DeserializeOwned,
+    ItemType: Send + serde::ser::Serialize,
+{
+    filter
+        .and(with_helper(config))
+        .and(with_helper(db))
+        .and(with_helper(auth))
+        .and(warp::body::json())
+        .and_then(move |config, db, auth, props| async move {
+            handler(config, db, auth, props)
+                .await
+                .map_err(todo_app_error)
+        })
+        .map(|page: page::Page<ItemType>| {
+            let mut response = warp::Reply::into_response(warp::reply::json(&page.items));
+            // cursors are base64, so they are always valid header values
+            if let Some(Ok(cursor)) = page.next_cursor.map(|x| HeaderValue::from_str(&x)) {
+                response.headers_mut().insert("X-Next-Cursor", cursor);
+            }
+            // otherwise browsers hide the cursor from pages on another origin
+            response.headers_mut().insert(
+                "Access-Control-Expose-Headers",
+                HeaderValue::from_static("X-Next-Cursor"),
+            );
+            response
+        })
+}
+
 // this function adapts a handler function to a warp filter
 // it accepts an initial path filter
 fn info_adapter<ResponseType, F>(
     config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
-    handler: fn(Config, Db, AuthService) -> F,
+    handler: fn(Config, Db, Auth) -> F,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
 where
     F: Future<Output = Result<ResponseType, TodoAppError>> + Send,
@@ -299,13 +624,213 @@ where
     filter
         .and(with_helper(config))
         .and(with_helper(db))
-        .and(with_helper(auth_service))
-        .and_then(move |config, db, auth_service| async move {
-            handler(config, db, auth_service)
+        .and(with_helper(auth))
+        .and_then(move |config, db, auth| async move {
+            handler(config, db, auth).await.map_err(todo_app_error)
+        })
+        .map(|x| warp::reply::json(&x))
+}
+
+// this function adapts a handler that streams a json response to a warp filter
+// it accepts an initial path filter
+fn stream_adapter<PropsType, F>(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
+    handler: fn(Config, Db, Auth, PropsType) -> F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Future<Output = Result<warp::hyper::Body, TodoAppError>> + Send,
+    PropsType: Send + serde::de::DeserializeOwned,
+{
+    filter
+        .and(with_helper(config))
+        .and(with_helper(db))
+        .and(with_helper(auth))
+        .and(warp::body::json())
+        .and_then(move |config, db, auth, props| async move {
+            handler(config, db, auth, props)
                 .await
                 .map_err(todo_app_error)
         })
-        .map(|x| warp::reply::json(&x))
+        .map(|body| {
+            warp::reply::with_header(
+                warp::reply::Response::new(body),
+                "Content-Type",
+                "application/json",
+            )
+        })
+}
+
+// this function adapts a handler that streams server-sent events to a warp filter
+// it accepts an initial path filter
+// EventSource can only GET a url, so the props are read from the query string,
+// and a reconnecting EventSource sends the id of the last event it saw in the Last-Event-ID header
+fn sse_adapter<F>(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    changes: change_stream::Changes,
+    filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
+    handler: fn(Config, Db, Auth, change_stream::Changes, request::ChangeStreamProps) -> F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Future<Output = Result<warp::hyper::Body, TodoAppError>> + Send,
+{
+    filter
+        .and(warp::get())
+        .and(warp::query::<request::ChangeStreamProps>())
+        .and(warp::header::optional::<i64>("last-event-id"))
+        .map(
+            |props: request::ChangeStreamProps, last_event_id: Option<i64>| {
+                request::ChangeStreamProps {
+                    last_event_id: last_event_id.or(props.last_event_id),
+                    ..props
+                }
+            },
+        )
+        .and(with_helper(config))
+        .and(with_helper(db))
+        .and(with_helper(auth))
+        .and(with_helper(changes))
+        .and_then(move |props, config, db, auth, changes| async move {
+            handler(config, db, auth, changes, props)
+                .await
+                .map_err(todo_app_error)
+        })
+        .map(|body| {
+            let mut resp = warp::reply::Response::new(body);
+            let headers = resp.headers_mut();
+            headers.insert(
+                "Content-Type",
+                HeaderValue::from_static("text/event-stream"),
+            );
+            headers.insert("Cache-Control", HeaderValue::from_static("no-cache"));
+            resp
+        })
+}
+
+// this function adapts a handler that produces a csv file to a warp filter
+// it accepts an initial path filter
+// the file is offered as a download rather than shown in the browser
+fn csv_adapter<PropsType, F>(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
+    handler: fn(Config, Db, Auth, PropsType) -> F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Future<Output = Result<String, TodoAppError>> + Send,
+    PropsType: Send + serde::de::DeserializeOwned,
+{
+    filter
+        .and(with_helper(config))
+        .and(with_helper(db))
+        .and(with_helper(auth))
+        .and(warp::body::json())
+        .and_then(move |config, db, auth, props| async move {
+            handler(config, db, auth, props)
+                .await
+                .map_err(todo_app_error)
+        })
+        .map(|x| warp::reply::with_header(x, "Content-Type", "text/csv; charset=utf-8"))
+        .map(|x| {
+            warp::reply::with_header(
+                x,
+                "Content-Disposition",
+                "attachment; filename=\"export.csv\"",
+            )
+        })
+}
+
+// this function adapts a handler that serves an iCalendar file to a warp filter
+// it accepts an initial path filter, which must extract the secret token
+// calendar clients only know how to GET a url, so there is no json body
+fn ics_adapter<F>(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    filter: impl Filter<Extract = (String,), Error = warp::Rejection> + Clone,
+    handler: fn(Config, Db, Auth, String) -> F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Future<Output = Result<String, TodoAppError>> + Send,
+{
+    filter
+        .and(warp::get())
+        .and(with_helper(config))
+        .and(with_helper(db))
+        .and(with_helper(auth))
+        .and_then(move |token, config, db, auth| async move {
+            handler(config, db, auth, token)
+                .await
+                .map_err(todo_app_error)
+        })
+        .map(|x| warp::reply::with_header(x, "Content-Type", "text/calendar; charset=utf-8"))
+}
+
+// this function adapts the caldav handler to a warp filter
+// it accepts an initial path filter, everything after it is the path of the resource
+// caldav uses its own methods and headers, and answers with xml instead of json
+fn caldav_adapter<F>(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
+    handler: fn(Config, Db, Auth, caldav::Request) -> F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Future<Output = Result<caldav::Response, TodoAppError>> + Send,
+{
+    filter
+        .and(warp::path::tail())
+        .and(warp::method())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::optional::<String>("depth"))
+        .and(warp::header::optional::<String>("if-match"))
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and(warp::body::bytes())
+        .map(
+            |path: warp::path::Tail,
+             method: warp::http::Method,
+             authorization,
+             depth,
+             if_match,
+             if_none_match,
+             body: warp::hyper::body::Bytes| caldav::Request {
+                method: method.to_string(),
+                path: path.as_str().to_owned(),
+                authorization,
+                depth,
+                if_match,
+                if_none_match,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            },
+        )
+        .and(with_helper(config))
+        .and(with_helper(db))
+        .and(with_helper(auth))
+        .and_then(move |request, config, db, auth| async move {
+            match handler(config, db, auth, request).await {
+                Ok(resp) => Ok(resp),
+                // calendar clients only prompt for a password when challenged
+                Err(TodoAppError::Unauthorized) => Ok(caldav::Response {
+                    status: 401,
+                    headers: vec![("WWW-Authenticate", "Basic realm=\"todo-app\"".to_owned())],
+                    body: String::new(),
+                }),
+                Err(e) => Err(todo_app_error(e)),
+            }
+        })
+        .map(|resp: caldav::Response| {
+            let mut builder = warp::http::Response::builder().status(resp.status);
+            for (name, value) in resp.headers {
+                builder = builder.header(name, value);
+            }
+            builder.body(resp.body).unwrap()
+        })
 }
 
 // This function receives a `Rejection` and tries to return a custom
@@ -327,7 +852,15 @@ async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, Infa
         code = StatusCode::METHOD_NOT_ALLOWED;
         message = TodoAppError::MethodNotAllowed;
     } else if let Some(TodoAppErrorRejection(todo_app_error)) = err.find() {
-        code = StatusCode::BAD_REQUEST;
+        code = match todo_app_error {
+            // the change was based on data that has since been replaced, the error carries the current data
+            TodoAppError::GoalDataConflict(_)
+            | TodoAppError::GoalTemplateDataConflict(_)
+            | TodoAppError::NamedEntityDataConflict(_)
+            | TodoAppError::ExternalEventDataConflict(_) => StatusCode::CONFLICT,
+            TodoAppError::LoginAttemptsExceeded => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::BAD_REQUEST,
+        };
         message = todo_app_error.clone();
     } else {
         // We should have expected this... Just log and say its a 500
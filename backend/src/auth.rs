@@ -0,0 +1,224 @@
+// Validates api keys, remembering the answers for a while.
+// Keys are checked by an AuthProvider: the auth service, or our own tables in local mode (see local_auth).
+// Almost every request carries an api key, and checking it each time adds a round trip
+// to every request. So a key the provider accepted is trusted for `ttl` without asking again, and a key it
+// rejected is rejected for `negative_ttl`, which keeps clients with a bad key from hammering the provider.
+// Entries never outlive the key they were made for.
+//
+// If the provider can't be reached, a key it accepted before is still trusted for `stale` past its ttl,
+// so brief auth service outages don't take us down with it. Keys it rejected are never let through this way.
+//
+// The cache holds at most `capacity` keys, the least useful ones are evicted first.
+// Keys are only held hashed, and how well the cache does is logged every few minutes.
+
+use super::utils;
+use async_trait::async_trait;
+use auth_service_api::client::AuthService;
+use auth_service_api::response::{AuthError, Info, User};
+use moka::future::Cache;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// how often the metrics are logged
+static METRICS_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// a key the provider accepted
+pub struct ValidApiKey {
+  pub user: User,
+  // when the key stops being valid, if the provider knows
+  pub expiry: Option<i64>,
+}
+
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+  // ApiKeyNonexistent and ApiKeyUnauthorized mean the key is no good, any other error that the provider failed
+  async fn validate_api_key(&self, api_key: String) -> Result<ValidApiKey, AuthError>;
+  async fn info(&self) -> Result<Info, AuthError>;
+}
+
+// the auth service doesn't tell us when its keys expire, so entries for them live for the ttl
+#[async_trait]
+impl AuthProvider for AuthService {
+  async fn validate_api_key(&self, api_key: String) -> Result<ValidApiKey, AuthError> {
+    let user = self.get_user_by_api_key_if_valid(api_key).await?;
+    Ok(ValidApiKey { user, expiry: None })
+  }
+
+  async fn info(&self) -> Result<Info, AuthError> {
+    AuthService::info(self).await
+  }
+}
+
+#[derive(Clone)]
+enum Entry {
+  // the provider accepted the key
+  Valid {
+    user: User,
+    // trusted without asking until then
+    fresh_until: i64,
+    // may be trusted until then if the provider is unreachable
+    usable_until: i64,
+  },
+  // the provider rejected the key
+  Invalid {
+    error: AuthError,
+    fresh_until: i64,
+  },
+}
+
+#[derive(Default)]
+struct Metrics {
+  // answered by a valid entry
+  hits: AtomicU64,
+  // answered by an invalid entry
+  negative_hits: AtomicU64,
+  // had to ask the provider
+  misses: AtomicU64,
+  // answered by an expired entry because the provider failed
+  stale_hits: AtomicU64,
+  // the provider failed and there was nothing to fall back on
+  errors: AtomicU64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetricsSnapshot {
+  entries: u64,
+  hits: u64,
+  negative_hits: u64,
+  misses: u64,
+  stale_hits: u64,
+  errors: u64,
+}
+
+// cheap to clone, every clone shares the same cache
+#[derive(Clone)]
+pub struct Auth {
+  provider: Arc<dyn AuthProvider>,
+  cache: Cache<String, Entry>,
+  metrics: Arc<Metrics>,
+  ttl: i64,
+  negative_ttl: i64,
+  stale: i64,
+}
+
+impl Auth {
+  pub fn new(
+    provider: Arc<dyn AuthProvider>,
+    capacity: u64,
+    ttl: Duration,
+    negative_ttl: Duration,
+    stale: Duration,
+  ) -> Auth {
+    Auth {
+      provider,
+      // nothing is kept longer than a valid entry may be used
+      cache: Cache::builder()
+        .max_capacity(capacity)
+        .time_to_live(ttl.max(negative_ttl) + stale)
+        .build(),
+      metrics: Arc::new(Metrics::default()),
+      ttl: ttl.as_millis() as i64,
+      negative_ttl: negative_ttl.as_millis() as i64,
+      stale: stale.as_millis() as i64,
+    }
+  }
+
+  pub async fn info(&self) -> Result<Info, AuthError> {
+    self.provider.info().await
+  }
+
+  // makes sure the key isn't accepted from the cache anymore, for when it is cancelled
+  pub async fn forget(&self, api_key: &str) {
+    self.cache.invalidate(&utils::hash_token(api_key)).await;
+  }
+
+  pub async fn get_user_by_api_key_if_valid(&self, api_key: String) -> Result<User, AuthError> {
+    let key = utils::hash_token(&api_key);
+    let now = utils::current_time_millis();
+
+    let cached = self.cache.get(&key).await;
+    match &cached {
+      Some(Entry::Valid {
+        user, fresh_until, ..
+      }) if *fresh_until > now => {
+        self.metrics.hits.fetch_add(1, Ordering::Relaxed);
+        return Ok(user.clone());
+      }
+      Some(Entry::Invalid { error, fresh_until }) if *fresh_until > now => {
+        self.metrics.negative_hits.fetch_add(1, Ordering::Relaxed);
+        return Err(error.clone());
+      }
+      _ => {
+        self.metrics.misses.fetch_add(1, Ordering::Relaxed);
+      }
+    }
+
+    match self.provider.validate_api_key(api_key).await {
+      Ok(ValidApiKey { user, expiry }) => {
+        let expiry = expiry.unwrap_or(i64::MAX);
+        let entry = Entry::Valid {
+          user: user.clone(),
+          fresh_until: (now + self.ttl).min(expiry),
+          usable_until: (now + self.ttl + self.stale).min(expiry),
+        };
+        self.cache.insert(key, entry).await;
+        Ok(user)
+      }
+      // the key is no good
+      Err(error @ (AuthError::ApiKeyNonexistent | AuthError::ApiKeyUnauthorized)) => {
+        let entry = Entry::Invalid {
+          error: error.clone(),
+          fresh_until: now + self.negative_ttl,
+        };
+        self.cache.insert(key, entry).await;
+        Err(error)
+      }
+      // the provider is having trouble, not the key
+      Err(error) => match cached {
+        Some(Entry::Valid {
+          user, usable_until, ..
+        }) if usable_until > now => {
+          self.metrics.stale_hits.fetch_add(1, Ordering::Relaxed);
+          utils::log(utils::Event {
+            msg: error.to_string(),
+            source: Some("auth cache: using stale entry".to_owned()),
+            severity: utils::SeverityKind::Warning,
+          });
+          Ok(user)
+        }
+        _ => {
+          self.metrics.errors.fetch_add(1, Ordering::Relaxed);
+          Err(error)
+        }
+      },
+    }
+  }
+
+  fn metrics(&self) -> MetricsSnapshot {
+    MetricsSnapshot {
+      entries: self.cache.entry_count(),
+      hits: self.metrics.hits.load(Ordering::Relaxed),
+      negative_hits: self.metrics.negative_hits.load(Ordering::Relaxed),
+      misses: self.metrics.misses.load(Ordering::Relaxed),
+      stale_hits: self.metrics.stale_hits.load(Ordering::Relaxed),
+      errors: self.metrics.errors.load(Ordering::Relaxed),
+    }
+  }
+}
+
+// logs the cache's metrics every few minutes. the counts are totals since startup
+pub async fn run_metrics(auth: Auth) {
+  let mut ticker = tokio::time::interval(METRICS_INTERVAL);
+  loop {
+    ticker.tick().await;
+
+    utils::log(utils::Event {
+      msg: auth.metrics(),
+      source: Some("auth cache".to_owned()),
+      severity: utils::SeverityKind::Info,
+    });
+  }
+}
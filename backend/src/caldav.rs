@@ -0,0 +1,539 @@
+// Just enough of RFC 4791 (CalDAV) for desktop calendar clients to read and edit the schedule.
+// This module only knows the wire format, the handler in handlers.rs decides what the resources are.
+//
+// Layout:
+//   /public/caldav/                  the user's principal and calendar home
+//   /public/caldav/calendar/         the one calendar collection
+//   /public/caldav/calendar/<x>.ics  one VEVENT per goal or external event
+
+use super::ical;
+use super::utils;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+pub static HOME_HREF: &str = "/public/caldav/";
+pub static CALENDAR_HREF: &str = "/public/caldav/calendar/";
+
+static DAV_NS: &str = "DAV:";
+static CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+static CALENDARSERVER_NS: &str = "http://calendarserver.org/ns/";
+
+// what has to be escaped in a resource name to make it one path segment
+static SEGMENT: &AsciiSet = &CONTROLS
+  .add(b' ')
+  .add(b'"')
+  .add(b'#')
+  .add(b'%')
+  .add(b'/')
+  .add(b'<')
+  .add(b'>')
+  .add(b'?')
+  .add(b'`')
+  .add(b'{')
+  .add(b'}');
+
+#[derive(Clone, Debug)]
+pub struct CaldavError(pub String);
+
+fn err<T>(msg: impl Into<String>) -> Result<T, CaldavError> {
+  Err(CaldavError(msg.into()))
+}
+
+// everything the handler needs to know about an incoming request
+#[derive(Clone, Debug)]
+pub struct Request {
+  pub method: String,
+  pub path: String,
+  pub authorization: Option<String>,
+  pub depth: Option<String>,
+  pub if_match: Option<String>,
+  pub if_none_match: Option<String>,
+  pub body: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+  pub status: u16,
+  pub headers: Vec<(&'static str, String)>,
+  pub body: String,
+}
+
+impl Response {
+  pub fn empty(status: u16) -> Response {
+    Response {
+      status,
+      headers: vec![],
+      body: String::new(),
+    }
+  }
+
+  pub fn multistatus(body: String) -> Response {
+    Response {
+      status: 207,
+      headers: vec![("Content-Type", "application/xml; charset=utf-8".to_owned())],
+      body,
+    }
+  }
+
+  pub fn calendar(body: String, etag: Option<String>) -> Response {
+    let mut headers = vec![("Content-Type", "text/calendar; charset=utf-8".to_owned())];
+    headers.extend(etag.map(|etag| ("ETag", etag)));
+    Response {
+      status: 200,
+      headers,
+      body,
+    }
+  }
+
+  pub fn options() -> Response {
+    Response {
+      status: 200,
+      headers: vec![
+        ("DAV", "1, calendar-access".to_owned()),
+        (
+          "Allow",
+          "OPTIONS, GET, PUT, DELETE, PROPFIND, REPORT".to_owned(),
+        ),
+      ],
+      body: String::new(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Path {
+  Home,
+  Calendar,
+  // the name of the resource, without the .ics extension
+  Event(String),
+  Unknown,
+}
+
+// the path is relative to the caldav root.
+// resource names are percent-decoded, so that `a%20b.ics` and `a b.ics` are the same resource
+pub fn parse_path(path: &str) -> Path {
+  let path = path.trim_matches('/');
+  if path.is_empty() {
+    return Path::Home;
+  }
+  if path == "calendar" {
+    return Path::Calendar;
+  }
+  match path
+    .strip_prefix("calendar/")
+    .and_then(|name| name.strip_suffix(".ics"))
+    .filter(|name| !name.contains('/'))
+    .and_then(|name| percent_decode_str(name).decode_utf8().ok())
+  {
+    Some(name) if !name.is_empty() && !name.contains('/') => Path::Event(name.into_owned()),
+    _ => Path::Unknown,
+  }
+}
+
+pub fn event_href(name: &str) -> String {
+  format!(
+    "{}{}.ics",
+    CALENDAR_HREF,
+    utf8_percent_encode(name, SEGMENT)
+  )
+}
+
+// clients address resources by absolute path, or sometimes by full url
+fn parse_href(href: &str) -> Option<String> {
+  let path = match href.find("://") {
+    Some(i) => &href[href[i + 3..].find('/').map(|j| i + 3 + j)?..],
+    None => href,
+  };
+  match parse_path(path.strip_prefix(HOME_HREF)?) {
+    Path::Event(name) => Some(name),
+    _ => None,
+  }
+}
+
+// the api key is sent as the password, the username is ignored
+pub fn parse_basic_auth(header: &str) -> Option<String> {
+  use base64::Engine;
+  let (scheme, credentials) = header.trim().split_once(' ')?;
+  if !scheme.eq_ignore_ascii_case("basic") {
+    return None;
+  }
+  let decoded = base64::engine::general_purpose::STANDARD
+    .decode(credentials.trim())
+    .ok()?;
+  let decoded = String::from_utf8(decoded).ok()?;
+  let (_, password) = decoded.split_once(':')?;
+  Some(password.to_owned())
+}
+
+// depth defaults to infinity, which we treat like 1 since there is nothing deeper
+pub fn depth_includes_children(depth: Option<&str>) -> bool {
+  depth.map(|d| d.trim() != "0").unwrap_or(true)
+}
+
+// changes whenever anything about the event that we serve changes
+pub fn etag(event: &ical::Event) -> String {
+  let hash = utils::hash_token(&format!(
+    "{}\n{}\n{}\n{}",
+    event.uid, event.name, event.start_time, event.end_time
+  ));
+  format!("\"{}\"", &hash[..32])
+}
+
+// true if the If-Match / If-None-Match header matches the current etag (None if the resource doesn't exist)
+pub fn etag_matches(header: &str, current: Option<&str>) -> bool {
+  match current {
+    Some(current) => header
+      .split(',')
+      .map(|x| x.trim())
+      .any(|x| x == "*" || x == current),
+    None => false,
+  }
+}
+
+// a property name, (namespace, local name)
+pub type PropName = (String, String);
+
+// which properties were requested, None means all of them
+fn parse_prop(node: roxmltree::Node) -> Option<Vec<PropName>> {
+  let prop = node
+    .children()
+    .find(|n| n.tag_name().namespace() == Some(DAV_NS) && n.tag_name().name() == "prop")?;
+  Some(
+    prop
+      .children()
+      .filter(|n| n.is_element())
+      .map(|n| {
+        (
+          n.tag_name().namespace().unwrap_or_default().to_owned(),
+          n.tag_name().name().to_owned(),
+        )
+      })
+      .collect(),
+  )
+}
+
+// an empty body is the same as asking for every property
+pub fn parse_propfind(body: &str) -> Result<Option<Vec<PropName>>, CaldavError> {
+  if body.trim().is_empty() {
+    return Ok(None);
+  }
+  let doc = roxmltree::Document::parse(body).map_err(|e| CaldavError(e.to_string()))?;
+  Ok(parse_prop(doc.root_element()))
+}
+
+#[derive(Clone, Debug)]
+pub enum Report {
+  CalendarQuery {
+    props: Option<Vec<PropName>>,
+    min_time: Option<i64>,
+    max_time: Option<i64>,
+  },
+  CalendarMultiget {
+    props: Option<Vec<PropName>>,
+    names: Vec<Option<String>>,
+    hrefs: Vec<String>,
+  },
+}
+
+fn parse_utc(value: &str) -> Result<i64, CaldavError> {
+  NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ")
+    .map(|x| Utc.from_utc_datetime(&x).timestamp_millis())
+    .map_err(|_| CaldavError(format!("invalid time: {}", value)))
+}
+
+pub fn parse_report(body: &str) -> Result<Report, CaldavError> {
+  let doc = roxmltree::Document::parse(body).map_err(|e| CaldavError(e.to_string()))?;
+  let root = doc.root_element();
+  if root.tag_name().namespace() != Some(CALDAV_NS) {
+    return err(format!("unsupported report: {}", root.tag_name().name()));
+  }
+
+  match root.tag_name().name() {
+    "calendar-query" => {
+      // only the time range filter is supported, we only ever serve VEVENTs anyway
+      let time_range = root.descendants().find(|n| {
+        n.tag_name().namespace() == Some(CALDAV_NS) && n.tag_name().name() == "time-range"
+      });
+      let (min_time, max_time) = match time_range {
+        Some(t) => (
+          t.attribute("start").map(parse_utc).transpose()?,
+          t.attribute("end").map(parse_utc).transpose()?,
+        ),
+        None => (None, None),
+      };
+      Ok(Report::CalendarQuery {
+        props: parse_prop(root),
+        min_time,
+        max_time,
+      })
+    }
+    "calendar-multiget" => {
+      let hrefs: Vec<String> = root
+        .children()
+        .filter(|n| n.tag_name().namespace() == Some(DAV_NS) && n.tag_name().name() == "href")
+        .map(|n| n.text().unwrap_or_default().trim().to_owned())
+        .collect();
+      Ok(Report::CalendarMultiget {
+        props: parse_prop(root),
+        names: hrefs.iter().map(|h| parse_href(h)).collect(),
+        hrefs,
+      })
+    }
+    name => err(format!("unsupported report: {}", name)),
+  }
+}
+
+fn escape_xml(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+// a property we know how to produce, the value is already serialized xml
+#[derive(Clone, Debug)]
+pub struct Prop {
+  ns: &'static str,
+  name: &'static str,
+  value: String,
+}
+
+fn prop(ns: &'static str, name: &'static str, value: String) -> Prop {
+  Prop { ns, name, value }
+}
+
+fn href(href: &str) -> String {
+  format!("<d:href>{}</d:href>", escape_xml(href))
+}
+
+pub fn home_props() -> Vec<Prop> {
+  vec![
+    prop(DAV_NS, "resourcetype", "<d:collection/>".to_owned()),
+    prop(DAV_NS, "displayname", "Todo App".to_owned()),
+    prop(DAV_NS, "current-user-principal", href(HOME_HREF)),
+    prop(DAV_NS, "principal-URL", href(HOME_HREF)),
+    prop(CALDAV_NS, "calendar-home-set", href(HOME_HREF)),
+  ]
+}
+
+// the ctag changes whenever any event in the calendar changes
+pub fn calendar_props(ctag: &str) -> Vec<Prop> {
+  vec![
+    prop(
+      DAV_NS,
+      "resourcetype",
+      "<d:collection/><c:calendar/>".to_owned(),
+    ),
+    prop(DAV_NS, "displayname", "Todo App".to_owned()),
+    prop(DAV_NS, "current-user-principal", href(HOME_HREF)),
+    prop(
+      DAV_NS,
+      "current-user-privilege-set",
+      "<d:privilege><d:read/></d:privilege><d:privilege><d:write/></d:privilege>".to_owned(),
+    ),
+    prop(
+      CALDAV_NS,
+      "supported-calendar-component-set",
+      "<c:comp name=\"VEVENT\"/>".to_owned(),
+    ),
+    prop(DAV_NS, "getetag", escape_xml(ctag)),
+    prop(CALENDARSERVER_NS, "getctag", escape_xml(ctag)),
+  ]
+}
+
+// calendar-data is only returned by REPORT, not PROPFIND
+pub fn event_props(etag: &str, calendar_data: Option<String>) -> Vec<Prop> {
+  let mut props = vec![
+    prop(DAV_NS, "resourcetype", String::new()),
+    prop(DAV_NS, "getetag", escape_xml(etag)),
+    prop(
+      DAV_NS,
+      "getcontenttype",
+      "text/calendar; charset=utf-8; component=VEVENT".to_owned(),
+    ),
+  ];
+  props.extend(calendar_data.map(|d| prop(CALDAV_NS, "calendar-data", escape_xml(&d))));
+  props
+}
+
+#[derive(Clone, Debug)]
+pub enum Entry {
+  Found { href: String, props: Vec<Prop> },
+  Missing { href: String },
+}
+
+fn prefix(ns: &str) -> &'static str {
+  match ns {
+    x if x == DAV_NS => "d",
+    x if x == CALDAV_NS => "c",
+    x if x == CALENDARSERVER_NS => "cs",
+    _ => "x",
+  }
+}
+
+fn write_prop(out: &mut String, ns: &str, name: &str, value: &str) {
+  let p = prefix(ns);
+  // properties from namespaces we don't know get declared inline
+  let decl = if p == "x" {
+    format!(" xmlns:x=\"{}\"", escape_xml(ns))
+  } else {
+    String::new()
+  };
+  if value.is_empty() {
+    out.push_str(&format!("<{}:{}{}/>", p, name, decl));
+  } else {
+    out.push_str(&format!(
+      "<{}:{}{}>{}</{}:{}>",
+      p, name, decl, value, p, name
+    ));
+  }
+}
+
+// requested properties we don't have are reported with a 404 propstat
+pub fn write_multistatus(entries: &[Entry], requested: Option<&[PropName]>) -> String {
+  let mut out = String::new();
+  out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>");
+  out.push_str(&format!(
+    "<d:multistatus xmlns:d=\"{}\" xmlns:c=\"{}\" xmlns:cs=\"{}\">",
+    DAV_NS, CALDAV_NS, CALENDARSERVER_NS
+  ));
+  for entry in entries {
+    out.push_str("<d:response>");
+    match entry {
+      Entry::Found { href: h, props } => {
+        out.push_str(&href(h));
+
+        let (found, missing): (Vec<&Prop>, Vec<(String, String)>) = match requested {
+          Some(requested) => (
+            props
+              .iter()
+              .filter(|p| {
+                requested
+                  .iter()
+                  .any(|(ns, name)| ns == p.ns && name == p.name)
+              })
+              .collect(),
+            requested
+              .iter()
+              .filter(|(ns, name)| !props.iter().any(|p| ns == p.ns && name == p.name))
+              .cloned()
+              .collect(),
+          ),
+          None => (props.iter().collect(), vec![]),
+        };
+
+        if !found.is_empty() {
+          out.push_str("<d:propstat><d:prop>");
+          for p in found {
+            write_prop(&mut out, p.ns, p.name, &p.value);
+          }
+          out.push_str("</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>");
+        }
+        if !missing.is_empty() {
+          out.push_str("<d:propstat><d:prop>");
+          for (ns, name) in missing {
+            write_prop(&mut out, &ns, &escape_xml(&name), "");
+          }
+          out.push_str("</d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>");
+        }
+      }
+      Entry::Missing { href: h } => {
+        out.push_str(&href(h));
+        out.push_str("<d:status>HTTP/1.1 404 Not Found</d:status>");
+      }
+    }
+    out.push_str("</d:response>");
+  }
+  out.push_str("</d:multistatus>");
+  out
+}
+
+// the rest of this module is for talking to remote CalDAV servers
+
+// what a remote collection says about its state, so we can tell whether anything changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollectionState {
+  pub ctag: Option<String>,
+  pub sync_token: Option<String>,
+}
+
+pub fn collection_state_request() -> String {
+  format!(
+    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+     <d:propfind xmlns:d=\"{}\" xmlns:cs=\"{}\">\
+     <d:prop><cs:getctag/><d:sync-token/></d:prop>\
+     </d:propfind>",
+    DAV_NS, CALENDARSERVER_NS
+  )
+}
+
+// asks for the data of every event overlapping [min_time, max_time)
+pub fn calendar_query_request(min_time: i64, max_time: i64) -> Result<String, CaldavError> {
+  let min_time = ical::format_utc(min_time).map_err(|e| CaldavError(e.0))?;
+  let max_time = ical::format_utc(max_time).map_err(|e| CaldavError(e.0))?;
+  Ok(format!(
+    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+     <c:calendar-query xmlns:d=\"{}\" xmlns:c=\"{}\">\
+     <d:prop><d:getetag/><c:calendar-data/></d:prop>\
+     <c:filter><c:comp-filter name=\"VCALENDAR\"><c:comp-filter name=\"VEVENT\">\
+     <c:time-range start=\"{}\" end=\"{}\"/>\
+     </c:comp-filter></c:comp-filter></c:filter>\
+     </c:calendar-query>",
+    DAV_NS, CALDAV_NS, min_time, max_time
+  ))
+}
+
+// the text of every property with the given name, across all responses of a multistatus
+fn find_texts(body: &str, ns: &str, name: &str) -> Result<Vec<String>, CaldavError> {
+  let doc = roxmltree::Document::parse(body).map_err(|e| CaldavError(e.to_string()))?;
+  let root = doc.root_element();
+  if root.tag_name().namespace() != Some(DAV_NS) || root.tag_name().name() != "multistatus" {
+    return err("expected a multistatus response");
+  }
+  Ok(
+    root
+      .descendants()
+      .filter(|n| n.tag_name().namespace() == Some(ns) && n.tag_name().name() == name)
+      .filter_map(|n| n.text())
+      .map(|t| t.trim().to_owned())
+      .collect(),
+  )
+}
+
+pub fn parse_collection_state(body: &str) -> Result<CollectionState, CaldavError> {
+  Ok(CollectionState {
+    ctag: find_texts(body, CALENDARSERVER_NS, "getctag")?
+      .into_iter()
+      .next(),
+    sync_token: find_texts(body, DAV_NS, "sync-token")?.into_iter().next(),
+  })
+}
+
+// one iCalendar object per event resource
+pub fn parse_calendar_data(body: &str) -> Result<Vec<String>, CaldavError> {
+  find_texts(body, CALDAV_NS, "calendar-data")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decodes_resource_names() {
+    assert_eq!(
+      parse_path("calendar/a%20b.ics"),
+      Path::Event("a b".to_owned())
+    );
+    assert_eq!(
+      parse_path("/calendar/a b.ics/"),
+      Path::Event("a b".to_owned())
+    );
+    assert_eq!(parse_path("calendar/a%2Fb.ics"), Path::Unknown);
+    assert_eq!(parse_path("calendar/%FF.ics"), Path::Unknown);
+    assert_eq!(
+      parse_path(event_href("50% off?").strip_prefix(HOME_HREF).unwrap()),
+      Path::Event("50% off?".to_owned())
+    );
+  }
+}
@@ -0,0 +1,328 @@
+// Pulls the events of every active CalDAV subscription into external_event.
+// A full resync only happens when the remote ctag or sync token changed, or once a day,
+// so that recurring events keep being expanded as the sync window moves forward.
+//
+// Subscriptions are made by users, so unless the operator allows it, the worker only talks to hosts
+// on the public internet. Otherwise anyone could use it to probe our own network.
+
+use super::caldav;
+use super::caldav_subscription_service;
+use super::caldav_sync_service;
+use super::db_types::*;
+use super::handlers;
+use super::ical;
+use super::secret_box::SecretBox;
+use super::utils;
+use super::Db;
+use deadpool_postgres::PoolError;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use warp::hyper::client::connect::dns::Name;
+
+// how far into the past and future events are pulled
+static SYNC_PAST: i64 = 30 * 24 * 60 * 60 * 1000;
+static SYNC_FUTURE: i64 = 365 * 24 * 60 * 60 * 1000;
+
+// resync even if the server says nothing changed after this long
+static MAX_SYNC_AGE: i64 = 24 * 60 * 60 * 1000;
+
+// how many redirects are followed, the same as reqwest's default
+static MAX_REDIRECTS: usize = 10;
+
+// looks up a host like the system resolver does, but drops every address that isn't public
+async fn lookup_public(host: &str) -> Result<Vec<SocketAddr>, String> {
+  let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+    .await
+    .map_err(|e| e.to_string())?
+    .filter(|x| utils::is_public_ip(x.ip()))
+    .collect();
+  if addrs.is_empty() {
+    return Err(format!("{} has no public address", host));
+  }
+  Ok(addrs)
+}
+
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+  fn resolve(&self, name: Name) -> Resolving {
+    Box::pin(async move {
+      let addrs = lookup_public(name.as_str()).await?;
+      Ok(Box::new(addrs.into_iter()) as Addrs)
+    })
+  }
+}
+
+// urls with an ip address for a host are never resolved, so they are checked here instead
+pub fn check_host(url: &reqwest::Url) -> Result<(), String> {
+  let host = url.host_str().ok_or("the url has no host")?;
+  match host
+    .trim_start_matches('[')
+    .trim_end_matches(']')
+    .parse::<IpAddr>()
+  {
+    Ok(ip) if !utils::is_public_ip(ip) => Err(format!("{} is not a public address", ip)),
+    _ => Ok(()),
+  }
+}
+
+// whether a subscription to the url could be synced, so that users find out when they subscribe.
+// the worker checks again every time it connects, as what a host resolves to can change.
+// webhook urls are checked the same way
+pub async fn check_url(url: &str, allow_private_addresses: bool) -> Result<(), String> {
+  let url = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+  if url.scheme() != "https" && url.scheme() != "http" {
+    return Err("the url must be http or https".to_owned());
+  }
+  if allow_private_addresses {
+    return Ok(());
+  }
+  check_host(&url)?;
+  // hosts that don't resolve are left for the worker to report
+  if let Some(host) = url.domain() {
+    if let Ok(addrs) = tokio::net::lookup_host((host, 0)).await {
+      let addrs: Vec<SocketAddr> = addrs.collect();
+      if !addrs.is_empty() && !addrs.iter().any(|x| utils::is_public_ip(x.ip())) {
+        return Err(format!("{} has no public address", host));
+      }
+    }
+  }
+  Ok(())
+}
+
+// a client that only connects to public addresses, also after redirects, unless private ones are allowed.
+// urls with an ip address for a host still need to go through check_host before each request
+pub fn client(timeout: Duration, allow_private_addresses: bool) -> reqwest::Client {
+  let mut client = reqwest::Client::builder().timeout(timeout);
+  if !allow_private_addresses {
+    client =
+      client
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(reqwest::redirect::Policy::custom(|attempt| {
+          if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+          } else if let Err(e) = check_host(attempt.url()) {
+            attempt.error(e)
+          } else {
+            attempt.follow()
+          }
+        }));
+  }
+  client.build().unwrap()
+}
+
+pub async fn run(db: Db, interval: Duration, secret_box: SecretBox, allow_private_addresses: bool) {
+  let client = client(Duration::from_secs(60), allow_private_addresses);
+
+  let mut ticker = tokio::time::interval(interval);
+  loop {
+    ticker.tick().await;
+
+    let subscriptions: Result<_, PoolError> = async {
+      let con = &mut **db.get().await?;
+      Ok(caldav_subscription_service::get_recent_active(con).await?)
+    }
+    .await;
+
+    match subscriptions {
+      Ok(subscriptions) => {
+        for subscription in subscriptions {
+          sync(
+            &db,
+            &client,
+            &secret_box,
+            allow_private_addresses,
+            subscription,
+          )
+          .await;
+        }
+      }
+      Err(e) => utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("caldav sync".to_owned()),
+        severity: utils::SeverityKind::Error,
+      }),
+    }
+  }
+}
+
+// failures are recorded, so the user can see why their calendar isn't updating
+async fn sync(
+  db: &Db,
+  client: &reqwest::Client,
+  secret_box: &SecretBox,
+  allow_private_addresses: bool,
+  subscription: CaldavSubscription,
+) {
+  if let Err(msg) = try_sync(
+    db,
+    client,
+    secret_box,
+    allow_private_addresses,
+    &subscription,
+  )
+  .await
+  {
+    utils::log(utils::Event {
+      msg: msg.clone(),
+      source: Some(format!("caldav sync: {}", subscription.url)),
+      severity: utils::SeverityKind::Info,
+    });
+
+    let result: Result<_, PoolError> = async {
+      let con = &mut **db.get().await?;
+      Ok(
+        caldav_sync_service::add(
+          con,
+          subscription.creator_user_id,
+          subscription.caldav_subscription_id,
+          None,
+          None,
+          0,
+          Some(msg),
+        )
+        .await?,
+      )
+    }
+    .await;
+
+    if let Err(e) = result {
+      utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("caldav sync".to_owned()),
+        severity: utils::SeverityKind::Error,
+      });
+    }
+  }
+}
+
+async fn try_sync(
+  db: &Db,
+  client: &reqwest::Client,
+  secret_box: &SecretBox,
+  allow_private_addresses: bool,
+  subscription: &CaldavSubscription,
+) -> Result<(), String> {
+  let now = utils::current_time_millis();
+
+  if !allow_private_addresses {
+    check_host(&reqwest::Url::parse(&subscription.url).map_err(|e| e.to_string())?)?;
+  }
+  let password = secret_box.open(&subscription.encrypted_password)?;
+
+  let body = request(
+    client,
+    subscription,
+    &password,
+    "PROPFIND",
+    "0",
+    caldav::collection_state_request(),
+  )
+  .await?;
+  let state = caldav::parse_collection_state(&body).map_err(|e| e.0)?;
+
+  let last_sync = {
+    let con = &mut **db.get().await.map_err(|e| e.to_string())?;
+    caldav_sync_service::get_recent_by_caldav_subscription_id(
+      con,
+      subscription.caldav_subscription_id,
+    )
+    .await
+    .map_err(|e| e.to_string())?
+  };
+
+  // servers without ctags or sync tokens are resynced every time
+  if let Some(last_sync) = last_sync {
+    if last_sync.error.is_none()
+      && (state.ctag.is_some() || state.sync_token.is_some())
+      && last_sync.ctag == state.ctag
+      && last_sync.sync_token == state.sync_token
+      && now - last_sync.creation_time < MAX_SYNC_AGE
+    {
+      return Ok(());
+    }
+  }
+
+  let min_time = now - SYNC_PAST;
+  let max_time = now + SYNC_FUTURE;
+
+  let body = request(
+    client,
+    subscription,
+    &password,
+    "REPORT",
+    "1",
+    caldav::calendar_query_request(min_time, max_time).map_err(|e| e.0)?,
+  )
+  .await?;
+
+  let mut events = vec![];
+  for data in caldav::parse_calendar_data(&body).map_err(|e| e.0)? {
+    for event in ical::parse_events(&data, min_time, max_time).map_err(|e| e.0)? {
+      events.push((event.uid, event.name, event.start_time, event.end_time));
+    }
+  }
+  let event_count = events.len() as i64;
+
+  let con = &mut **db.get().await.map_err(|e| e.to_string())?;
+
+  let mut sp = con.transaction().await.map_err(|e| e.to_string())?;
+
+  // the collection url is the source, so each subscription has its own namespace of uids
+  handlers::sync_external_events(
+    &mut sp,
+    subscription.creator_user_id,
+    &subscription.url,
+    events,
+    Some((min_time, max_time)),
+  )
+  .await
+  .map_err(|e| e.to_string())?;
+
+  caldav_sync_service::add(
+    &mut sp,
+    subscription.creator_user_id,
+    subscription.caldav_subscription_id,
+    state.ctag,
+    state.sync_token,
+    event_count,
+    None,
+  )
+  .await
+  .map_err(|e| e.to_string())?;
+
+  sp.commit().await.map_err(|e| e.to_string())?;
+
+  Ok(())
+}
+
+// sends a WebDAV request to the subscribed collection, expecting a multistatus back
+async fn request(
+  client: &reqwest::Client,
+  subscription: &CaldavSubscription,
+  password: &str,
+  method: &str,
+  depth: &str,
+  body: String,
+) -> Result<String, String> {
+  let resp = client
+    .request(
+      reqwest::Method::from_bytes(method.as_bytes()).unwrap(),
+      &subscription.url,
+    )
+    .basic_auth(&subscription.username, Some(password))
+    .header("Depth", depth)
+    .header("Content-Type", "application/xml; charset=utf-8")
+    .body(body)
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  if resp.status().as_u16() != 207 {
+    return Err(format!("{} returned {}", method, resp.status()));
+  }
+
+  resp.text().await.map_err(|e| e.to_string())
+}
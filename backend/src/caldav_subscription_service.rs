@@ -0,0 +1,169 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for CaldavSubscription {
+  // select * from caldav_subscription order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> CaldavSubscription {
+    CaldavSubscription {
+      caldav_subscription_id: row.get("caldav_subscription_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      url: row.get("url"),
+      username: row.get("username"),
+      encrypted_password: row.get("encrypted_password"),
+      active: row.get("active"),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  url: String,
+  username: String,
+  encrypted_password: Vec<u8>,
+  active: bool,
+) -> Result<CaldavSubscription, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let caldav_subscription_id = con
+    .query_one(
+      "INSERT INTO
+       caldav_subscription(
+           creation_time,
+           creator_user_id,
+           url,
+           username,
+           encrypted_password,
+           active
+       )
+       VALUES($1, $2, $3, $4, $5, $6)
+       RETURNING caldav_subscription_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &url,
+        &username,
+        &encrypted_password,
+        &active,
+      ],
+    )
+    .await?
+    .get(0);
+
+  // return caldav_subscription
+  Ok(CaldavSubscription {
+    caldav_subscription_id,
+    creation_time,
+    creator_user_id,
+    url,
+    username,
+    encrypted_password,
+    active,
+  })
+}
+
+pub async fn get_by_caldav_subscription_id(
+  con: &mut impl GenericClient,
+  caldav_subscription_id: i64,
+) -> Result<Option<CaldavSubscription>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM caldav_subscription WHERE caldav_subscription_id=$1",
+      &[&caldav_subscription_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+pub async fn get_by_caldav_subscription_ids(
+  con: &mut impl GenericClient,
+  caldav_subscription_ids: &[i64],
+) -> Result<Vec<CaldavSubscription>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM caldav_subscription WHERE caldav_subscription_id = ANY($1)";
+  let results = con
+    .query(sql, &[&caldav_subscription_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// every subscription of every user that should currently be synced
+pub async fn get_recent_active(
+  con: &mut impl GenericClient,
+) -> Result<Vec<CaldavSubscription>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT * FROM recent_caldav_subscription WHERE active ORDER BY caldav_subscription_id",
+      &[],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("cs.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: todo_app_service_api::request::CaldavSubscriptionViewProps,
+) -> Result<Page<CaldavSubscription>, tokio_postgres::Error> {
+  let sql = [
+    if props.only_recent {
+      "SELECT cs.* FROM recent_caldav_subscription cs"
+    } else {
+      "SELECT cs.* FROM caldav_subscription cs"
+    },
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR cs.caldav_subscription_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR cs.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR cs.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR cs.creator_user_id = ANY($4))",
+    " AND ($5::text[]   IS NULL OR cs.url = ANY($5))",
+    " AND ($6::bool     IS NULL OR cs.active = $6)",
+    " AND cs.creator_user_id = $7",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "cs", "caldav_subscription_id", 8);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.caldav_subscription_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &props.url,
+        &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}
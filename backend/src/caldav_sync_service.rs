@@ -0,0 +1,141 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for CaldavSync {
+  // select * from caldav_sync order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> CaldavSync {
+    CaldavSync {
+      caldav_sync_id: row.get("caldav_sync_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      caldav_subscription_id: row.get("caldav_subscription_id"),
+      ctag: row.get("ctag"),
+      sync_token: row.get("sync_token"),
+      event_count: row.get("event_count"),
+      error: row.get("error"),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  caldav_subscription_id: i64,
+  ctag: Option<String>,
+  sync_token: Option<String>,
+  event_count: i64,
+  error: Option<String>,
+) -> Result<CaldavSync, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let caldav_sync_id = con
+    .query_one(
+      "INSERT INTO
+       caldav_sync(
+           creation_time,
+           creator_user_id,
+           caldav_subscription_id,
+           ctag,
+           sync_token,
+           event_count,
+           error
+       )
+       VALUES($1, $2, $3, $4, $5, $6, $7)
+       RETURNING caldav_sync_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &caldav_subscription_id,
+        &ctag,
+        &sync_token,
+        &event_count,
+        &error,
+      ],
+    )
+    .await?
+    .get(0);
+
+  // return caldav_sync
+  Ok(CaldavSync {
+    caldav_sync_id,
+    creation_time,
+    creator_user_id,
+    caldav_subscription_id,
+    ctag,
+    sync_token,
+    event_count,
+    error,
+  })
+}
+
+pub async fn get_recent_by_caldav_subscription_id(
+  con: &mut impl GenericClient,
+  caldav_subscription_id: i64,
+) -> Result<Option<CaldavSync>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_caldav_sync WHERE caldav_subscription_id=$1",
+      &[&caldav_subscription_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("cs.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: todo_app_service_api::request::CaldavSyncViewProps,
+) -> Result<Page<CaldavSync>, tokio_postgres::Error> {
+  let sql = [
+    if props.only_recent {
+      "SELECT cs.* FROM recent_caldav_sync cs"
+    } else {
+      "SELECT cs.* FROM caldav_sync cs"
+    },
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR cs.caldav_sync_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR cs.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR cs.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR cs.creator_user_id = ANY($4))",
+    " AND ($5::bigint[] IS NULL OR cs.caldav_subscription_id = ANY($5))",
+    " AND cs.creator_user_id = $6",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "cs", "caldav_sync_id", 7);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.caldav_sync_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &props.caldav_subscription_id,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}
@@ -0,0 +1,67 @@
+use super::db_types::*;
+use super::utils::current_time_millis;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for CalendarFeed {
+  // select * from calendar_feed order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> CalendarFeed {
+    CalendarFeed {
+      calendar_feed_id: row.get("calendar_feed_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      token_hash: row.get("token_hash"),
+      active: row.get("active"),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  token_hash: String,
+  active: bool,
+) -> Result<CalendarFeed, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let calendar_feed_id = con
+    .query_one(
+      "INSERT INTO
+       calendar_feed(
+           creation_time,
+           creator_user_id,
+           token_hash,
+           active
+       )
+       VALUES($1, $2, $3, $4)
+       RETURNING calendar_feed_id
+      ",
+      &[&creation_time, &creator_user_id, &token_hash, &active],
+    )
+    .await?
+    .get(0);
+
+  // return calendar_feed
+  Ok(CalendarFeed {
+    calendar_feed_id,
+    creation_time,
+    creator_user_id,
+    token_hash,
+    active,
+  })
+}
+
+// only the most recent token of each user is valid, older ones have been rotated out
+pub async fn get_active_by_token_hash(
+  con: &mut impl GenericClient,
+  token_hash: &str,
+) -> Result<Option<CalendarFeed>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_calendar_feed WHERE token_hash=$1 AND active",
+      &[&token_hash],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
@@ -0,0 +1,89 @@
+use super::account_service;
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for ChangeEvent {
+  // select * from change_event order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> ChangeEvent {
+    ChangeEvent {
+      change_event_id: row.get("change_event_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      table_name: row.get("table_name"),
+      row_id: row.get("row_id"),
+    }
+  }
+}
+
+// the user's events that came after change_event_id, up to and including until, oldest first
+pub async fn get_after(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  change_event_id: i64,
+  until: i64,
+  limit: i64,
+) -> Result<Vec<ChangeEvent>, tokio_postgres::Error> {
+  let result = con
+    .query(
+      "SELECT * FROM change_event
+       WHERE creator_user_id = $1 AND change_event_id > $2 AND change_event_id <= $3
+       ORDER BY change_event_id
+       LIMIT $4
+      ",
+      &[&creator_user_id, &change_event_id, &until, &limit],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(result)
+}
+
+// the greatest id a committed event of the user can have, see account_service::high_water_marks.
+// con must not be in a transaction
+pub async fn get_high_water_mark(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+) -> Result<i64, tokio_postgres::Error> {
+  let marks = account_service::high_water_marks(con, creator_user_id, &["change_event"]).await?;
+  Ok(marks.into_iter().next().unwrap_or(0))
+}
+
+// the id of the user's newest event that was pruned, if any were
+pub async fn get_last_pruned_id(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+) -> Result<Option<i64>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT change_event_id FROM change_event_pruned WHERE creator_user_id = $1",
+      &[&creator_user_id],
+    )
+    .await?
+    .map(|row| row.get(0));
+
+  Ok(result)
+}
+
+// deletes the events made before creation_time, and records how far each user's events were pruned.
+// returns how many users had events deleted
+pub async fn delete_before(
+  con: &mut impl GenericClient,
+  creation_time: i64,
+) -> Result<u64, tokio_postgres::Error> {
+  con
+    .execute(
+      "WITH deleted AS (
+         DELETE FROM change_event WHERE creation_time < $1
+         RETURNING creator_user_id, change_event_id
+       )
+       INSERT INTO change_event_pruned(creator_user_id, change_event_id)
+       SELECT creator_user_id, max(change_event_id) FROM deleted GROUP BY creator_user_id
+       ON CONFLICT (creator_user_id) DO UPDATE
+       SET change_event_id = greatest(change_event_pruned.change_event_id, excluded.change_event_id)
+      ",
+      &[&creation_time],
+    )
+    .await
+}
@@ -0,0 +1,204 @@
+// Pushes the user's changes to clients as server-sent events.
+// Every insert into one of a user's tables is recorded in change_event by a trigger, which also sends a NOTIFY
+// on the change_event channel. Each backend instance LISTENs on its own connection and passes the notifications
+// on to its open streams, which then read the new events from the table.
+// So a stream hears about changes made through any instance, and can resume from the id of the last event it saw.
+//
+// Every event is sent as `id: <changeEventId>`, `event: change` and `data: ` followed by the json ChangeEvent.
+// If the events after the id a stream resumes from were already pruned, a `reset` event is sent first,
+// and the client should reload everything it shows.
+
+use super::change_event_service;
+use super::db_types::*;
+use super::utils;
+use super::Db;
+use deadpool_postgres::PoolError;
+use futures_util::StreamExt;
+use std::time::Duration;
+use todo_app_service_api::response;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_postgres::{AsyncMessage, NoTls};
+use warp::hyper::body::{Bytes, Sender};
+
+// how many notices a slow stream may fall behind by, after that it just catches up from the table
+static BUFFER: usize = 1024;
+// the most events read from the table at once
+static BATCH_SIZE: i64 = 1000;
+// how often a comment is sent on an idle stream, so that proxies keep it open and we notice closed clients
+static KEEP_ALIVE: Duration = Duration::from_secs(15);
+// events are kept this long, streams that were gone for longer have to reload everything
+static RETENTION: i64 = 7 * 24 * 60 * 60 * 1000;
+// how often old events are pruned
+static PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Debug)]
+pub enum Notice {
+  // a row was inserted into one of the user's tables
+  Changed { creator_user_id: i64 },
+  // notifications may have been missed while we weren't listening, every stream should catch up
+  Resync,
+}
+
+// every open stream subscribes to this
+pub type Changes = broadcast::Sender<Notice>;
+
+pub fn channel() -> Changes {
+  broadcast::channel(BUFFER).0
+}
+
+// listens for notifications on a connection of its own, reconnecting if it is lost
+pub async fn run(config: tokio_postgres::Config, changes: Changes) {
+  loop {
+    if let Err(e) = listen(&config, &changes).await {
+      utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("change stream".to_owned()),
+        severity: utils::SeverityKind::Error,
+      });
+    }
+
+    // sleep for 5 seconds
+    tokio::time::sleep(Duration::from_secs(5)).await;
+  }
+}
+
+async fn listen(
+  config: &tokio_postgres::Config,
+  changes: &Changes,
+) -> Result<(), tokio_postgres::Error> {
+  let (client, mut connection) = config.connect(NoTls).await?;
+
+  // the connection has to be polled for anything to be sent or received,
+  // notifications are only seen by polling it ourselves
+  let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+  let driver = tokio::spawn(async move {
+    let mut messages = futures_util::stream::poll_fn(move |cx| connection.poll_message(cx));
+    while let Some(message) = messages.next().await {
+      if tx.send(message?).is_err() {
+        break;
+      }
+    }
+    Ok::<_, tokio_postgres::Error>(())
+  });
+
+  client.batch_execute("LISTEN change_event").await?;
+
+  // anything may have happened while we weren't listening
+  let _ = changes.send(Notice::Resync);
+
+  while let Some(message) = rx.recv().await {
+    if let AsyncMessage::Notification(notification) = message {
+      if let Ok(creator_user_id) = notification.payload().parse() {
+        // nobody may be listening, that's fine
+        let _ = changes.send(Notice::Changed { creator_user_id });
+      }
+    }
+  }
+
+  // the connection is gone
+  match driver.await {
+    Ok(result) => result,
+    Err(_) => Ok(()),
+  }
+}
+
+// deletes the events older than the retention period
+pub async fn run_prune(db: Db) {
+  let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+  loop {
+    ticker.tick().await;
+
+    let result: Result<_, PoolError> = async {
+      let con = &mut **db.get().await?;
+      Ok(change_event_service::delete_before(con, utils::current_time_millis() - RETENTION).await?)
+    }
+    .await;
+
+    if let Err(e) = result {
+      utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("change event pruning".to_owned()),
+        severity: utils::SeverityKind::Error,
+      });
+    }
+  }
+}
+
+fn frame(change_event: ChangeEvent) -> Bytes {
+  let data = serde_json::to_string(&response::ChangeEvent {
+    change_event_id: change_event.change_event_id,
+    creation_time: change_event.creation_time,
+    creator_user_id: change_event.creator_user_id,
+    table_name: change_event.table_name,
+    row_id: change_event.row_id,
+  })
+  .unwrap();
+
+  Bytes::from(format!(
+    "id: {}\nevent: change\ndata: {}\n\n",
+    change_event.change_event_id, data
+  ))
+}
+
+// writes the user's events after last_event_id to out, and then every new one as it happens.
+// a connection is only checked out while reading events, not for the lifetime of the stream.
+// returns once the client is gone
+pub async fn stream(
+  db: Db,
+  mut notices: broadcast::Receiver<Notice>,
+  creator_user_id: i64,
+  mut last_event_id: i64,
+  reset: bool,
+  mut out: Sender,
+) -> Result<(), PoolError> {
+  if reset {
+    let reset = Bytes::from("event: reset\ndata: {}\n\n");
+    if out.send_data(reset).await.is_err() {
+      return Ok(());
+    }
+  }
+
+  let mut keep_alive = tokio::time::interval(KEEP_ALIVE);
+  loop {
+    // send everything since the last event that was sent
+    loop {
+      let events = {
+        let con = &mut **db.get().await?;
+        // events of writes that are still in progress may have smaller ids than committed ones,
+        // so only the events up to the mark are certain not to be followed by a smaller one
+        let until = change_event_service::get_high_water_mark(con, creator_user_id).await?;
+        change_event_service::get_after(con, creator_user_id, last_event_id, until, BATCH_SIZE)
+          .await?
+      };
+      let done = (events.len() as i64) < BATCH_SIZE;
+      for event in events {
+        last_event_id = event.change_event_id;
+        if out.send_data(frame(event)).await.is_err() {
+          return Ok(());
+        }
+      }
+      if done {
+        break;
+      }
+    }
+
+    // wait until there may be something new for this user
+    loop {
+      tokio::select! {
+        notice = notices.recv() => match notice {
+          Ok(Notice::Changed { creator_user_id: id }) if id != creator_user_id => continue,
+          // if we fell behind, some of the notices we missed may have been for us
+          Ok(_) | Err(RecvError::Lagged(_)) => break,
+          Err(RecvError::Closed) => return Ok(()),
+        },
+        _ = keep_alive.tick() => {
+          // comments are ignored by clients, but writing one tells us whether the client is still there
+          if out.send_data(Bytes::from(": keep-alive\n\n")).await.is_err() {
+            return Ok(());
+          }
+        }
+      }
+    }
+  }
+}
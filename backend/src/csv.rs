@@ -0,0 +1,36 @@
+// Just enough of RFC 4180 to hand data to a spreadsheet.
+
+// fields that need quoting get it, quotes inside are doubled
+fn escape(field: &str) -> String {
+  if field.contains([',', '"', '\r', '\n']) || field.trim() != field {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_owned()
+  }
+}
+
+// spreadsheets run text starting with these as a formula, so user text gets a leading quote
+pub fn text(s: &str) -> String {
+  if s.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+    format!("'{}", s)
+  } else {
+    s.to_owned()
+  }
+}
+
+#[derive(Default)]
+pub struct Writer {
+  out: String,
+}
+
+impl Writer {
+  pub fn row<S: AsRef<str>>(&mut self, fields: impl IntoIterator<Item = S>) {
+    let fields: Vec<String> = fields.into_iter().map(|x| escape(x.as_ref())).collect();
+    self.out.push_str(&fields.join(","));
+    self.out.push_str("\r\n");
+  }
+
+  pub fn finish(self) -> String {
+    self.out
+  }
+}
@@ -1,14 +1,21 @@
+use serde::{Deserialize, Serialize};
 use todo_app_service_api::request::GoalDataStatusKind;
 use todo_app_service_api::request::NamedEntityKind;
+use todo_app_service_api::request::ReminderChannelKind;
+use todo_app_service_api::request::ReminderDeliveryStatus;
+use todo_app_service_api::request::ReminderKind;
+use todo_app_service_api::request::WebhookEventKind;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Goal {
   pub goal_id: i64,
   pub creation_time: i64,
   pub creator_user_id: i64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GoalData {
   pub goal_data_id: i64,
   pub creation_time: i64,
@@ -20,7 +27,8 @@ pub struct GoalData {
   pub status: GoalDataStatusKind,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GoalEvent {
   pub goal_event_id: i64,
   pub creation_time: i64,
@@ -31,14 +39,16 @@ pub struct GoalEvent {
   pub active: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GoalTemplate {
   pub goal_template_id: i64,
   pub creation_time: i64,
   pub creator_user_id: i64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GoalTemplateData {
   pub goal_template_data_id: i64,
   pub creation_time: i64,
@@ -48,10 +58,12 @@ pub struct GoalTemplateData {
   pub utility: i64,
   pub user_generated_code_id: i64,
   pub duration_estimate: Option<i64>,
+  pub published: bool,
   pub active: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GoalTemplatePattern {
   pub goal_template_pattern_id: i64,
   pub creation_time: i64,
@@ -61,7 +73,8 @@ pub struct GoalTemplatePattern {
   pub active: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GoalDependency {
   pub goal_dependency_id: i64,
   pub creation_time: i64,
@@ -72,14 +85,16 @@ pub struct GoalDependency {
 }
 
 // essentially a tag
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct NamedEntity {
   pub named_entity_id: i64,
   pub creation_time: i64,
   pub creator_user_id: i64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct NamedEntityData {
   pub named_entity_data_id: i64,
   pub creation_time: i64,
@@ -90,7 +105,8 @@ pub struct NamedEntityData {
   pub active: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct NamedEntityPattern {
   pub named_entity_pattern_id: i64,
   pub creation_time: i64,
@@ -100,7 +116,8 @@ pub struct NamedEntityPattern {
   pub active: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct GoalEntityTag {
   pub goal_entity_tag_id: i64,
   pub creation_time: i64,
@@ -110,7 +127,8 @@ pub struct GoalEntityTag {
   pub active: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UserGeneratedCode {
   pub user_generated_code_id: i64,
   pub creation_time: i64,
@@ -121,7 +139,8 @@ pub struct UserGeneratedCode {
 }
 
 // made seperate to avoid having to regenerate it
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TimeUtilityFunction {
   pub time_utility_function_id: i64,
   pub creation_time: i64,
@@ -130,14 +149,19 @@ pub struct TimeUtilityFunction {
   pub utils: Vec<i64>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ExternalEvent {
   pub external_event_id: i64,
   pub creation_time: i64,
   pub creator_user_id: i64,
+  pub source: Option<String>,
+  pub external_uid: Option<String>,
+  pub resource_name: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ExternalEventData {
   pub external_event_data_id: i64,
   pub creation_time: i64,
@@ -148,3 +172,152 @@ pub struct ExternalEventData {
   pub end_time: i64,
   pub active: bool,
 }
+
+// a secret url that serves a user's schedule as an iCalendar feed
+#[derive(Clone, Debug)]
+pub struct CalendarFeed {
+  pub calendar_feed_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub token_hash: String,
+  pub active: bool,
+}
+
+// a remote CalDAV collection that is pulled into external_event
+#[derive(Clone, Debug)]
+pub struct CaldavSubscription {
+  pub caldav_subscription_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub url: String,
+  pub username: String,
+  // see secret_box
+  pub encrypted_password: Vec<u8>,
+  pub active: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct CaldavSync {
+  pub caldav_sync_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub caldav_subscription_id: i64,
+  pub ctag: Option<String>,
+  pub sync_token: Option<String>,
+  pub event_count: i64,
+  pub error: Option<String>,
+}
+
+// a url that is notified about changes to a user's goals and events
+#[derive(Clone, Debug)]
+pub struct Webhook {
+  pub webhook_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub url: String,
+  pub secret: String,
+  pub event_kinds: Vec<WebhookEventKind>,
+  pub active: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookEvent {
+  pub webhook_event_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub webhook_id: i64,
+  pub kind: WebhookEventKind,
+  pub payload: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookDelivery {
+  pub webhook_delivery_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub webhook_event_id: i64,
+  pub status_code: Option<i64>,
+  pub error: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReminderSetting {
+  pub reminder_setting_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub goal_event_offset: Option<i64>,
+  pub utility_threshold: Option<i64>,
+  pub utility_offset: Option<i64>,
+  pub channels: Vec<ReminderChannelKind>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Reminder {
+  pub reminder_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub kind: ReminderKind,
+  pub goal_id: i64,
+  pub goal_event_id: Option<i64>,
+  pub due_time: i64,
+  pub fire_time: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReminderDelivery {
+  pub reminder_delivery_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub reminder_id: i64,
+  pub channel: ReminderChannelKind,
+  pub attempt: i64,
+  pub status: ReminderDeliveryStatus,
+  pub error: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MailSetting {
+  pub mail_setting_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub address: String,
+  pub digest_time: Option<i64>,
+  pub tz: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChangeEvent {
+  pub change_event_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub table_name: String,
+  pub row_id: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct IdempotencyKey {
+  pub idempotency_key_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub idempotency_key: String,
+  pub request_hash: String,
+  pub response: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LocalUser {
+  pub local_user_id: i64,
+  pub creation_time: i64,
+  pub email: String,
+  pub password_hash: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct LocalApiKey {
+  pub local_api_key_id: i64,
+  pub creation_time: i64,
+  pub creator_user_id: i64,
+  pub key_hash: String,
+  pub duration: i64,
+  pub active: bool,
+}
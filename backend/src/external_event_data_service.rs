@@ -1,5 +1,7 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use std::convert::From;
+use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for ExternalEventData {
@@ -80,10 +82,82 @@ pub async fn get_by_external_event_data_id(
   Ok(result)
 }
 
+pub async fn get_recent_active(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+) -> Result<Vec<ExternalEventData>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT eed.* FROM recent_external_event_data eed
+       WHERE eed.creator_user_id = $1
+       AND eed.active
+       ORDER BY eed.start_time
+      ",
+      &[&creator_user_id],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the current data of every event that was synced from the given source, keyed by external uid
+pub async fn get_recent_by_source(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  source: &str,
+) -> Result<Vec<(String, ExternalEventData)>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT eed.*, ee.external_uid FROM recent_external_event_data eed
+       INNER JOIN external_event ee ON ee.external_event_id = eed.external_event_id
+       WHERE ee.creator_user_id = $1
+       AND ee.source = $2
+       ORDER BY eed.external_event_data_id
+      ",
+      &[&creator_user_id, &source],
+    )
+    .await?
+    .into_iter()
+    .map(|row| (row.get("external_uid"), row.into()))
+    .collect();
+
+  Ok(results)
+}
+
+pub async fn get_recent_by_external_event_id(
+  con: &mut impl GenericClient,
+  external_event_id: i64,
+) -> Result<Option<ExternalEventData>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_external_event_data WHERE external_event_id=$1",
+      &[&external_event_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("eed.creation_time"),
+    request::ViewSortKind::Name => Some("eed.name"),
+    request::ViewSortKind::StartTime => Some("eed.start_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::ExternalEventDataViewProps,
-) -> Result<Vec<ExternalEventData>, tokio_postgres::Error> {
+) -> Result<Page<ExternalEventData>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT eed.* FROM recent_external_event_data eed"
@@ -102,13 +176,14 @@ pub async fn query(
     " AND ($9::bigint   IS NULL OR eed.end_time >= $9)",
     " AND ($10::bigint  IS NULL OR eed.end_time <= $10)",
     " AND ($11::bool    IS NULL OR eed.active = $11)",
-    " ORDER BY eed.external_event_data_id",
+    " AND eed.creator_user_id = $12",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "eed", "external_event_data_id", 13);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -123,12 +198,13 @@ pub async fn query(
         &props.min_end_time,
         &props.max_end_time,
         &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
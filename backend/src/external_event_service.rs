@@ -1,4 +1,5 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use tokio_postgres::GenericClient;
 use todo_app_service_api::request;
@@ -10,6 +11,9 @@ impl From<tokio_postgres::row::Row> for ExternalEvent {
       external_event_id: row.get("external_event_id"),
       creation_time: row.get("creation_time"),
       creator_user_id: row.get("creator_user_id"),
+      source: row.get("source"),
+      external_uid: row.get("external_uid"),
+      resource_name: row.get("resource_name"),
     }
   }
 }
@@ -17,6 +21,9 @@ impl From<tokio_postgres::row::Row> for ExternalEvent {
 pub async fn add(
   con: &mut impl GenericClient,
   creator_user_id: i64,
+  source: Option<String>,
+  external_uid: Option<String>,
+  resource_name: Option<String>,
 ) -> Result<ExternalEvent, tokio_postgres::Error> {
   let creation_time = current_time_millis();
 
@@ -25,12 +32,15 @@ pub async fn add(
       "INSERT INTO
        external_event(
            creation_time,
-           creator_user_id
+           creator_user_id,
+           source,
+           external_uid,
+           resource_name
        )
-       VALUES($1, $2)
+       VALUES($1, $2, $3, $4, $5)
        RETURNING external_event_id
       ",
-      &[&creation_time, &creator_user_id],
+      &[&creation_time, &creator_user_id, &source, &external_uid, &resource_name],
     ).await?
     .get(0);
 
@@ -39,6 +49,9 @@ pub async fn add(
     external_event_id,
     creation_time,
     creator_user_id,
+    source,
+    external_uid,
+    resource_name,
   })
 }
 
@@ -56,29 +69,109 @@ pub async fn get_by_external_event_id(
   Ok(result)
 }
 
-pub async fn query(
+pub async fn get_by_external_event_ids(
   con: &mut impl GenericClient,
-  props: request::ExternalEventViewProps,
+  external_event_ids: &[i64],
 ) -> Result<Vec<ExternalEvent>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM external_event WHERE external_event_id = ANY($1)";
   let results = con
-    .query(
-      " SELECT ee.* FROM external_event ee WHERE 1 = 1
+    .query(sql, &[&external_event_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+pub async fn get_by_external_uid(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  source: &str,
+  external_uid: &str,
+) -> Result<Option<ExternalEvent>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM external_event
+       WHERE creator_user_id=$1
+       AND source=$2
+       AND external_uid=$3
+      ",
+      &[&creator_user_id, &source, &external_uid],
+    ).await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// the newest event from the source that was created under the resource name.
+// events from before resource names were recorded were named after their uid
+pub async fn get_by_resource_name(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  source: &str,
+  resource_name: &str,
+) -> Result<Option<ExternalEvent>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM external_event
+       WHERE creator_user_id=$1
+       AND source=$2
+       AND coalesce(resource_name, external_uid)=$3
+       ORDER BY external_event_id DESC
+       LIMIT 1
+      ",
+      &[&creator_user_id, &source, &resource_name],
+    ).await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("ee.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::ExternalEventViewProps,
+) -> Result<Page<ExternalEvent>, tokio_postgres::Error> {
+  let sql = page.wrap(
+    " SELECT ee.* FROM external_event ee WHERE 1 = 1
         AND ($1::bigint[] IS NULL OR ee.external_event_id = ANY($1))
         AND ($2::bigint   IS NULL OR ee.creation_time >= $2)
         AND ($3::bigint   IS NULL OR ee.creation_time <= $3)
         AND ($4::bigint[] IS NULL OR ee.creator_user_id = ANY($4))
-        ORDER BY ee.external_event_id
+        AND ($5::text[]   IS NULL OR ee.source = ANY($5))
+        AND ($6::text[]   IS NULL OR ee.external_uid = ANY($6))
+        AND ee.creator_user_id = $7
       ",
+    "ee",
+    "external_event_id",
+    8,
+  );
+
+  let rows = con
+    .query(
+      sql.as_str(),
       &[
         &props.external_event_id,
         &props.min_creation_time,
         &props.max_creation_time,
         &props.creator_user_id,
+        &props.source,
+        &props.external_uid,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
-    ).await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    ).await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
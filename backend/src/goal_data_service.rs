@@ -1,4 +1,5 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use std::convert::TryInto;
 use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
@@ -67,6 +68,57 @@ pub async fn add(
   })
 }
 
+// same as add, but backdated, used when importing goals from elsewhere
+pub async fn add_at(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  creation_time: i64,
+  goal_id: i64,
+  name: String,
+  duration_estimate: Option<i64>,
+  time_utility_function_id: i64,
+  status: request::GoalDataStatusKind,
+) -> Result<GoalData, tokio_postgres::Error> {
+  let goal_data_id = con
+    .query_one(
+      "INSERT INTO
+       goal_data(
+           creation_time,
+           creator_user_id,
+           goal_id,
+           name,
+           duration_estimate,
+           time_utility_function_id,
+           status
+       )
+       VALUES ($1, $2, $3, $4, $5, $6, $7)
+       RETURNING goal_data_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &goal_id,
+        &name,
+        &duration_estimate,
+        &time_utility_function_id,
+        &(status.clone() as i64),
+      ],
+    )
+    .await?
+    .get(0);
+
+  Ok(GoalData {
+    goal_data_id,
+    creation_time,
+    creator_user_id,
+    goal_id,
+    name,
+    duration_estimate,
+    time_utility_function_id,
+    status,
+  })
+}
+
 pub async fn get_by_goal_data_id(
   con: &mut impl GenericClient,
   goal_data_id: i64,
@@ -81,10 +133,91 @@ pub async fn get_by_goal_data_id(
   Ok(result)
 }
 
+pub async fn get_recent_by_goal_id(
+  con: &mut impl GenericClient,
+  goal_id: i64,
+) -> Result<Option<GoalData>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_goal_data WHERE goal_id=$1",
+      &[&goal_id],
+    )
+    .await?
+    .map(|x| x.into());
+  Ok(result)
+}
+
+pub async fn get_recent(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+) -> Result<Vec<GoalData>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT gd.* FROM recent_goal_data gd
+       WHERE gd.creator_user_id = $1
+       ORDER BY gd.goal_id
+      ",
+      &[&creator_user_id],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the pending goals of the given users
+pub async fn get_recent_pending(
+  con: &mut impl GenericClient,
+  creator_user_id: &[i64],
+) -> Result<Vec<GoalData>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT gd.* FROM recent_goal_data gd
+       WHERE gd.creator_user_id = ANY($1)
+       AND gd.status = $2
+       ORDER BY gd.goal_id
+      ",
+      &[
+        &creator_user_id,
+        &(request::GoalDataStatusKind::Pending as i64),
+      ],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("gd.creation_time"),
+    request::ViewSortKind::Name => Some("gd.name"),
+    // the ones without an estimate go last
+    request::ViewSortKind::DurationEstimate => {
+      Some("coalesce(gd.duration_estimate, 9223372036854775807)")
+    }
+    // when the goal is scheduled, the unscheduled ones go last
+    request::ViewSortKind::StartTime => Some(
+      "coalesce((SELECT ge.start_time FROM recent_goal_event ge WHERE ge.goal_id = gd.goal_id AND ge.active), 9223372036854775807)",
+    ),
+    // the peak of the time utility function
+    request::ViewSortKind::Utility => Some(
+      "(SELECT max(u) FROM time_utility_function tuf, unnest(tuf.utils) u WHERE tuf.time_utility_function_id = gd.time_utility_function_id)",
+    ),
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::GoalDataViewProps,
-) -> Result<Vec<GoalData>, tokio_postgres::Error> {
+) -> Result<Page<GoalData>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT gd.* FROM recent_goal_data gd"
@@ -106,13 +239,14 @@ pub async fn query(
     " AND ($10::bigint[] IS NULL OR gd.time_utility_function_id = ANY($10))",
     " AND ($11::bigint[] IS NULL OR gd.status = ANY($11))",
     " AND ($12::bool     IS NULL OR (ge.active IS TRUE) = $12)",
-    " ORDER BY gd.goal_data_id",
+    " AND gd.creator_user_id = $13",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "gd", "goal_data_id", 14);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -130,12 +264,13 @@ pub async fn query(
           .status
           .map(|x| x.into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
         &props.scheduled,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
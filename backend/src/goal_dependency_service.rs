@@ -1,6 +1,8 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use std::convert::From;
+use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for GoalDependency {
@@ -75,10 +77,20 @@ pub async fn get_by_goal_dependency_id(
   Ok(result)
 }
 
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("gd.creation_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::GoalDependencyViewProps,
-) -> Result<Vec<GoalDependency>, tokio_postgres::Error> {
+) -> Result<Page<GoalDependency>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT gd.* FROM recent_goal_dependency gd"
@@ -93,13 +105,14 @@ pub async fn query(
     " AND ($5::bigint[]  IS NULL OR gd.goal_id = ANY($5))",
     " AND ($6::bigint[]  IS NULL OR gd.dependent_goal_id = ANY($6))",
     " AND ($7::bool      IS NULL OR gd.active = $7)",
-    " ORDER BY gd.goal_dependency_id",
+    " AND gd.creator_user_id = $8",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "gd", "goal_dependency_id", 9);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -110,12 +123,13 @@ pub async fn query(
         &props.goal_id,
         &props.dependent_goal_id,
         &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
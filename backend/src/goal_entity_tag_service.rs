@@ -1,6 +1,8 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use std::convert::From;
+use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for GoalEntityTag {
@@ -75,10 +77,44 @@ pub async fn get_by_goal_entity_tag_id(
   Ok(result)
 }
 
+// the named entities a goal is currently tagged with, along with their current data
+pub async fn get_recent_active_by_goal_id(
+  con: &mut impl GenericClient,
+  goal_id: i64,
+) -> Result<Vec<NamedEntityData>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT ned.* FROM recent_goal_entity_tag get
+       INNER JOIN recent_named_entity_data ned ON ned.named_entity_id = get.named_entity_id
+       WHERE get.goal_id = $1
+       AND get.active
+       AND ned.active
+       ORDER BY get.goal_entity_tag_id
+      ",
+      &[&goal_id],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("get.creation_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::GoalEntityTagViewProps,
-) -> Result<Vec<GoalEntityTag>, tokio_postgres::Error> {
+) -> Result<Page<GoalEntityTag>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT get.* FROM recent_goal_entity_tag get"
@@ -93,13 +129,14 @@ pub async fn query(
     " AND ($5::bigint[]  IS NULL OR get.named_entity_id = ANY($5))",
     " AND ($6::bigint[]  IS NULL OR get.goal_id = ANY($6))",
     " AND ($7::bool      IS NULL OR get.active = $7)",
-    " ORDER BY get.goal_entity_tag_id",
+    " AND get.creator_user_id = $8",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "get", "goal_entity_tag_id", 9);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -110,12 +147,13 @@ pub async fn query(
         &props.named_entity_id,
         &props.goal_id,
         &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
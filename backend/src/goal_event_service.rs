@@ -1,6 +1,8 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use std::convert::From;
+use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for GoalEvent {
@@ -81,10 +83,90 @@ pub async fn get_by_goal_event_id(
   Ok(result)
 }
 
+pub async fn get_recent_by_goal_id(
+  con: &mut impl GenericClient,
+  goal_id: i64,
+) -> Result<Option<GoalEvent>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_goal_event WHERE goal_id=$1",
+      &[&goal_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// every goal that is currently scheduled, along with the goal's current name
+pub async fn get_recent_active_with_name(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+) -> Result<Vec<(GoalEvent, String)>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT ge.*, gd.name FROM recent_goal_event ge
+       INNER JOIN recent_goal_data gd ON gd.goal_id = ge.goal_id
+       WHERE ge.creator_user_id = $1
+       AND ge.active
+       ORDER BY ge.start_time
+      ",
+      &[&creator_user_id],
+    )
+    .await?
+    .into_iter()
+    .map(|row| (row.get("name"), row))
+    .map(|(name, row)| (row.into(), name))
+    .collect();
+
+  Ok(results)
+}
+
+// the scheduled events of the given users' pending goals that start after the given time
+pub async fn get_recent_active_pending(
+  con: &mut impl GenericClient,
+  creator_user_id: &[i64],
+  min_start_time: i64,
+) -> Result<Vec<GoalEvent>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT ge.* FROM recent_goal_event ge
+       INNER JOIN recent_goal_data gd ON gd.goal_id = ge.goal_id
+       WHERE ge.creator_user_id = ANY($1)
+       AND ge.active
+       AND ge.start_time > $2
+       AND gd.status = $3
+       ORDER BY ge.start_time
+      ",
+      &[
+        &creator_user_id,
+        &min_start_time,
+        &(todo_app_service_api::request::GoalDataStatusKind::Pending as i64),
+      ],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("ge.creation_time"),
+    request::ViewSortKind::StartTime => Some("ge.start_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::GoalEventViewProps,
-) -> Result<Vec<GoalEvent>, tokio_postgres::Error> {
+) -> Result<Page<GoalEvent>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT ge.* FROM recent_goal_event ge"
@@ -102,13 +184,14 @@ pub async fn query(
     " AND ($8::bigint   IS NULL OR ge.end_time >= $8)",
     " AND ($9::bigint   IS NULL OR ge.end_time <= $9)",
     " AND ($10::bool    IS NULL OR ge.active = $10)",
-    " ORDER BY ge.goal_event_id",
+    " AND ge.creator_user_id = $11",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "ge", "goal_event_id", 12);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -122,12 +205,13 @@ pub async fn query(
         &props.min_end_time,
         &props.max_end_time,
         &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
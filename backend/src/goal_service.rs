@@ -1,4 +1,5 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
@@ -39,6 +40,34 @@ pub async fn add(
   })
 }
 
+// same as add, but backdated, used when importing goals from elsewhere
+pub async fn add_at(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  creation_time: i64,
+) -> Result<Goal, tokio_postgres::Error> {
+  let goal_id = con
+    .query_one(
+      "INSERT INTO
+       goal(
+           creation_time,
+           creator_user_id
+       )
+       VALUES($1, $2)
+       RETURNING goal_id
+      ",
+      &[&creation_time, &creator_user_id],
+    )
+    .await?
+    .get(0);
+
+  Ok(Goal {
+    goal_id,
+    creation_time,
+    creator_user_id,
+  })
+}
+
 pub async fn get_by_goal_id(
   con: &mut impl GenericClient,
   goal_id: i64,
@@ -51,21 +80,46 @@ pub async fn get_by_goal_id(
   Ok(result)
 }
 
+pub async fn get_by_goal_ids(
+  con: &mut impl GenericClient,
+  goal_ids: &[i64],
+) -> Result<Vec<Goal>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM goal WHERE goal_id = ANY($1)";
+  let results = con
+    .query(sql, &[&goal_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("g.creation_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: request::GoalViewProps,
-) -> Result<Vec<Goal>, tokio_postgres::Error> {
+) -> Result<Page<Goal>, tokio_postgres::Error> {
   let sql = "SELECT g.* FROM goal g WHERE 1 = 1
      AND ($1::bigint IS NULL OR g.goal_id = $1)
      AND ($2::bigint IS NULL OR g.creation_time >= $2)
      AND ($3::bigint IS NULL OR g.creation_time <= $3)
      AND ($4::bigint IS NULL OR g.creator_user_id = $4)
-     ORDER BY g.goal_id
+     AND g.creator_user_id = $5
      ";
+  let sql = page.wrap(sql, "g", "goal_id", 6);
 
-  let stmnt = con.prepare(sql).await?;
+  let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -73,11 +127,12 @@ pub async fn query(
         &props.min_creation_time,
         &props.max_creation_time,
         &props.creator_user_id,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|x| x.into())
-    .collect();
-  Ok(results)
+    .await?;
+  Ok(page.page(rows))
 }
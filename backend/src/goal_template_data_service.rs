@@ -1,6 +1,8 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use std::convert::From;
+use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for GoalTemplateData {
@@ -15,6 +17,7 @@ impl From<tokio_postgres::row::Row> for GoalTemplateData {
       utility: row.get("utility"),
       duration_estimate: row.get("duration_estimate"),
       user_generated_code_id: row.get("user_generated_code_id"),
+      published: row.get("published"),
       active: row.get("active"),
     }
   }
@@ -29,6 +32,7 @@ pub async fn add(
   utility: i64,
   duration_estimate: Option<i64>,
   user_generated_code_id: i64,
+  published: bool,
   active: bool,
 ) -> Result<GoalTemplateData, tokio_postgres::Error> {
   let creation_time = current_time_millis();
@@ -44,9 +48,10 @@ pub async fn add(
            utility,
            duration_estimate,
            user_generated_code_id,
+           published,
            active
        )
-       VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+       VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
        RETURNING goal_template_data_id
       ",
       &[
@@ -57,6 +62,7 @@ pub async fn add(
         &utility,
         &duration_estimate,
         &user_generated_code_id,
+        &published,
         &active,
       ],
     )
@@ -72,6 +78,7 @@ pub async fn add(
     utility,
     user_generated_code_id,
     duration_estimate,
+    published,
     active,
   })
 }
@@ -91,10 +98,41 @@ pub async fn get_by_goal_template_data_id(
   Ok(result)
 }
 
+pub async fn get_recent_by_goal_template_id(
+  con: &mut impl GenericClient,
+  goal_template_id: i64,
+) -> Result<Option<GoalTemplateData>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_goal_template_data WHERE goal_template_id=$1",
+      &[&goal_template_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("gtd.creation_time"),
+    request::ViewSortKind::Name => Some("gtd.name"),
+    // the ones without an estimate go last
+    request::ViewSortKind::DurationEstimate => {
+      Some("coalesce(gtd.duration_estimate, 9223372036854775807)")
+    }
+    request::ViewSortKind::Utility => Some("gtd.utility"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::GoalTemplateDataViewProps,
-) -> Result<Vec<GoalTemplateData>, tokio_postgres::Error> {
+) -> Result<Page<GoalTemplateData>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT gtd.* FROM recent_goal_template_data gtd"
@@ -115,13 +153,15 @@ pub async fn query(
     " AND ($11::bool     IS NULL OR gtd.duration_estimate IS NOT NULL)",
     " AND ($12::bigint[] IS NULL OR gtd.user_generated_code_id = ANY($12))",
     " AND ($13::bool     IS NULL OR gtd.active = $13)",
-    " ORDER BY gtd.goal_template_data_id",
+    " AND ($14::bool     IS NULL OR gtd.published = $14)",
+    " AND gtd.creator_user_id = $15",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "gtd", "goal_template_data_id", 16);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -138,12 +178,41 @@ pub async fn query(
         &props.concrete,
         &props.user_generated_code_id,
         &props.active,
+        &props.published,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
+
+  Ok(page.page(rows))
+}
+
+// the template library: the recent data of every active, published template
+pub async fn search_published(
+  con: &mut impl GenericClient,
+  page: &PageQuery,
+  name: Option<String>,
+) -> Result<Page<GoalTemplateData>, tokio_postgres::Error> {
+  let sql = page.wrap(
+    "SELECT gtd.* FROM recent_goal_template_data gtd
+       WHERE gtd.published
+       AND gtd.active
+       AND ($1::text IS NULL OR strpos(lower(gtd.name), lower($1)) > 0)
+      ",
+    "gtd",
+    "goal_template_data_id",
+    2,
+  );
+
+  let rows = con
+    .query(
+      sql.as_str(),
+      &[&name, &page.after_key, &page.after_id, &page.fetch_limit],
+    )
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
@@ -1,6 +1,8 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use std::convert::From;
+use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for GoalTemplatePattern {
@@ -76,10 +78,86 @@ pub async fn get_by_goal_template_pattern_id(
   Ok(result)
 }
 
+pub async fn get_active_by_goal_template_id(
+  con: &mut impl GenericClient,
+  goal_template_id: i64,
+) -> Result<Vec<GoalTemplatePattern>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT gtp.* FROM recent_goal_template_pattern gtp
+       WHERE gtp.goal_template_id = $1
+       AND gtp.active
+       ORDER BY gtp.goal_template_pattern_id
+      ",
+      &[&goal_template_id],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+pub async fn get_active_by_goal_template_ids(
+  con: &mut impl GenericClient,
+  goal_template_ids: &[i64],
+) -> Result<Vec<GoalTemplatePattern>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT gtp.* FROM recent_goal_template_pattern gtp
+       WHERE gtp.goal_template_id = ANY($1)
+       AND gtp.active
+       ORDER BY gtp.goal_template_pattern_id
+      ",
+      &[&goal_template_ids],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// returns all active patterns that occur somewhere in the given name (case insensitive)
+pub async fn get_matching(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  name: &str,
+) -> Result<Vec<GoalTemplatePattern>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT gtp.* FROM recent_goal_template_pattern gtp
+       WHERE gtp.creator_user_id = $1
+       AND gtp.active
+       AND strpos(lower($2), lower(gtp.pattern)) > 0
+       ORDER BY gtp.goal_template_pattern_id
+      ",
+      &[&creator_user_id, &name],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("gtp.creation_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::GoalTemplatePatternViewProps,
-) -> Result<Vec<GoalTemplatePattern>, tokio_postgres::Error> {
+) -> Result<Page<GoalTemplatePattern>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT gtp.* FROM recent_goal_template_pattern gtp"
@@ -94,13 +172,14 @@ pub async fn query(
     " AND ($5::bigint[]  IS NULL OR gtp.goal_template_id = ANY($5))",
     " AND ($6::text[]    IS NULL OR gtp.pattern = ANY($6))",
     " AND ($7::bool      IS NULL OR gtp.active = $7)",
-    " ORDER BY gtp.goal_template_pattern_id",
+    " AND gtp.creator_user_id = $8",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "gtp", "goal_template_pattern_id", 9);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -111,12 +190,13 @@ pub async fn query(
         &props.goal_template_id,
         &props.pattern,
         &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
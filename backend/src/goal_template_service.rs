@@ -1,5 +1,6 @@
 
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use tokio_postgres::GenericClient;
 use todo_app_service_api::request;
@@ -57,30 +58,62 @@ pub async fn get_by_goal_template_id(
   Ok(result)
 }
 
-pub async fn query(
+pub async fn get_by_goal_template_ids(
   con: &mut impl GenericClient,
-  props: request::GoalTemplateViewProps,
+  goal_template_ids: &[i64],
 ) -> Result<Vec<GoalTemplate>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM goal_template WHERE goal_template_id = ANY($1)";
   let results = con
-    .query(
-      "
+    .query(sql, &[&goal_template_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("gt.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::GoalTemplateViewProps,
+) -> Result<Page<GoalTemplate>, tokio_postgres::Error> {
+  let sql = page.wrap(
+    "
         SELECT gt.* FROM goal_template gt WHERE 1 = 1
         AND ($1::bigint[] IS NULL OR gt.goal_template_id = ANY($1))
         AND ($2::bigint   IS NULL OR gt.creation_time >= $2)
         AND ($3::bigint   IS NULL OR gt.creation_time <= $3)
         AND ($4::bigint[] IS NULL OR gt.creator_user_id = ANY($4))
-        ORDER BY gt.goal_template_id
+        AND gt.creator_user_id = $5
       ",
+    "gt",
+    "goal_template_id",
+    6,
+  );
+
+  let rows = con
+    .query(
+      sql.as_str(),
       &[
         &props.goal_template_id,
         &props.min_creation_time,
         &props.max_creation_time,
         &props.creator_user_id,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
-    ).await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    ).await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
@@ -1,14 +1,28 @@
+use super::auth::Auth;
 use super::Db;
-use auth_service_api::client::AuthService;
 use auth_service_api::response::AuthError;
 use auth_service_api::response::User;
 
 use todo_app_service_api::request;
 use todo_app_service_api::response;
 
+use super::caldav;
+use super::caldav_client;
+use super::change_stream;
+use super::csv;
 use super::db_types::*;
+use super::ical;
+use super::local_auth;
+use super::outline;
+use super::page;
+use super::todo_txt;
 use super::utils;
 
+use super::account_service;
+use super::caldav_subscription_service;
+use super::caldav_sync_service;
+use super::calendar_feed_service;
+use super::change_event_service;
 use super::external_event_data_service;
 use super::external_event_service;
 use super::goal_data_service;
@@ -19,17 +33,31 @@ use super::goal_service;
 use super::goal_template_data_service;
 use super::goal_template_pattern_service;
 use super::goal_template_service;
+use super::local_api_key_service;
+use super::local_user_service;
+use super::mail_setting_service;
 use super::named_entity_data_service;
 use super::named_entity_pattern_service;
 use super::named_entity_service;
+use super::reminder_delivery_service;
+use super::reminder_service;
+use super::reminder_setting_service;
+use super::sync_service;
 use super::time_utility_function_service;
 use super::user_generated_code_service;
+use super::webhook_delivery_service;
+use super::webhook_event_service;
+use super::webhook_service;
 
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
+use tokio_postgres::GenericClient;
 
 use super::Config;
 
-fn report_postgres_err(e: tokio_postgres::Error) -> response::TodoAppError {
+pub fn report_postgres_err(e: tokio_postgres::Error) -> response::TodoAppError {
     utils::log(utils::Event {
         msg: e.to_string(),
         source: e.source().map(|e| e.to_string()),
@@ -38,6 +66,34 @@ fn report_postgres_err(e: tokio_postgres::Error) -> response::TodoAppError {
     response::TodoAppError::InternalServerError
 }
 
+// no connection could be checked out of the pool in time
+pub fn report_pool_err(e: deadpool_postgres::PoolError) -> response::TodoAppError {
+    utils::log(utils::Event {
+        msg: e.to_string(),
+        source: e.source().map(|e| e.to_string()),
+        severity: utils::SeverityKind::Error,
+    });
+    response::TodoAppError::InternalServerError
+}
+
+fn report_local_auth_err(e: String) -> response::TodoAppError {
+    utils::log(utils::Event {
+        msg: e,
+        source: Some("local auth".to_owned()),
+        severity: utils::SeverityKind::Error,
+    });
+    response::TodoAppError::InternalServerError
+}
+
+fn report_secret_box_err(e: String) -> response::TodoAppError {
+    utils::log(utils::Event {
+        msg: e,
+        source: Some("secret box".to_owned()),
+        severity: utils::SeverityKind::Error,
+    });
+    response::TodoAppError::InternalServerError
+}
+
 fn report_auth_err(e: AuthError) -> response::TodoAppError {
     match e {
         AuthError::ApiKeyNonexistent => response::TodoAppError::Unauthorized,
@@ -62,8 +118,27 @@ fn report_auth_err(e: AuthError) -> response::TodoAppError {
     }
 }
 
+// why a page query couldn't be made: a sort the view doesn't have is answered with the ones it does have,
+// anything else that's off is a DecodeError
+fn report_page_query_err(
+    sort: &Option<request::ViewSortKind>,
+    sort_key: fn(&request::ViewSortKind) -> Option<&'static str>,
+) -> response::TodoAppError {
+    match sort {
+        Some(sort) if sort_key(sort).is_none() => {
+            response::TodoAppError::SortUnsupported(page::supported_sorts(sort_key))
+        }
+        _ => response::TodoAppError::DecodeError,
+    }
+}
+
+// keys rows by their id, so the rows they are referenced by can be filled from memory
+fn by_id<T>(rows: Vec<T>, id: impl Fn(&T) -> i64) -> HashMap<i64, T> {
+    rows.into_iter().map(|x| (id(&x), x)).collect()
+}
+
 async fn fill_goal(
-    _con: &mut tokio_postgres::Client,
+    _con: &mut impl GenericClient,
     goal: Goal,
 ) -> Result<response::Goal, response::TodoAppError> {
     Ok(response::Goal {
@@ -74,105 +149,201 @@ async fn fill_goal(
 }
 
 async fn fill_goal_data(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     goal_data: GoalData,
 ) -> Result<response::GoalData, response::TodoAppError> {
-    let goal = goal_service::get_by_goal_id(con, goal_data.goal_id)
+    Ok(fill_goal_data_batch(con, vec![goal_data]).await?.remove(0))
+}
+
+// fetches everything the rows reference with one query per table
+async fn fill_goal_data_batch(
+    con: &mut impl GenericClient,
+    goal_datas: Vec<GoalData>,
+) -> Result<Vec<response::GoalData>, response::TodoAppError> {
+    let goal_ids: Vec<i64> = goal_datas.iter().map(|x| x.goal_id).collect();
+    let goals = goal_service::get_by_goal_ids(con, &goal_ids)
         .await
-        .map_err(report_postgres_err)?
-        .ok_or(response::TodoAppError::GoalNonexistent)?;
+        .map_err(report_postgres_err)?;
+    let goals = by_id(goals, |x| x.goal_id);
 
-    let time_utility_function = time_utility_function_service::get_by_time_utility_function_id(
+    let time_utility_function_ids: Vec<i64> = goal_datas
+        .iter()
+        .map(|x| x.time_utility_function_id)
+        .collect();
+    let time_utility_functions = time_utility_function_service::get_by_time_utility_function_ids(
         con,
-        goal_data.time_utility_function_id,
+        &time_utility_function_ids,
     )
     .await
-    .map_err(report_postgres_err)?
-    .ok_or(response::TodoAppError::TimeUtilityFunctionNonexistent)?;
+    .map_err(report_postgres_err)?;
+    let time_utility_functions = by_id(time_utility_functions, |x| x.time_utility_function_id);
 
-    Ok(response::GoalData {
-        goal_data_id: goal_data.goal_data_id,
-        creation_time: goal_data.creation_time,
-        creator_user_id: goal_data.creator_user_id,
-        goal: fill_goal(con, goal).await?,
-        name: goal_data.name,
-        duration_estimate: goal_data.duration_estimate,
-        time_utility_function: fill_time_utility_function(con, time_utility_function).await?,
-        status: goal_data.status,
-    })
+    let mut resp_goal_datas = vec![];
+    for goal_data in goal_datas {
+        let goal = goals
+            .get(&goal_data.goal_id)
+            .cloned()
+            .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+        let time_utility_function = time_utility_functions
+            .get(&goal_data.time_utility_function_id)
+            .cloned()
+            .ok_or(response::TodoAppError::TimeUtilityFunctionNonexistent)?;
+
+        resp_goal_datas.push(response::GoalData {
+            goal_data_id: goal_data.goal_data_id,
+            creation_time: goal_data.creation_time,
+            creator_user_id: goal_data.creator_user_id,
+            goal: fill_goal(con, goal).await?,
+            name: goal_data.name,
+            duration_estimate: goal_data.duration_estimate,
+            time_utility_function: fill_time_utility_function(con, time_utility_function).await?,
+            status: goal_data.status,
+        });
+    }
+
+    Ok(resp_goal_datas)
 }
 
 async fn fill_goal_event(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     goal_event: GoalEvent,
 ) -> Result<response::GoalEvent, response::TodoAppError> {
-    let goal = goal_service::get_by_goal_id(con, goal_event.goal_id)
+    Ok(fill_goal_event_batch(con, vec![goal_event])
+        .await?
+        .remove(0))
+}
+
+async fn fill_goal_event_batch(
+    con: &mut impl GenericClient,
+    goal_events: Vec<GoalEvent>,
+) -> Result<Vec<response::GoalEvent>, response::TodoAppError> {
+    let goal_ids: Vec<i64> = goal_events.iter().map(|x| x.goal_id).collect();
+    let goals = goal_service::get_by_goal_ids(con, &goal_ids)
         .await
-        .map_err(report_postgres_err)?
-        .ok_or(response::TodoAppError::GoalNonexistent)?;
+        .map_err(report_postgres_err)?;
+    let goals = by_id(goals, |x| x.goal_id);
 
-    Ok(response::GoalEvent {
-        goal_event_id: goal_event.goal_event_id,
-        creation_time: goal_event.creation_time,
-        creator_user_id: goal_event.creator_user_id,
-        goal: fill_goal(con, goal).await?,
-        start_time: goal_event.start_time,
-        end_time: goal_event.end_time,
-        active: goal_event.active,
-    })
+    let mut resp_goal_events = vec![];
+    for goal_event in goal_events {
+        let goal = goals
+            .get(&goal_event.goal_id)
+            .cloned()
+            .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+        resp_goal_events.push(response::GoalEvent {
+            goal_event_id: goal_event.goal_event_id,
+            creation_time: goal_event.creation_time,
+            creator_user_id: goal_event.creator_user_id,
+            goal: fill_goal(con, goal).await?,
+            start_time: goal_event.start_time,
+            end_time: goal_event.end_time,
+            active: goal_event.active,
+        });
+    }
+
+    Ok(resp_goal_events)
 }
 
 async fn fill_goal_dependency(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     goal_dependency: GoalDependency,
 ) -> Result<response::GoalDependency, response::TodoAppError> {
-    let goal = goal_service::get_by_goal_id(con, goal_dependency.goal_id)
-        .await
-        .map_err(report_postgres_err)?
-        .ok_or(response::TodoAppError::GoalNonexistent)?;
+    Ok(fill_goal_dependency_batch(con, vec![goal_dependency])
+        .await?
+        .remove(0))
+}
 
-    let dependent_goal = goal_service::get_by_goal_id(con, goal_dependency.dependent_goal_id)
+async fn fill_goal_dependency_batch(
+    con: &mut impl GenericClient,
+    goal_dependencys: Vec<GoalDependency>,
+) -> Result<Vec<response::GoalDependency>, response::TodoAppError> {
+    let goal_ids: Vec<i64> = goal_dependencys
+        .iter()
+        .flat_map(|x| [x.goal_id, x.dependent_goal_id])
+        .collect();
+    let goals = goal_service::get_by_goal_ids(con, &goal_ids)
         .await
-        .map_err(report_postgres_err)?
-        .ok_or(response::TodoAppError::GoalNonexistent)?;
+        .map_err(report_postgres_err)?;
+    let goals = by_id(goals, |x| x.goal_id);
 
-    Ok(response::GoalDependency {
-        goal_dependency_id: goal_dependency.goal_dependency_id,
-        creation_time: goal_dependency.creation_time,
-        creator_user_id: goal_dependency.creator_user_id,
-        goal: fill_goal(con, goal).await?,
-        dependent_goal: fill_goal(con, dependent_goal).await?,
-        active: goal_dependency.active,
-    })
+    let mut resp_goal_dependencys = vec![];
+    for goal_dependency in goal_dependencys {
+        let goal = goals
+            .get(&goal_dependency.goal_id)
+            .cloned()
+            .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+        let dependent_goal = goals
+            .get(&goal_dependency.dependent_goal_id)
+            .cloned()
+            .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+        resp_goal_dependencys.push(response::GoalDependency {
+            goal_dependency_id: goal_dependency.goal_dependency_id,
+            creation_time: goal_dependency.creation_time,
+            creator_user_id: goal_dependency.creator_user_id,
+            goal: fill_goal(con, goal).await?,
+            dependent_goal: fill_goal(con, dependent_goal).await?,
+            active: goal_dependency.active,
+        });
+    }
+
+    Ok(resp_goal_dependencys)
 }
 
 async fn fill_goal_entity_tag(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     goal_entity_tag: GoalEntityTag,
 ) -> Result<response::GoalEntityTag, response::TodoAppError> {
-    let goal = goal_service::get_by_goal_id(con, goal_entity_tag.goal_id)
+    Ok(fill_goal_entity_tag_batch(con, vec![goal_entity_tag])
+        .await?
+        .remove(0))
+}
+
+async fn fill_goal_entity_tag_batch(
+    con: &mut impl GenericClient,
+    goal_entity_tags: Vec<GoalEntityTag>,
+) -> Result<Vec<response::GoalEntityTag>, response::TodoAppError> {
+    let goal_ids: Vec<i64> = goal_entity_tags.iter().map(|x| x.goal_id).collect();
+    let goals = goal_service::get_by_goal_ids(con, &goal_ids)
         .await
-        .map_err(report_postgres_err)?
-        .ok_or(response::TodoAppError::GoalNonexistent)?;
+        .map_err(report_postgres_err)?;
+    let goals = by_id(goals, |x| x.goal_id);
 
-    let named_entity =
-        named_entity_service::get_by_named_entity_id(con, goal_entity_tag.named_entity_id)
-            .await
-            .map_err(report_postgres_err)?
+    let named_entity_ids: Vec<i64> = goal_entity_tags.iter().map(|x| x.named_entity_id).collect();
+    let named_entitys = named_entity_service::get_by_named_entity_ids(con, &named_entity_ids)
+        .await
+        .map_err(report_postgres_err)?;
+    let named_entitys = by_id(named_entitys, |x| x.named_entity_id);
+
+    let mut resp_goal_entity_tags = vec![];
+    for goal_entity_tag in goal_entity_tags {
+        let goal = goals
+            .get(&goal_entity_tag.goal_id)
+            .cloned()
+            .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+        let named_entity = named_entitys
+            .get(&goal_entity_tag.named_entity_id)
+            .cloned()
             .ok_or(response::TodoAppError::NamedEntityNonexistent)?;
 
-    Ok(response::GoalEntityTag {
-        goal_entity_tag_id: goal_entity_tag.goal_entity_tag_id,
-        creation_time: goal_entity_tag.creation_time,
-        creator_user_id: goal_entity_tag.creator_user_id,
-        goal: fill_goal(con, goal).await?,
-        named_entity: fill_named_entity(con, named_entity).await?,
-        active: goal_entity_tag.active,
-    })
+        resp_goal_entity_tags.push(response::GoalEntityTag {
+            goal_entity_tag_id: goal_entity_tag.goal_entity_tag_id,
+            creation_time: goal_entity_tag.creation_time,
+            creator_user_id: goal_entity_tag.creator_user_id,
+            goal: fill_goal(con, goal).await?,
+            named_entity: fill_named_entity(con, named_entity).await?,
+            active: goal_entity_tag.active,
+        });
+    }
+
+    Ok(resp_goal_entity_tags)
 }
 
 async fn fill_time_utility_function(
-    _con: &mut tokio_postgres::Client,
+    _con: &mut impl GenericClient,
     time_utility_function: TimeUtilityFunction,
 ) -> Result<response::TimeUtilityFunction, response::TodoAppError> {
     Ok(response::TimeUtilityFunction {
@@ -185,7 +356,7 @@ async fn fill_time_utility_function(
 }
 
 async fn fill_goal_template(
-    _con: &mut tokio_postgres::Client,
+    _con: &mut impl GenericClient,
     goal_template: GoalTemplate,
 ) -> Result<response::GoalTemplate, response::TodoAppError> {
     Ok(response::GoalTemplate {
@@ -196,58 +367,115 @@ async fn fill_goal_template(
 }
 
 async fn fill_goal_template_data(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     goal_template_data: GoalTemplateData,
 ) -> Result<response::GoalTemplateData, response::TodoAppError> {
-    let goal_template =
-        goal_template_service::get_by_goal_template_id(con, goal_template_data.goal_template_id)
+    Ok(fill_goal_template_data_batch(con, vec![goal_template_data])
+        .await?
+        .remove(0))
+}
+
+async fn fill_goal_template_data_batch(
+    con: &mut impl GenericClient,
+    goal_template_datas: Vec<GoalTemplateData>,
+) -> Result<Vec<response::GoalTemplateData>, response::TodoAppError> {
+    let goal_template_ids: Vec<i64> = goal_template_datas
+        .iter()
+        .map(|x| x.goal_template_id)
+        .collect();
+    let goal_templates = goal_template_service::get_by_goal_template_ids(con, &goal_template_ids)
+        .await
+        .map_err(report_postgres_err)?;
+    let goal_templates = by_id(goal_templates, |x| x.goal_template_id);
+
+    let user_generated_code_ids: Vec<i64> = goal_template_datas
+        .iter()
+        .map(|x| x.user_generated_code_id)
+        .collect();
+    let user_generated_codes =
+        user_generated_code_service::get_by_user_generated_code_ids(con, &user_generated_code_ids)
             .await
-            .map_err(report_postgres_err)?
+            .map_err(report_postgres_err)?;
+    let user_generated_codes = by_id(user_generated_codes, |x| x.user_generated_code_id);
+
+    let mut resp_goal_template_datas = vec![];
+    for goal_template_data in goal_template_datas {
+        let goal_template = goal_templates
+            .get(&goal_template_data.goal_template_id)
+            .cloned()
             .ok_or(response::TodoAppError::GoalTemplateNonexistent)?;
 
-    let user_generated_code = user_generated_code_service::get_by_user_generated_code_id(
-        con,
-        goal_template_data.user_generated_code_id,
-    )
-    .await
-    .map_err(report_postgres_err)?
-    .ok_or(response::TodoAppError::UserGeneratedCodeNonexistent)?;
+        let user_generated_code = user_generated_codes
+            .get(&goal_template_data.user_generated_code_id)
+            .cloned()
+            .ok_or(response::TodoAppError::UserGeneratedCodeNonexistent)?;
+
+        resp_goal_template_datas.push(response::GoalTemplateData {
+            // this has always held the parent's id and clients rely on it, the row's own id is the row_id
+            goal_template_data_id: goal_template_data.goal_template_id,
+            goal_template_data_row_id: goal_template_data.goal_template_data_id,
+            creation_time: goal_template_data.creation_time,
+            creator_user_id: goal_template_data.creator_user_id,
+            goal_template: fill_goal_template(con, goal_template).await?,
+            name: goal_template_data.name,
+            utility: goal_template_data.utility,
+            duration_estimate: goal_template_data.duration_estimate,
+            user_generated_code: fill_user_generated_code(con, user_generated_code).await?,
+            published: goal_template_data.published,
+            active: goal_template_data.active,
+        });
+    }
 
-    Ok(response::GoalTemplateData {
-        goal_template_data_id: goal_template_data.goal_template_id,
-        creation_time: goal_template_data.creation_time,
-        creator_user_id: goal_template_data.creator_user_id,
-        goal_template: fill_goal_template(con, goal_template).await?,
-        name: goal_template_data.name,
-        utility: goal_template_data.utility,
-        duration_estimate: goal_template_data.duration_estimate,
-        user_generated_code: fill_user_generated_code(con, user_generated_code).await?,
-        active: goal_template_data.active,
-    })
+    Ok(resp_goal_template_datas)
 }
 
 async fn fill_goal_template_pattern(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     goal_template_pattern: GoalTemplatePattern,
 ) -> Result<response::GoalTemplatePattern, response::TodoAppError> {
-    let goal_template =
-        goal_template_service::get_by_goal_template_id(con, goal_template_pattern.goal_template_id)
-            .await
-            .map_err(report_postgres_err)?
+    Ok(
+        fill_goal_template_pattern_batch(con, vec![goal_template_pattern])
+            .await?
+            .remove(0),
+    )
+}
+
+async fn fill_goal_template_pattern_batch(
+    con: &mut impl GenericClient,
+    goal_template_patterns: Vec<GoalTemplatePattern>,
+) -> Result<Vec<response::GoalTemplatePattern>, response::TodoAppError> {
+    let goal_template_ids: Vec<i64> = goal_template_patterns
+        .iter()
+        .map(|x| x.goal_template_id)
+        .collect();
+    let goal_templates = goal_template_service::get_by_goal_template_ids(con, &goal_template_ids)
+        .await
+        .map_err(report_postgres_err)?;
+    let goal_templates = by_id(goal_templates, |x| x.goal_template_id);
+
+    let mut resp_goal_template_patterns = vec![];
+    for goal_template_pattern in goal_template_patterns {
+        let goal_template = goal_templates
+            .get(&goal_template_pattern.goal_template_id)
+            .cloned()
             .ok_or(response::TodoAppError::GoalTemplateNonexistent)?;
 
-    Ok(response::GoalTemplatePattern {
-        goal_template_pattern_id: goal_template_pattern.goal_template_id,
-        creation_time: goal_template_pattern.creation_time,
-        creator_user_id: goal_template_pattern.creator_user_id,
-        goal_template: fill_goal_template(con, goal_template).await?,
-        pattern: goal_template_pattern.pattern,
-        active: goal_template_pattern.active,
-    })
+        resp_goal_template_patterns.push(response::GoalTemplatePattern {
+            goal_template_pattern_id: goal_template_pattern.goal_template_id,
+            goal_template_pattern_row_id: goal_template_pattern.goal_template_pattern_id,
+            creation_time: goal_template_pattern.creation_time,
+            creator_user_id: goal_template_pattern.creator_user_id,
+            goal_template: fill_goal_template(con, goal_template).await?,
+            pattern: goal_template_pattern.pattern,
+            active: goal_template_pattern.active,
+        });
+    }
+
+    Ok(resp_goal_template_patterns)
 }
 
 async fn fill_user_generated_code(
-    _con: &mut tokio_postgres::Client,
+    _con: &mut impl GenericClient,
     user_generated_code: UserGeneratedCode,
 ) -> Result<response::UserGeneratedCode, response::TodoAppError> {
     Ok(response::UserGeneratedCode {
@@ -261,7 +489,7 @@ async fn fill_user_generated_code(
 }
 
 async fn fill_named_entity(
-    _con: &mut tokio_postgres::Client,
+    _con: &mut impl GenericClient,
     named_entity: NamedEntity,
 ) -> Result<response::NamedEntity, response::TodoAppError> {
     Ok(response::NamedEntity {
@@ -272,146 +500,2210 @@ async fn fill_named_entity(
 }
 
 async fn fill_named_entity_data(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     named_entity_data: NamedEntityData,
 ) -> Result<response::NamedEntityData, response::TodoAppError> {
-    let named_entity =
-        named_entity_service::get_by_named_entity_id(con, named_entity_data.named_entity_id)
-            .await
-            .map_err(report_postgres_err)?
+    Ok(fill_named_entity_data_batch(con, vec![named_entity_data])
+        .await?
+        .remove(0))
+}
+
+async fn fill_named_entity_data_batch(
+    con: &mut impl GenericClient,
+    named_entity_datas: Vec<NamedEntityData>,
+) -> Result<Vec<response::NamedEntityData>, response::TodoAppError> {
+    let named_entity_ids: Vec<i64> = named_entity_datas
+        .iter()
+        .map(|x| x.named_entity_id)
+        .collect();
+    let named_entitys = named_entity_service::get_by_named_entity_ids(con, &named_entity_ids)
+        .await
+        .map_err(report_postgres_err)?;
+    let named_entitys = by_id(named_entitys, |x| x.named_entity_id);
+
+    let mut resp_named_entity_datas = vec![];
+    for named_entity_data in named_entity_datas {
+        let named_entity = named_entitys
+            .get(&named_entity_data.named_entity_id)
+            .cloned()
             .ok_or(response::TodoAppError::NamedEntityNonexistent)?;
 
-    Ok(response::NamedEntityData {
-        named_entity_data_id: named_entity_data.named_entity_id,
-        creation_time: named_entity_data.creation_time,
-        creator_user_id: named_entity_data.creator_user_id,
-        named_entity: fill_named_entity(con, named_entity).await?,
-        name: named_entity_data.name,
-        kind: named_entity_data.kind,
-        active: named_entity_data.active,
-    })
+        resp_named_entity_datas.push(response::NamedEntityData {
+            named_entity_data_id: named_entity_data.named_entity_id,
+            named_entity_data_row_id: named_entity_data.named_entity_data_id,
+            creation_time: named_entity_data.creation_time,
+            creator_user_id: named_entity_data.creator_user_id,
+            named_entity: fill_named_entity(con, named_entity).await?,
+            name: named_entity_data.name,
+            kind: named_entity_data.kind,
+            active: named_entity_data.active,
+        });
+    }
+
+    Ok(resp_named_entity_datas)
 }
 
 async fn fill_named_entity_pattern(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     named_entity_pattern: NamedEntityPattern,
 ) -> Result<response::NamedEntityPattern, response::TodoAppError> {
-    let named_entity =
-        named_entity_service::get_by_named_entity_id(con, named_entity_pattern.named_entity_id)
-            .await
-            .map_err(report_postgres_err)?
+    Ok(
+        fill_named_entity_pattern_batch(con, vec![named_entity_pattern])
+            .await?
+            .remove(0),
+    )
+}
+
+async fn fill_named_entity_pattern_batch(
+    con: &mut impl GenericClient,
+    named_entity_patterns: Vec<NamedEntityPattern>,
+) -> Result<Vec<response::NamedEntityPattern>, response::TodoAppError> {
+    let named_entity_ids: Vec<i64> = named_entity_patterns
+        .iter()
+        .map(|x| x.named_entity_id)
+        .collect();
+    let named_entitys = named_entity_service::get_by_named_entity_ids(con, &named_entity_ids)
+        .await
+        .map_err(report_postgres_err)?;
+    let named_entitys = by_id(named_entitys, |x| x.named_entity_id);
+
+    let mut resp_named_entity_patterns = vec![];
+    for named_entity_pattern in named_entity_patterns {
+        let named_entity = named_entitys
+            .get(&named_entity_pattern.named_entity_id)
+            .cloned()
             .ok_or(response::TodoAppError::NamedEntityNonexistent)?;
 
-    Ok(response::NamedEntityPattern {
-        named_entity_pattern_id: named_entity_pattern.named_entity_id,
-        creation_time: named_entity_pattern.creation_time,
-        creator_user_id: named_entity_pattern.creator_user_id,
-        named_entity: fill_named_entity(con, named_entity).await?,
-        pattern: named_entity_pattern.pattern,
-        active: named_entity_pattern.active,
-    })
+        resp_named_entity_patterns.push(response::NamedEntityPattern {
+            named_entity_pattern_id: named_entity_pattern.named_entity_id,
+            named_entity_pattern_row_id: named_entity_pattern.named_entity_pattern_id,
+            creation_time: named_entity_pattern.creation_time,
+            creator_user_id: named_entity_pattern.creator_user_id,
+            named_entity: fill_named_entity(con, named_entity).await?,
+            pattern: named_entity_pattern.pattern,
+            active: named_entity_pattern.active,
+        });
+    }
+
+    Ok(resp_named_entity_patterns)
 }
 
 async fn fill_external_event(
-    _con: &mut tokio_postgres::Client,
+    _con: &mut impl GenericClient,
     external_event: ExternalEvent,
 ) -> Result<response::ExternalEvent, response::TodoAppError> {
     Ok(response::ExternalEvent {
         external_event_id: external_event.external_event_id,
         creation_time: external_event.creation_time,
         creator_user_id: external_event.creator_user_id,
+        source: external_event.source,
+        external_uid: external_event.external_uid,
     })
 }
 
 async fn fill_external_event_data(
-    con: &mut tokio_postgres::Client,
+    con: &mut impl GenericClient,
     external_event_data: ExternalEventData,
 ) -> Result<response::ExternalEventData, response::TodoAppError> {
-    let external_event = external_event_service::get_by_external_event_id(
-        con,
-        external_event_data.external_event_id,
+    Ok(
+        fill_external_event_data_batch(con, vec![external_event_data])
+            .await?
+            .remove(0),
     )
-    .await
-    .map_err(report_postgres_err)?
-    .ok_or(response::TodoAppError::GoalNonexistent)?;
-
-    Ok(response::ExternalEventData {
-        external_event_data_id: external_event_data.external_event_id,
-        creation_time: external_event_data.creation_time,
-        creator_user_id: external_event_data.creator_user_id,
-        external_event: fill_external_event(con, external_event).await?,
-        name: external_event_data.name,
-        start_time: external_event_data.start_time,
-        end_time: external_event_data.end_time,
-        active: external_event_data.active,
-    })
 }
 
-pub async fn get_user_if_api_key_valid(
-    auth_service: &auth_service_api::client::AuthService,
-    api_key: String,
-) -> Result<User, response::TodoAppError> {
-    auth_service
-        .get_user_by_api_key_if_valid(api_key)
-        .await
-        .map_err(report_auth_err)
+async fn fill_external_event_data_batch(
+    con: &mut impl GenericClient,
+    external_event_datas: Vec<ExternalEventData>,
+) -> Result<Vec<response::ExternalEventData>, response::TodoAppError> {
+    let external_event_ids: Vec<i64> = external_event_datas
+        .iter()
+        .map(|x| x.external_event_id)
+        .collect();
+    let external_events =
+        external_event_service::get_by_external_event_ids(con, &external_event_ids)
+            .await
+            .map_err(report_postgres_err)?;
+    let external_events = by_id(external_events, |x| x.external_event_id);
+
+    let mut resp_external_event_datas = vec![];
+    for external_event_data in external_event_datas {
+        let external_event = external_events
+            .get(&external_event_data.external_event_id)
+            .cloned()
+            .ok_or(response::TodoAppError::ExternalEventNonexistent)?;
+
+        resp_external_event_datas.push(response::ExternalEventData {
+            external_event_data_id: external_event_data.external_event_id,
+            external_event_data_row_id: external_event_data.external_event_data_id,
+            creation_time: external_event_data.creation_time,
+            creator_user_id: external_event_data.creator_user_id,
+            external_event: fill_external_event(con, external_event).await?,
+            name: external_event_data.name,
+            start_time: external_event_data.start_time,
+            end_time: external_event_data.end_time,
+            active: external_event_data.active,
+        });
+    }
+
+    Ok(resp_external_event_datas)
 }
 
-pub async fn api_info(
-    config: Config,
-    _db: Db,
-    auth_service: AuthService,
-) -> Result<response::Info, response::TodoAppError> {
-    let auth_info = auth_service.info().await.map_err(report_auth_err)?;
-    Ok(response::Info {
-        service: crate::SERVICE_NAME.into(),
-        version_major: 0,
-        version_minor: 0,
-        version_rev: 1,
-        app_pub_origin: config.app_pub_origin,
-        auth_pub_api_href: auth_info.app_pub_api_href,
-        auth_authenticator_href: auth_info.app_authenticator_href,
+// the password is write only
+async fn fill_caldav_subscription(
+    _con: &mut impl GenericClient,
+    caldav_subscription: CaldavSubscription,
+) -> Result<response::CaldavSubscription, response::TodoAppError> {
+    Ok(response::CaldavSubscription {
+        caldav_subscription_id: caldav_subscription.caldav_subscription_id,
+        creation_time: caldav_subscription.creation_time,
+        creator_user_id: caldav_subscription.creator_user_id,
+        url: caldav_subscription.url,
+        username: caldav_subscription.username,
+        active: caldav_subscription.active,
     })
 }
 
-pub async fn external_event_new(
-    _config: Config,
-    db: Db,
-    auth_service: AuthService,
-    props: request::ExternalEventNewProps,
-) -> Result<response::ExternalEventData, response::TodoAppError> {
-    // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+async fn fill_caldav_sync_batch(
+    con: &mut impl GenericClient,
+    caldav_syncs: Vec<CaldavSync>,
+) -> Result<Vec<response::CaldavSync>, response::TodoAppError> {
+    let caldav_subscription_ids: Vec<i64> = caldav_syncs
+        .iter()
+        .map(|x| x.caldav_subscription_id)
+        .collect();
+    let caldav_subscriptions =
+        caldav_subscription_service::get_by_caldav_subscription_ids(con, &caldav_subscription_ids)
+            .await
+            .map_err(report_postgres_err)?;
+    let caldav_subscriptions = by_id(caldav_subscriptions, |x| x.caldav_subscription_id);
+
+    let mut resp_caldav_syncs = vec![];
+    for caldav_sync in caldav_syncs {
+        let caldav_subscription = caldav_subscriptions
+            .get(&caldav_sync.caldav_subscription_id)
+            .cloned()
+            .ok_or(response::TodoAppError::CaldavSubscriptionNonexistent)?;
+
+        resp_caldav_syncs.push(response::CaldavSync {
+            caldav_sync_id: caldav_sync.caldav_sync_id,
+            creation_time: caldav_sync.creation_time,
+            creator_user_id: caldav_sync.creator_user_id,
+            caldav_subscription: fill_caldav_subscription(con, caldav_subscription).await?,
+            ctag: caldav_sync.ctag,
+            sync_token: caldav_sync.sync_token,
+            event_count: caldav_sync.event_count,
+            error: caldav_sync.error,
+        });
+    }
 
-    // validate
-    if props.start_time < 0 {
-        return Err(response::TodoAppError::NegativeStartTime);
+    Ok(resp_caldav_syncs)
+}
+
+async fn fill_webhook(
+    _con: &mut impl GenericClient,
+    webhook: Webhook,
+) -> Result<response::Webhook, response::TodoAppError> {
+    Ok(response::Webhook {
+        webhook_id: webhook.webhook_id,
+        creation_time: webhook.creation_time,
+        creator_user_id: webhook.creator_user_id,
+        url: webhook.url,
+        event_kinds: webhook.event_kinds,
+        active: webhook.active,
+    })
+}
+
+async fn fill_webhook_event_batch(
+    con: &mut impl GenericClient,
+    webhook_events: Vec<WebhookEvent>,
+) -> Result<Vec<response::WebhookEvent>, response::TodoAppError> {
+    let webhook_ids: Vec<i64> = webhook_events.iter().map(|x| x.webhook_id).collect();
+    let webhooks = webhook_service::get_by_webhook_ids(con, &webhook_ids)
+        .await
+        .map_err(report_postgres_err)?;
+    let webhooks = by_id(webhooks, |x| x.webhook_id);
+
+    let mut resp_webhook_events = vec![];
+    for webhook_event in webhook_events {
+        let webhook = webhooks
+            .get(&webhook_event.webhook_id)
+            .cloned()
+            .ok_or(response::TodoAppError::WebhookNonexistent)?;
+
+        resp_webhook_events.push(response::WebhookEvent {
+            webhook_event_id: webhook_event.webhook_event_id,
+            creation_time: webhook_event.creation_time,
+            creator_user_id: webhook_event.creator_user_id,
+            webhook: fill_webhook(con, webhook).await?,
+            kind: webhook_event.kind,
+            payload: webhook_event.payload,
+        });
     }
-    if props.start_time >= props.end_time {
-        return Err(response::TodoAppError::NegativeDuration);
+
+    Ok(resp_webhook_events)
+}
+
+async fn fill_webhook_delivery_batch(
+    con: &mut impl GenericClient,
+    webhook_deliveries: Vec<WebhookDelivery>,
+) -> Result<Vec<response::WebhookDelivery>, response::TodoAppError> {
+    let webhook_event_ids: Vec<i64> = webhook_deliveries
+        .iter()
+        .map(|x| x.webhook_event_id)
+        .collect();
+    let webhook_events = webhook_event_service::get_by_webhook_event_ids(con, &webhook_event_ids)
+        .await
+        .map_err(report_postgres_err)?;
+    let webhook_events = by_id(webhook_events, |x| x.webhook_event_id);
+
+    // the event of each delivery, in order, so they can be filled together
+    let mut delivery_webhook_events = vec![];
+    for webhook_delivery in &webhook_deliveries {
+        delivery_webhook_events.push(
+            webhook_events
+                .get(&webhook_delivery.webhook_event_id)
+                .cloned()
+                .ok_or(response::TodoAppError::WebhookEventNonexistent)?,
+        );
     }
+    let resp_webhook_events = fill_webhook_event_batch(con, delivery_webhook_events).await?;
 
-    let con = &mut *db.lock().await;
+    Ok(webhook_deliveries
+        .into_iter()
+        .zip(resp_webhook_events)
+        .map(
+            |(webhook_delivery, webhook_event)| response::WebhookDelivery {
+                webhook_delivery_id: webhook_delivery.webhook_delivery_id,
+                creation_time: webhook_delivery.creation_time,
+                creator_user_id: webhook_delivery.creator_user_id,
+                webhook_event,
+                status_code: webhook_delivery.status_code,
+                error: webhook_delivery.error,
+            },
+        )
+        .collect())
+}
 
-    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+async fn fill_reminder_setting(
+    _con: &mut impl GenericClient,
+    reminder_setting: ReminderSetting,
+) -> Result<response::ReminderSetting, response::TodoAppError> {
+    Ok(response::ReminderSetting {
+        reminder_setting_id: reminder_setting.reminder_setting_id,
+        creation_time: reminder_setting.creation_time,
+        creator_user_id: reminder_setting.creator_user_id,
+        goal_event_offset: reminder_setting.goal_event_offset,
+        utility_threshold: reminder_setting.utility_threshold,
+        utility_offset: reminder_setting.utility_offset,
+        channels: reminder_setting.channels,
+    })
+}
 
-    // create event
-    let external_event = external_event_service::add(&mut sp, user.user_id)
+async fn fill_reminder(
+    _con: &mut impl GenericClient,
+    reminder: Reminder,
+) -> Result<response::Reminder, response::TodoAppError> {
+    Ok(response::Reminder {
+        reminder_id: reminder.reminder_id,
+        creation_time: reminder.creation_time,
+        creator_user_id: reminder.creator_user_id,
+        kind: reminder.kind,
+        goal_id: reminder.goal_id,
+        goal_event_id: reminder.goal_event_id,
+        due_time: reminder.due_time,
+        fire_time: reminder.fire_time,
+    })
+}
+
+async fn fill_reminder_delivery_batch(
+    con: &mut impl GenericClient,
+    reminder_deliveries: Vec<ReminderDelivery>,
+) -> Result<Vec<response::ReminderDelivery>, response::TodoAppError> {
+    let reminder_ids: Vec<i64> = reminder_deliveries.iter().map(|x| x.reminder_id).collect();
+    let reminders = reminder_service::get_by_reminder_ids(con, &reminder_ids)
         .await
         .map_err(report_postgres_err)?;
+    let reminders = by_id(reminders, |x| x.reminder_id);
+
+    let mut resp_reminder_deliveries = vec![];
+    for reminder_delivery in reminder_deliveries {
+        let reminder = reminders
+            .get(&reminder_delivery.reminder_id)
+            .cloned()
+            .ok_or(response::TodoAppError::ReminderNonexistent)?;
+
+        resp_reminder_deliveries.push(response::ReminderDelivery {
+            reminder_delivery_id: reminder_delivery.reminder_delivery_id,
+            creation_time: reminder_delivery.creation_time,
+            creator_user_id: reminder_delivery.creator_user_id,
+            reminder: fill_reminder(con, reminder).await?,
+            channel: reminder_delivery.channel,
+            attempt: reminder_delivery.attempt,
+            status: reminder_delivery.status,
+            error: reminder_delivery.error,
+        });
+    }
 
-    // create data
-    let external_event_data = external_event_data_service::add(
-        &mut sp,
-        user.user_id,
-        external_event.external_event_id,
-        props.name,
-        props.start_time,
-        props.end_time,
-        true,
+    Ok(resp_reminder_deliveries)
+}
+
+async fn fill_mail_setting(
+    _con: &mut impl GenericClient,
+    mail_setting: MailSetting,
+) -> Result<response::MailSetting, response::TodoAppError> {
+    Ok(response::MailSetting {
+        mail_setting_id: mail_setting.mail_setting_id,
+        creation_time: mail_setting.creation_time,
+        creator_user_id: mail_setting.creator_user_id,
+        address: mail_setting.address,
+        digest_time: mail_setting.digest_time,
+        tz: mail_setting.tz,
+    })
+}
+
+// changes are queued for the user's webhooks in the same transaction as the change itself,
+// webhook_client only sends them once that transaction has committed
+async fn queue_goal_created(
+    con: &mut impl GenericClient,
+    goal_data: &GoalData,
+) -> Result<(), tokio_postgres::Error> {
+    webhook_event_service::add_for_user(
+        con,
+        goal_data.creator_user_id,
+        request::WebhookEventKind::GoalCreated,
+        serde_json::json!({ "goalData": goal_data }).to_string(),
     )
-    .await
-    .map_err(report_postgres_err)?;
+    .await?;
+    Ok(())
+}
+
+async fn queue_goal_status_changed(
+    con: &mut impl GenericClient,
+    previous_status: request::GoalDataStatusKind,
+    goal_data: &GoalData,
+) -> Result<(), tokio_postgres::Error> {
+    webhook_event_service::add_for_user(
+        con,
+        goal_data.creator_user_id,
+        request::WebhookEventKind::GoalStatusChanged,
+        serde_json::json!({ "previousStatus": previous_status, "goalData": goal_data }).to_string(),
+    )
+    .await?;
+    Ok(())
+}
+
+// an active event schedules the goal, an inactive one deschedules it
+async fn queue_goal_event(
+    con: &mut impl GenericClient,
+    goal_event: &GoalEvent,
+) -> Result<(), tokio_postgres::Error> {
+    webhook_event_service::add_for_user(
+        con,
+        goal_event.creator_user_id,
+        if goal_event.active {
+            request::WebhookEventKind::GoalEventScheduled
+        } else {
+            request::WebhookEventKind::GoalEventDescheduled
+        },
+        serde_json::json!({ "goalEvent": goal_event }).to_string(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn queue_external_event_changed(
+    con: &mut impl GenericClient,
+    external_event_data: &ExternalEventData,
+) -> Result<(), tokio_postgres::Error> {
+    webhook_event_service::add_for_user(
+        con,
+        external_event_data.creator_user_id,
+        request::WebhookEventKind::ExternalEventChanged,
+        serde_json::json!({ "externalEventData": external_event_data }).to_string(),
+    )
+    .await?;
+    Ok(())
+}
+
+// the shape of a templated goal's time utility function:
+// worthless before the start time and after the deadline, and worth `utility` in between
+fn windowed_utility(
+    utility: i64,
+    min_time: Option<i64>,
+    max_time: Option<i64>,
+) -> (Vec<i64>, Vec<i64>) {
+    let mut start_times = vec![];
+    let mut utils = vec![];
+    if min_time.is_none() && max_time.is_none() {
+        start_times.push(utils::current_time_millis());
+        utils.push(utility);
+    } else {
+        if let Some(min_time) = min_time {
+            // worthless before the start time
+            start_times.push(min_time - 1);
+            utils.push(0);
+            start_times.push(min_time);
+            utils.push(utility);
+        }
+        if let Some(max_time) = max_time {
+            // worthless after the deadline
+            start_times.push(max_time);
+            utils.push(utility);
+            start_times.push(max_time + 1);
+            utils.push(0);
+        }
+    }
+    (start_times, utils)
+}
+
+// recovers the utility and window that windowed_utility put into a time utility function
+pub fn utility_window(
+    time_utility_function: &TimeUtilityFunction,
+) -> (i64, Option<i64>, Option<i64>) {
+    let start_times = &time_utility_function.start_times;
+    let utils = &time_utility_function.utils;
+    let n = utils.len().min(start_times.len());
+    let utility = utils.iter().copied().max().unwrap_or(0);
+    let min_time = match n >= 2 && utils[0] == 0 && utils[1] > 0 {
+        true => Some(start_times[1]),
+        false => None,
+    };
+    let max_time = match n >= 2 && utils[n - 1] == 0 && utils[n - 2] > 0 {
+        true => Some(start_times[n - 2]),
+        false => None,
+    };
+    (utility, min_time, max_time)
+}
+
+// letter priorities (todo.txt, org) map onto utility: none is worth the usual 100 utils, Z 200 and A 2700
+fn priority_utility(priority: Option<char>) -> i64 {
+    match priority {
+        Some(p) => 100 * (2 + ('Z' as i64 - p as i64)),
+        None => 100,
+    }
+}
+
+fn utility_priority(utility: i64) -> Option<char> {
+    if utility < 200 {
+        return None;
+    }
+    let steps = (utility / 100 - 2).min(25) as u8;
+    Some((b'Z' - steps) as char)
+}
+
+fn parse_tz(tz: Option<String>) -> Result<Tz, response::TodoAppError> {
+    match tz {
+        Some(tz) => tz.parse().map_err(|_| response::TodoAppError::DecodeError),
+        None => Ok(Tz::UTC),
+    }
+}
+
+fn local_time(tz: Tz, time: NaiveDateTime) -> i64 {
+    match tz.from_local_datetime(&time).earliest() {
+        Some(x) => x.timestamp_millis(),
+        // skipped by a dst transition
+        None => Utc.from_utc_datetime(&time).timestamp_millis(),
+    }
+}
+
+// the first millisecond of the date in the given timezone
+pub fn local_day_start(tz: Tz, date: NaiveDate) -> i64 {
+    local_time(tz, date.and_time(NaiveTime::MIN))
+}
+
+pub fn local_date(tz: Tz, time: i64) -> Option<NaiveDate> {
+    tz.timestamp_millis_opt(time)
+        .single()
+        .map(|x| x.date_naive())
+}
+
+// tags the goal with the named entity of that name, creating one of the given kind if there is none
+async fn tag_goal_by_name(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    goal_id: i64,
+    name: String,
+    kind: request::NamedEntityKind,
+) -> Result<(), response::TodoAppError> {
+    let existing = named_entity_data_service::get_recent_active_by_name(con, user_id, &name)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let named_entity_id = match existing {
+        Some(named_entity_data) => named_entity_data.named_entity_id,
+        None => {
+            let named_entity = named_entity_service::add(con, user_id)
+                .await
+                .map_err(report_postgres_err)?;
+            named_entity_data_service::add(
+                con,
+                user_id,
+                named_entity.named_entity_id,
+                name,
+                kind,
+                true,
+            )
+            .await
+            .map_err(report_postgres_err)?;
+            named_entity.named_entity_id
+        }
+    };
+
+    goal_entity_tag_service::add(con, user_id, named_entity_id, goal_id, true)
+        .await
+        .map_err(report_postgres_err)?;
+
+    Ok(())
+}
+
+// Applies a goal template to the name of a new goal.
+// This mirrors what the frontend does when a goal is typed in:
+// the first active template with a matching pattern determines the utility and duration,
+// a time utility function is built from the optional bounds,
+// and the goal is tagged with every named entity whose pattern matches.
+// Everything is written through `con`, so the caller decides whether to commit or roll back.
+// We can't run a template's user_generated_code on the server yet, so it is applied without it,
+// and user_generated_code_skipped tells the client that the result may differ from the real thing.
+async fn apply_goal_template(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    name: String,
+    min_time: Option<i64>,
+    max_time: Option<i64>,
+    time_span: Option<(i64, i64)>,
+) -> Result<response::GoalTemplatePreview, response::TodoAppError> {
+    let goal_template_patterns = goal_template_pattern_service::get_matching(con, user_id, &name)
+        .await
+        .map_err(report_postgres_err)?;
+
+    // get the first template that is still active
+    let mut goal_template_data = None;
+    for goal_template_pattern in goal_template_patterns {
+        let maybe_goal_template_data = goal_template_data_service::get_recent_by_goal_template_id(
+            con,
+            goal_template_pattern.goal_template_id,
+        )
+        .await
+        .map_err(report_postgres_err)?;
+
+        if let Some(gtd) = maybe_goal_template_data.filter(|x| x.active) {
+            goal_template_data = Some(gtd);
+            break;
+        }
+    }
+
+    let mut user_generated_code_skipped = false;
+    if let Some(ref gtd) = goal_template_data {
+        let user_generated_code = user_generated_code_service::get_by_user_generated_code_id(
+            con,
+            gtd.user_generated_code_id,
+        )
+        .await
+        .map_err(report_postgres_err)?
+        .ok_or(response::TodoAppError::UserGeneratedCodeNonexistent)?;
+
+        user_generated_code_skipped = !user_generated_code.source_code.is_empty()
+            || !user_generated_code.wasm_cache.is_empty();
+    }
+
+    // untemplated goals default to 100 utils and an hour of work
+    let (utility, duration_estimate) = match goal_template_data {
+        Some(ref gtd) => (gtd.utility, gtd.duration_estimate),
+        None => (100, Some(1000 * 60 * 60)),
+    };
+
+    // construct time utility function
+    let (start_times, utils) = windowed_utility(utility, min_time, max_time);
+
+    let time_utility_function =
+        time_utility_function_service::add(con, user_id, start_times, utils)
+            .await
+            .map_err(report_postgres_err)?;
+
+    let goal = goal_service::add(con, user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let goal_data = goal_data_service::add(
+        con,
+        user_id,
+        goal.goal_id,
+        name.clone(),
+        duration_estimate,
+        time_utility_function.time_utility_function_id,
+        request::GoalDataStatusKind::Pending,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+    queue_goal_created(con, &goal_data)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let mut goal_events = vec![];
+    if let Some((start_time, end_time)) = time_span {
+        let goal_event =
+            goal_event_service::add(con, user_id, goal.goal_id, start_time, end_time, true)
+                .await
+                .map_err(report_postgres_err)?;
+        queue_goal_event(con, &goal_event)
+            .await
+            .map_err(report_postgres_err)?;
+        goal_events.push(goal_event);
+    }
+
+    // tag the goal with every matching named entity
+    let named_entity_patterns = named_entity_pattern_service::get_matching(con, user_id, &name)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let mut goal_entity_tags = vec![];
+    for named_entity_pattern in named_entity_patterns {
+        if goal_entity_tags
+            .iter()
+            .any(|x: &GoalEntityTag| x.named_entity_id == named_entity_pattern.named_entity_id)
+        {
+            continue;
+        }
+        goal_entity_tags.push(
+            goal_entity_tag_service::add(
+                con,
+                user_id,
+                named_entity_pattern.named_entity_id,
+                goal.goal_id,
+                true,
+            )
+            .await
+            .map_err(report_postgres_err)?,
+        );
+    }
+
+    // fill everything while the rows are still visible to us
+    let resp_goal_events = fill_goal_event_batch(con, goal_events).await?;
+    let resp_goal_entity_tags = fill_goal_entity_tag_batch(con, goal_entity_tags).await?;
+
+    Ok(response::GoalTemplatePreview {
+        goal_template_data: match goal_template_data {
+            Some(gtd) => Some(fill_goal_template_data(con, gtd).await?),
+            None => None,
+        },
+        goal_data: vec![fill_goal_data(con, goal_data).await?],
+        goal_event: resp_goal_events,
+        goal_dependency: vec![],
+        goal_entity_tag: resp_goal_entity_tags,
+        user_generated_code_skipped,
+    })
+}
+
+pub async fn get_user_if_api_key_valid(
+    auth: &Auth,
+    api_key: String,
+) -> Result<User, response::TodoAppError> {
+    auth.get_user_by_api_key_if_valid(api_key)
+        .await
+        .map_err(report_auth_err)
+}
+
+pub async fn api_info(
+    config: Config,
+    _db: Db,
+    auth: Auth,
+) -> Result<response::Info, response::TodoAppError> {
+    let auth_info = auth.info().await.map_err(report_auth_err)?;
+    Ok(response::Info {
+        service: crate::SERVICE_NAME.into(),
+        version_major: 0,
+        version_minor: 0,
+        version_rev: 1,
+        app_pub_origin: config.app_pub_origin,
+        auth_pub_api_href: auth_info.app_pub_api_href,
+        auth_authenticator_href: auth_info.app_authenticator_href,
+    })
+}
+
+pub async fn local_user_new(
+    config: Config,
+    db: Db,
+    _auth: Auth,
+    props: request::LocalUserNewProps,
+) -> Result<response::LocalUser, response::TodoAppError> {
+    // there are no local users unless local auth is on
+    if !config.local_auth {
+        return Err(response::TodoAppError::NotFound);
+    }
+
+    // only lets people in if we were told to
+    if !config.local_auth_signup {
+        return Err(response::TodoAppError::NoCapability);
+    }
+
+    let email = props.email.trim().to_lowercase();
+    if email.is_empty() {
+        return Err(response::TodoAppError::DecodeError);
+    }
+
+    if props.password.chars().count() < local_auth::MIN_PASSWORD_LEN {
+        return Err(response::TodoAppError::PasswordInsecure);
+    }
+
+    let password_hash = local_auth::hash_password(props.password)
+        .await
+        .map_err(report_local_auth_err)?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let local_user = local_user_service::add(con, email, password_hash)
+        .await
+        .map_err(report_postgres_err)?
+        .ok_or(response::TodoAppError::LocalUserExistent)?;
+
+    Ok(response::LocalUser {
+        local_user_id: local_user.local_user_id,
+        creation_time: local_user.creation_time,
+        email: local_user.email,
+    })
+}
+
+// logs in, the key is only ever returned here
+pub async fn local_api_key_new_valid(
+    config: Config,
+    db: Db,
+    _auth: Auth,
+    props: request::LocalApiKeyNewValidProps,
+) -> Result<response::LocalApiKey, response::TodoAppError> {
+    // there are no local users unless local auth is on
+    if !config.local_auth {
+        return Err(response::TodoAppError::NotFound);
+    }
+
+    if props.duration <= 0 {
+        return Err(response::TodoAppError::NegativeDuration);
+    }
+
+    let email = props.email.trim().to_lowercase();
+    if config.login_attempts.locked(&email).await {
+        return Err(response::TodoAppError::LoginAttemptsExceeded);
+    }
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    // a wrong email is reported the same way as a wrong password, and takes as long to check
+    let local_user = local_user_service::get_by_email(con, &email)
+        .await
+        .map_err(report_postgres_err)?;
+    let password_hash = match &local_user {
+        Some(local_user) => local_user.password_hash.clone(),
+        None => local_auth::DUMMY_PASSWORD_HASH.to_owned(),
+    };
+    let valid = local_auth::verify_password(props.password, password_hash)
+        .await
+        .map_err(report_local_auth_err)?;
+    let local_user = match local_user {
+        Some(local_user) if valid => local_user,
+        _ => {
+            config.login_attempts.failed(&email).await;
+            return Err(response::TodoAppError::Unauthorized);
+        }
+    };
+    config.login_attempts.succeeded(&email).await;
+
+    let key = utils::gen_random_token();
+
+    let local_api_key = local_api_key_service::add(
+        con,
+        local_user.local_user_id,
+        utils::hash_token(&key),
+        props.duration,
+        true,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    Ok(response::LocalApiKey {
+        local_api_key_id: local_api_key.local_api_key_id,
+        creation_time: local_api_key.creation_time,
+        creator_user_id: local_api_key.creator_user_id,
+        key: Some(key),
+        duration: local_api_key.duration,
+        active: local_api_key.active,
+    })
+}
+
+// logs out, the key stops working right away
+pub async fn local_api_key_new_cancel(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::LocalApiKeyNewCancelProps,
+) -> Result<response::LocalApiKey, response::TodoAppError> {
+    // there are no local users unless local auth is on
+    if !config.local_auth {
+        return Err(response::TodoAppError::NotFound);
+    }
+
+    // validate api key
+    get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let key_hash = utils::hash_token(&props.api_key);
+    let local_api_key = local_api_key_service::get_recent_by_key_hash(con, &key_hash)
+        .await
+        .map_err(report_postgres_err)?
+        .ok_or(response::TodoAppError::Unauthorized)?;
+
+    let local_api_key = local_api_key_service::add(
+        con,
+        local_api_key.creator_user_id,
+        key_hash,
+        local_api_key.duration,
+        false,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    auth.forget(&props.api_key).await;
+
+    Ok(response::LocalApiKey {
+        local_api_key_id: local_api_key.local_api_key_id,
+        creation_time: local_api_key.creation_time,
+        creator_user_id: local_api_key.creator_user_id,
+        key: None,
+        duration: local_api_key.duration,
+        active: local_api_key.active,
+    })
+}
+
+// streams the whole account as a json document
+// the connection is held until the client has read everything
+pub async fn account_export(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: account_service::AccountExportProps,
+) -> Result<warp::hyper::Body, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    let mut con = db.get().await.map_err(report_pool_err)?;
+
+    let (mut sender, body) = warp::hyper::Body::channel();
+
+    tokio::spawn(async move {
+        let export_time = utils::current_time_millis();
+        let con = &mut **con;
+        if let Err(e) = account_service::export(con, user.user_id, export_time, &mut sender).await {
+            utils::log(utils::Event {
+                msg: e.to_string(),
+                source: Some("account export".to_owned()),
+                severity: utils::SeverityKind::Error,
+            });
+            // make sure the client can tell the document is incomplete
+            sender.abort();
+        }
+    });
+
+    Ok(body)
+}
+
+// streams the user's changes as server-sent events, see change_stream
+pub async fn change_stream(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    changes: change_stream::Changes,
+    props: request::ChangeStreamProps,
+) -> Result<warp::hyper::Body, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // subscribe before finding out where to start, so that nothing that happens in between is missed
+    let notices = changes.subscribe();
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let (last_event_id, reset) = match props.last_event_id {
+        Some(last_event_id) => {
+            // if any of the user's events after it were pruned, the client has missed them for good
+            let last_pruned_id = change_event_service::get_last_pruned_id(con, user.user_id)
+                .await
+                .map_err(report_postgres_err)?;
+            (
+                last_event_id,
+                last_pruned_id.is_some_and(|last_pruned_id| last_pruned_id > last_event_id),
+            )
+        }
+        // only send what happens from now on
+        None => (
+            change_event_service::get_high_water_mark(con, user.user_id)
+                .await
+                .map_err(report_postgres_err)?,
+            false,
+        ),
+    };
+
+    let (sender, body) = warp::hyper::Body::channel();
+
+    tokio::spawn(async move {
+        if let Err(e) =
+            change_stream::stream(db, notices, user.user_id, last_event_id, reset, sender).await
+        {
+            // the client reconnects and resumes where it left off
+            utils::log(utils::Event {
+                msg: e.to_string(),
+                source: Some("change stream".to_owned()),
+                severity: utils::SeverityKind::Error,
+            });
+        }
+    });
+
+    Ok(body)
+}
+
+// recreates an exported account (possibly from another instance) under the current user
+pub async fn account_import(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: account_service::AccountImportProps,
+) -> Result<BTreeMap<&'static str, usize>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let counts = account_service::import(&mut sp, user.user_id, props.account)
+        .await
+        .map_err(|e| match e {
+            account_service::ImportError::Postgres(e) => report_postgres_err(e),
+            account_service::ImportError::Invalid(msg) => {
+                utils::log(utils::Event {
+                    msg,
+                    source: Some("account import".to_owned()),
+                    severity: utils::SeverityKind::Info,
+                });
+                response::TodoAppError::DecodeError
+            }
+        })?;
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // return json
+    Ok(counts)
+}
+
+// applies the client's offline changes, and sends every row it is missing, see sync_service
+pub async fn sync(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: sync_service::SyncProps,
+) -> Result<sync_service::SyncResult, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let report_sync_err = |e| match e {
+        sync_service::SyncError::Postgres(e) => report_postgres_err(e),
+        sync_service::SyncError::Invalid(msg) => {
+            utils::log(utils::Event {
+                msg,
+                source: Some("sync".to_owned()),
+                severity: utils::SeverityKind::Info,
+            });
+            response::TodoAppError::DecodeError
+        }
+    };
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let applied = sync_service::apply(&mut sp, user.user_id, props.upload)
+        .await
+        .map_err(report_sync_err)?;
+
+    // webhooks hear about offline changes like about any other
+    for (previous_status, goal_data) in applied.goal_data {
+        if let Some(previous_status) = previous_status {
+            if previous_status.clone() as i64 != goal_data.status.clone() as i64 {
+                queue_goal_status_changed(&mut sp, previous_status, &goal_data)
+                    .await
+                    .map_err(report_postgres_err)?;
+            }
+        } else {
+            queue_goal_created(&mut sp, &goal_data)
+                .await
+                .map_err(report_postgres_err)?;
+        }
+    }
+    for goal_event in applied.goal_event {
+        queue_goal_event(&mut sp, &goal_event)
+            .await
+            .map_err(report_postgres_err)?;
+    }
+    for external_event_data in applied.external_event_data {
+        queue_external_event_changed(&mut sp, &external_event_data)
+            .await
+            .map_err(report_postgres_err)?;
+    }
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // the upload is committed first, so that the user's other writes don't wait for the read.
+    // if the read fails the client retries the upload like it would if the response was lost
+    let mut result = sync_service::read(con, user.user_id, props.since)
+        .await
+        .map_err(report_sync_err)?;
+    result.id_map = applied.id_map;
+
+    // return json
+    Ok(result)
+}
+
+pub async fn calendar_feed_new(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::CalendarFeedNewProps,
+) -> Result<response::CalendarFeed, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // the token is only ever returned here, we only keep its hash
+    let token = utils::gen_random_token();
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    // this supersedes any previous token
+    let calendar_feed = calendar_feed_service::add(
+        con,
+        user.user_id,
+        utils::hash_token(&token),
+        props.active,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    // return json
+    Ok(response::CalendarFeed {
+        calendar_feed_id: calendar_feed.calendar_feed_id,
+        creation_time: calendar_feed.creation_time,
+        creator_user_id: calendar_feed.creator_user_id,
+        url: format!("{}/public/calendar_feed/{}.ics", config.app_pub_origin, token),
+        token,
+        active: calendar_feed.active,
+    })
+}
+
+// serves the schedule as an iCalendar file
+// calendar clients can't send api keys, so the secret token in the url is the credential
+pub async fn calendar_feed_ics(
+    _config: Config,
+    db: Db,
+    _auth: Auth,
+    token: String,
+) -> Result<String, response::TodoAppError> {
+    let token = token.trim_end_matches(".ics");
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let calendar_feed =
+        calendar_feed_service::get_active_by_token_hash(con, &utils::hash_token(token))
+            .await
+            .map_err(report_postgres_err)?
+            .ok_or(response::TodoAppError::Unauthorized)?;
+
+    let events = calendar_entries(con, calendar_feed.creator_user_id)
+        .await?
+        .into_iter()
+        .map(|e| e.event)
+        .collect::<Vec<_>>();
+
+    Ok(ical::write_calendar("Todo App", &events))
+}
+
+// the source of external events that were created by a caldav client
+static CALDAV_SOURCE: &str = "caldav";
+
+// an event in the user's calendar, along with what it is backed by
+struct CalendarEntry {
+    // the name of its caldav resource
+    name: String,
+    goal_id: Option<i64>,
+    external_event_id: Option<i64>,
+    event: ical::Event,
+}
+
+// every scheduled goal and active external event of the user
+// uids are per goal and per external event, so that rescheduling updates the existing entry
+async fn calendar_entries(
+    con: &mut impl GenericClient,
+    user_id: i64,
+) -> Result<Vec<CalendarEntry>, response::TodoAppError> {
+    let goal_events = goal_event_service::get_recent_active_with_name(con, user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let external_event_data = external_event_data_service::get_recent_active(con, user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let external_event_ids: Vec<i64> = external_event_data
+        .iter()
+        .map(|x| x.external_event_id)
+        .collect();
+    let external_events =
+        external_event_service::get_by_external_event_ids(con, &external_event_ids)
+            .await
+            .map_err(report_postgres_err)?;
+    let external_events = by_id(external_events, |x| x.external_event_id);
+
+    let mut entries = vec![];
+    for (goal_event, name) in goal_events {
+        entries.push(CalendarEntry {
+            name: format!("goal-{}", goal_event.goal_id),
+            goal_id: Some(goal_event.goal_id),
+            external_event_id: None,
+            event: ical::Event {
+                uid: format!("goal-{}@{}", goal_event.goal_id, crate::SERVICE_NAME),
+                name,
+                start_time: goal_event.start_time,
+                end_time: goal_event.end_time,
+            },
+        });
+    }
+    for eed in external_event_data {
+        let external_event = external_events
+            .get(&eed.external_event_id)
+            .cloned()
+            .ok_or(response::TodoAppError::ExternalEventNonexistent)?;
+
+        // events created by a caldav client keep the name and uid they were created with
+        let (name, uid) = match (external_event.source, external_event.external_uid) {
+            (Some(source), Some(external_uid)) if source == CALDAV_SOURCE => (
+                external_event
+                    .resource_name
+                    .unwrap_or_else(|| external_uid.clone()),
+                external_uid,
+            ),
+            _ => {
+                let name = format!("external_event-{}", eed.external_event_id);
+                let uid = format!("{}@{}", name, crate::SERVICE_NAME);
+                (name, uid)
+            }
+        };
+
+        entries.push(CalendarEntry {
+            event: ical::Event {
+                uid,
+                name: eed.name,
+                start_time: eed.start_time,
+                end_time: eed.end_time,
+            },
+            name,
+            goal_id: None,
+            external_event_id: Some(eed.external_event_id),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn report_caldav_err(e: caldav::CaldavError) -> response::TodoAppError {
+    utils::log(utils::Event {
+        msg: e.0,
+        source: Some("caldav".to_owned()),
+        severity: utils::SeverityKind::Info,
+    });
+    response::TodoAppError::DecodeError
+}
+
+// changes whenever any event in the calendar changes
+fn calendar_ctag(entries: &[CalendarEntry]) -> String {
+    let etags = entries
+        .iter()
+        .map(|e| format!("{}={}", e.name, caldav::etag(&e.event)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("\"{}\"", &utils::hash_token(&etags)[..32])
+}
+
+fn calendar_data(entry: &CalendarEntry) -> String {
+    ical::write_calendar("Todo App", std::slice::from_ref(&entry.event))
+}
+
+// a minimal CalDAV server over the same events as the calendar feed
+// desktop clients authenticate with basic auth, using an api key as the password
+// edits append new goal_event, goal_data and external_event_data rows
+pub async fn caldav(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    request: caldav::Request,
+) -> Result<caldav::Response, response::TodoAppError> {
+    // clients probe for caldav support before logging in
+    if request.method == "OPTIONS" {
+        return Ok(caldav::Response::options());
+    }
+
+    // validate api key
+    let api_key = request
+        .authorization
+        .as_deref()
+        .and_then(caldav::parse_basic_auth)
+        .ok_or(response::TodoAppError::Unauthorized)?;
+    let user = get_user_if_api_key_valid(&auth, api_key).await?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let path = caldav::parse_path(&request.path);
+    if path == caldav::Path::Unknown {
+        return Ok(caldav::Response::empty(404));
+    }
+
+    match (request.method.as_str(), path) {
+        ("PROPFIND", path) => caldav_propfind(con, user.user_id, path, request).await,
+        ("REPORT", caldav::Path::Calendar) => caldav_report(con, user.user_id, request).await,
+        ("GET", caldav::Path::Calendar) => {
+            let events = calendar_entries(con, user.user_id)
+                .await?
+                .into_iter()
+                .map(|e| e.event)
+                .collect::<Vec<_>>();
+            Ok(caldav::Response::calendar(
+                ical::write_calendar("Todo App", &events),
+                None,
+            ))
+        }
+        ("GET", caldav::Path::Event(name)) => {
+            let entries = calendar_entries(con, user.user_id).await?;
+            match entries.iter().find(|e| e.name == name) {
+                Some(entry) => Ok(caldav::Response::calendar(
+                    calendar_data(entry),
+                    Some(caldav::etag(&entry.event)),
+                )),
+                None => Ok(caldav::Response::empty(404)),
+            }
+        }
+        ("PUT", caldav::Path::Event(name)) => caldav_put(con, user.user_id, name, request).await,
+        ("DELETE", caldav::Path::Event(name)) => {
+            caldav_delete(con, user.user_id, name, request).await
+        }
+        _ => Ok(caldav::Response::empty(405)),
+    }
+}
+
+async fn caldav_propfind(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    path: caldav::Path,
+    request: caldav::Request,
+) -> Result<caldav::Response, response::TodoAppError> {
+    let requested = caldav::parse_propfind(&request.body).map_err(report_caldav_err)?;
+    let children = caldav::depth_includes_children(request.depth.as_deref());
+
+    let entries = calendar_entries(con, user_id).await?;
+
+    let calendar = caldav::Entry::Found {
+        href: caldav::CALENDAR_HREF.to_owned(),
+        props: caldav::calendar_props(&calendar_ctag(&entries)),
+    };
+
+    let mut responses = vec![];
+    match path {
+        caldav::Path::Home => {
+            responses.push(caldav::Entry::Found {
+                href: caldav::HOME_HREF.to_owned(),
+                props: caldav::home_props(),
+            });
+            if children {
+                responses.push(calendar);
+            }
+        }
+        caldav::Path::Calendar => {
+            responses.push(calendar);
+            if children {
+                for entry in entries.iter() {
+                    responses.push(caldav::Entry::Found {
+                        href: caldav::event_href(&entry.name),
+                        props: caldav::event_props(&caldav::etag(&entry.event), None),
+                    });
+                }
+            }
+        }
+        caldav::Path::Event(name) => match entries.iter().find(|e| e.name == name) {
+            Some(entry) => responses.push(caldav::Entry::Found {
+                href: caldav::event_href(&entry.name),
+                props: caldav::event_props(&caldav::etag(&entry.event), None),
+            }),
+            None => return Ok(caldav::Response::empty(404)),
+        },
+        caldav::Path::Unknown => return Ok(caldav::Response::empty(404)),
+    }
+
+    Ok(caldav::Response::multistatus(caldav::write_multistatus(
+        &responses,
+        requested.as_deref(),
+    )))
+}
+
+async fn caldav_report(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    request: caldav::Request,
+) -> Result<caldav::Response, response::TodoAppError> {
+    let report = caldav::parse_report(&request.body).map_err(report_caldav_err)?;
+
+    let entries = calendar_entries(con, user_id).await?;
+
+    let found = |entry: &CalendarEntry| caldav::Entry::Found {
+        href: caldav::event_href(&entry.name),
+        props: caldav::event_props(&caldav::etag(&entry.event), Some(calendar_data(entry))),
+    };
+
+    let (responses, requested) = match report {
+        caldav::Report::CalendarQuery {
+            props,
+            min_time,
+            max_time,
+        } => (
+            entries
+                .iter()
+                .filter(|e| min_time.is_none_or(|min_time| e.event.end_time > min_time))
+                .filter(|e| max_time.is_none_or(|max_time| e.event.start_time < max_time))
+                .map(found)
+                .collect::<Vec<_>>(),
+            props,
+        ),
+        caldav::Report::CalendarMultiget {
+            props,
+            names,
+            hrefs,
+        } => (
+            names
+                .into_iter()
+                .zip(hrefs)
+                .map(
+                    |(name, href)| match entries.iter().find(|e| Some(&e.name) == name.as_ref()) {
+                        Some(entry) => found(entry),
+                        None => caldav::Entry::Missing { href },
+                    },
+                )
+                .collect::<Vec<_>>(),
+            props,
+        ),
+    };
+
+    Ok(caldav::Response::multistatus(caldav::write_multistatus(
+        &responses,
+        requested.as_deref(),
+    )))
+}
+
+// enforces If-Match and If-None-Match, returns false if the request should be rejected
+fn caldav_preconditions(request: &caldav::Request, current: Option<&CalendarEntry>) -> bool {
+    let etag = current.map(|e| caldav::etag(&e.event));
+    if let Some(ref if_match) = request.if_match {
+        if !caldav::etag_matches(if_match, etag.as_deref()) {
+            return false;
+        }
+    }
+    if let Some(ref if_none_match) = request.if_none_match {
+        if caldav::etag_matches(if_none_match, etag.as_deref()) {
+            return false;
+        }
+    }
+    true
+}
+
+async fn caldav_put(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    name: String,
+    request: caldav::Request,
+) -> Result<caldav::Response, response::TodoAppError> {
+    let entries = calendar_entries(con, user_id).await?;
+    let current = entries.iter().find(|e| e.name == name);
+
+    if !caldav_preconditions(&request, current) {
+        return Ok(caldav::Response::empty(412));
+    }
+
+    // we store a single time range per resource, so recurring events are refused
+    let events = ical::parse_events(&request.body, i64::MIN, i64::MAX)
+        .map_err(|e| report_caldav_err(caldav::CaldavError(e.0)))?;
+    let event = match events.as_slice() {
+        [event] => event.clone(),
+        _ => return Ok(caldav::Response::empty(403)),
+    };
+
+    // a resource keeps its uid, changing it would make it another event
+    if current.is_some_and(|c| c.event.uid != event.uid) {
+        return Ok(caldav::Response::empty(409));
+    }
+
+    // validate
+    if event.start_time < 0 {
+        return Err(response::TodoAppError::NegativeStartTime);
+    }
+
+    let rescheduled = current.map(|c| (c.event.start_time, c.event.end_time))
+        != Some((event.start_time, event.end_time));
+    let renamed = current.map(|c| &c.event.name) != Some(&event.name);
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let goal_id = current.and_then(|c| c.goal_id).or_else(|| {
+        name.strip_prefix("goal-")
+            .and_then(|x| x.parse::<i64>().ok())
+    });
+    let external_event_id = current.and_then(|c| c.external_event_id).or_else(|| {
+        name.strip_prefix("external_event-")
+            .and_then(|x| x.parse::<i64>().ok())
+    });
+
+    if let Some(goal_id) = goal_id {
+        // validate goal is owned by correct user
+        let goal = goal_service::get_by_goal_id(&mut sp, goal_id)
+            .await
+            .map_err(report_postgres_err)?;
+        if goal.map(|g| g.creator_user_id) != Some(user_id) {
+            return Ok(caldav::Response::empty(403));
+        }
+
+        if rescheduled {
+            let goal_event = goal_event_service::add(
+                &mut sp,
+                user_id,
+                goal_id,
+                event.start_time,
+                event.end_time,
+                true,
+            )
+            .await
+            .map_err(report_postgres_err)?;
+            queue_goal_event(&mut sp, &goal_event)
+                .await
+                .map_err(report_postgres_err)?;
+        }
+
+        if renamed {
+            let goal_data = goal_data_service::get_recent_by_goal_id(&mut sp, goal_id)
+                .await
+                .map_err(report_postgres_err)?
+                .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+            if goal_data.name != event.name {
+                goal_data_service::add(
+                    &mut sp,
+                    user_id,
+                    goal_id,
+                    event.name,
+                    goal_data.duration_estimate,
+                    goal_data.time_utility_function_id,
+                    goal_data.status,
+                )
+                .await
+                .map_err(report_postgres_err)?;
+            }
+        }
+    } else {
+        let external_event = match external_event_id {
+            Some(external_event_id) => {
+                external_event_service::get_by_external_event_id(&mut sp, external_event_id)
+                    .await
+                    .map_err(report_postgres_err)?
+            }
+            // a resource that was deleted is recreated with the same event, unless its uid changed
+            None => {
+                external_event_service::get_by_resource_name(&mut sp, user_id, CALDAV_SOURCE, &name)
+                    .await
+                    .map_err(report_postgres_err)?
+                    .filter(|x| x.external_uid.as_ref() == Some(&event.uid))
+            }
+        };
+
+        let external_event = match external_event {
+            // validate event is owned by correct user
+            Some(external_event) if external_event.creator_user_id != user_id => {
+                return Ok(caldav::Response::empty(403));
+            }
+            Some(external_event) => external_event,
+            // names we handed out ourselves can't be created by the client
+            None if external_event_id.is_some() => return Ok(caldav::Response::empty(403)),
+            None => {
+                // the uid already belongs to another resource
+                let taken = external_event_service::get_by_external_uid(
+                    &mut sp,
+                    user_id,
+                    CALDAV_SOURCE,
+                    &event.uid,
+                )
+                .await
+                .map_err(report_postgres_err)?;
+                if taken.is_some() {
+                    return Ok(caldav::Response::empty(409));
+                }
+
+                external_event_service::add(
+                    &mut sp,
+                    user_id,
+                    Some(CALDAV_SOURCE.to_owned()),
+                    Some(event.uid.clone()),
+                    Some(name),
+                )
+                .await
+                .map_err(report_postgres_err)?
+            }
+        };
+
+        if rescheduled || renamed {
+            let external_event_data = external_event_data_service::add(
+                &mut sp,
+                user_id,
+                external_event.external_event_id,
+                event.name,
+                event.start_time,
+                event.end_time,
+                true,
+            )
+            .await
+            .map_err(report_postgres_err)?;
+            queue_external_event_changed(&mut sp, &external_event_data)
+                .await
+                .map_err(report_postgres_err)?;
+        }
+    }
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // we don't send an etag, since what we store is not byte for byte what the client sent
+    Ok(caldav::Response::empty(if current.is_some() {
+        204
+    } else {
+        201
+    }))
+}
+
+// goals are only descheduled, the goal itself stays around
+async fn caldav_delete(
+    con: &mut tokio_postgres::Client,
+    user_id: i64,
+    name: String,
+    request: caldav::Request,
+) -> Result<caldav::Response, response::TodoAppError> {
+    let entries = calendar_entries(con, user_id).await?;
+    let current = match entries.iter().find(|e| e.name == name) {
+        Some(current) => current,
+        None => return Ok(caldav::Response::empty(404)),
+    };
+
+    if !caldav_preconditions(&request, Some(current)) {
+        return Ok(caldav::Response::empty(412));
+    }
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    if let Some(goal_id) = current.goal_id {
+        let goal_event = goal_event_service::add(
+            &mut sp,
+            user_id,
+            goal_id,
+            current.event.start_time,
+            current.event.end_time,
+            false,
+        )
+        .await
+        .map_err(report_postgres_err)?;
+        queue_goal_event(&mut sp, &goal_event)
+            .await
+            .map_err(report_postgres_err)?;
+    }
+
+    if let Some(external_event_id) = current.external_event_id {
+        let external_event_data = external_event_data_service::add(
+            &mut sp,
+            user_id,
+            external_event_id,
+            current.event.name.clone(),
+            current.event.start_time,
+            current.event.end_time,
+            false,
+        )
+        .await
+        .map_err(report_postgres_err)?;
+        queue_external_event_changed(&mut sp, &external_event_data)
+            .await
+            .map_err(report_postgres_err)?;
+    }
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    Ok(caldav::Response::empty(204))
+}
+
+// the events of the collection are pulled in periodically by the caldav sync worker
+// subscribing to the same url again replaces the credentials, pass `active: false` to unsubscribe
+pub async fn caldav_subscription_new(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::CaldavSubscriptionNewProps,
+) -> Result<response::CaldavSubscription, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // the password can't be stored without a key to seal it with
+    let secret_box = config.secret_box.ok_or(response::TodoAppError::NotFound)?;
+
+    // validate
+    if !props.url.starts_with("https://") && !props.url.starts_with("http://") {
+        return Err(response::TodoAppError::DecodeError);
+    }
+    caldav_client::check_url(&props.url, config.caldav_allow_private_addresses)
+        .await
+        .map_err(|_| response::TodoAppError::UrlForbidden)?;
+
+    let encrypted_password = secret_box
+        .seal(&props.password)
+        .map_err(report_secret_box_err)?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let caldav_subscription = caldav_subscription_service::add(
+        con,
+        user.user_id,
+        props.url,
+        props.username,
+        encrypted_password,
+        props.active,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    // return json
+    fill_caldav_subscription(con, caldav_subscription).await
+}
+
+pub async fn caldav_subscription_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::CaldavSubscriptionViewProps,
+) -> Result<page::Page<response::CaldavSubscription>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        caldav_subscription_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, caldav_subscription_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get caldav_subscriptions
+    let caldav_subscription =
+        caldav_subscription_service::query(con, user.user_id, &page_query, props)
+            .await
+            .map_err(report_postgres_err)?;
+    // return caldav_subscriptions
+    let mut resp_caldav_subscriptions = vec![];
+    for u in caldav_subscription.items {
+        resp_caldav_subscriptions.push(fill_caldav_subscription(con, u).await?);
+    }
+
+    Ok(page::Page {
+        items: resp_caldav_subscriptions,
+        next_cursor: caldav_subscription.next_cursor,
+    })
+}
+
+// with `only_recent` this is the current sync status of each subscription
+pub async fn caldav_sync_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::CaldavSyncViewProps,
+) -> Result<page::Page<response::CaldavSync>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        caldav_sync_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, caldav_sync_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get caldav_syncs
+    let caldav_sync = caldav_sync_service::query(con, user.user_id, &page_query, props)
+        .await
+        .map_err(report_postgres_err)?;
+    // return caldav_syncs
+    Ok(page::Page {
+        items: fill_caldav_sync_batch(con, caldav_sync.items).await?,
+        next_cursor: caldav_sync.next_cursor,
+    })
+}
+
+// every payload is POSTed as json, signed with the secret, see webhook_client
+// registering the same url again replaces the secret and event kinds, pass `active: false` to remove it
+pub async fn webhook_new(
+    config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::WebhookNewProps,
+) -> Result<response::Webhook, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // validate
+    if !props.url.starts_with("https://") && !props.url.starts_with("http://") {
+        return Err(response::TodoAppError::DecodeError);
+    }
+    caldav_client::check_url(&props.url, config.caldav_allow_private_addresses)
+        .await
+        .map_err(|_| response::TodoAppError::UrlForbidden)?;
+    if props.secret.is_empty() {
+        return Err(response::TodoAppError::DecodeError);
+    }
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let webhook = webhook_service::add(
+        con,
+        user.user_id,
+        props.url,
+        props.secret,
+        props.event_kinds,
+        props.active,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    // return json
+    fill_webhook(con, webhook).await
+}
+
+pub async fn webhook_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::WebhookViewProps,
+) -> Result<page::Page<response::Webhook>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        webhook_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, webhook_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get webhooks
+    let webhook = webhook_service::query(con, user.user_id, &page_query, props)
+        .await
+        .map_err(report_postgres_err)?;
+    // return webhooks
+    let mut resp_webhooks = vec![];
+    for u in webhook.items {
+        resp_webhooks.push(fill_webhook(con, u).await?);
+    }
+
+    Ok(page::Page {
+        items: resp_webhooks,
+        next_cursor: webhook.next_cursor,
+    })
+}
+
+// every attempt to deliver an event, successful or not
+pub async fn webhook_delivery_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::WebhookDeliveryViewProps,
+) -> Result<page::Page<response::WebhookDelivery>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        webhook_delivery_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, webhook_delivery_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get webhook_deliveries
+    let webhook_delivery = webhook_delivery_service::query(con, user.user_id, &page_query, props)
+        .await
+        .map_err(report_postgres_err)?;
+    // return webhook_deliveries
+    Ok(page::Page {
+        items: fill_webhook_delivery_batch(con, webhook_delivery.items).await?,
+        next_cursor: webhook_delivery.next_cursor,
+    })
+}
+
+pub async fn reminder_setting_new(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::ReminderSettingNewProps,
+) -> Result<response::ReminderSetting, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // validate
+    if props.goal_event_offset.unwrap_or(0) < 0 || props.utility_offset.unwrap_or(0) < 0 {
+        return Err(response::TodoAppError::NegativeDuration);
+    }
+    // a threshold is no use without knowing how long before to remind, and the other way around
+    if props.utility_threshold.is_some() != props.utility_offset.is_some() {
+        return Err(response::TodoAppError::DecodeError);
+    }
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let reminder_setting = reminder_setting_service::add(
+        con,
+        user.user_id,
+        props.goal_event_offset,
+        props.utility_threshold,
+        props.utility_offset,
+        props.channels,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    // return json
+    fill_reminder_setting(con, reminder_setting).await
+}
+
+pub async fn reminder_setting_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::ReminderSettingViewProps,
+) -> Result<page::Page<response::ReminderSetting>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        reminder_setting_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, reminder_setting_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get reminder_settings
+    let reminder_setting = reminder_setting_service::query(con, user.user_id, &page_query, props)
+        .await
+        .map_err(report_postgres_err)?;
+    // return reminder_settings
+    let mut resp_reminder_settings = vec![];
+    for u in reminder_setting.items {
+        resp_reminder_settings.push(fill_reminder_setting(con, u).await?);
+    }
+
+    Ok(page::Page {
+        items: resp_reminder_settings,
+        next_cursor: reminder_setting.next_cursor,
+    })
+}
+
+pub async fn reminder_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::ReminderViewProps,
+) -> Result<page::Page<response::Reminder>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        reminder_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, reminder_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get reminders
+    let reminder = reminder_service::query(con, user.user_id, &page_query, props)
+        .await
+        .map_err(report_postgres_err)?;
+    // return reminders
+    let mut resp_reminders = vec![];
+    for u in reminder.items {
+        resp_reminders.push(fill_reminder(con, u).await?);
+    }
+
+    Ok(page::Page {
+        items: resp_reminders,
+        next_cursor: reminder.next_cursor,
+    })
+}
+
+// every step of sending a reminder, including claims and cancellations
+pub async fn reminder_delivery_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::ReminderDeliveryViewProps,
+) -> Result<page::Page<response::ReminderDelivery>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        reminder_delivery_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, reminder_delivery_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get reminder_deliveries
+    let reminder_delivery = reminder_delivery_service::query(con, user.user_id, &page_query, props)
+        .await
+        .map_err(report_postgres_err)?;
+    // return reminder_deliveries
+    Ok(page::Page {
+        items: fill_reminder_delivery_batch(con, reminder_delivery.items).await?,
+        next_cursor: reminder_delivery.next_cursor,
+    })
+}
+
+pub async fn mail_setting_new(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::MailSettingNewProps,
+) -> Result<response::MailSetting, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // validate
+    if !props.address.contains('@') {
+        return Err(response::TodoAppError::DecodeError);
+    }
+    // the digest is sent at some point during the day
+    if let Some(digest_time) = props.digest_time {
+        if !(0..24 * 60 * 60 * 1000).contains(&digest_time) {
+            return Err(response::TodoAppError::DecodeError);
+        }
+    }
+    let tz = parse_tz(props.tz)?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mail_setting = mail_setting_service::add(
+        con,
+        user.user_id,
+        props.address,
+        props.digest_time,
+        tz.name().to_owned(),
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    // return json
+    fill_mail_setting(con, mail_setting).await
+}
+
+pub async fn mail_setting_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::MailSettingViewProps,
+) -> Result<page::Page<response::MailSetting>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        mail_setting_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, mail_setting_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get mail_settings
+    let mail_setting = mail_setting_service::query(con, user.user_id, &page_query, props)
+        .await
+        .map_err(report_postgres_err)?;
+    // return mail_settings
+    let mut resp_mail_settings = vec![];
+    for u in mail_setting.items {
+        resp_mail_settings.push(fill_mail_setting(con, u).await?);
+    }
+
+    Ok(page::Page {
+        items: resp_mail_settings,
+        next_cursor: mail_setting.next_cursor,
+    })
+}
+
+// brings the events synced from a source in line with the given (external_uid, name, start_time, end_time) list
+// new revisions are only written when something changed
+// events that are missing from the list (and overlap the window, if given) are marked inactive
+// if the same external_uid appears more than once, only the first one counts
+// returns the current data of every event from the source that was touched
+pub async fn sync_external_events(
+    con: &mut impl GenericClient,
+    user_id: i64,
+    source: &str,
+    events: Vec<(String, String, i64, i64)>,
+    window: Option<(i64, i64)>,
+) -> Result<Vec<ExternalEventData>, tokio_postgres::Error> {
+    let mut existing: HashMap<String, ExternalEventData> =
+        external_event_data_service::get_recent_by_source(con, user_id, source)
+            .await?
+            .into_iter()
+            .collect();
+
+    let mut seen = HashSet::new();
+    let mut external_event_data = vec![];
+    for (external_uid, name, start_time, end_time) in events {
+        if !seen.insert(external_uid.clone()) {
+            continue;
+        }
+        match existing.remove(&external_uid) {
+            // nothing changed
+            Some(eed)
+                if eed.active
+                    && eed.name == name
+                    && eed.start_time == start_time
+                    && eed.end_time == end_time =>
+            {
+                external_event_data.push(eed)
+            }
+            // append a new revision
+            Some(eed) => {
+                let eed = external_event_data_service::add(
+                    con,
+                    user_id,
+                    eed.external_event_id,
+                    name,
+                    start_time,
+                    end_time,
+                    true,
+                )
+                .await?;
+                queue_external_event_changed(con, &eed).await?;
+                external_event_data.push(eed);
+            }
+            // first time we see this event
+            None => {
+                let external_event = external_event_service::add(
+                    con,
+                    user_id,
+                    Some(source.to_owned()),
+                    Some(external_uid),
+                    None,
+                )
+                .await?;
+                let eed = external_event_data_service::add(
+                    con,
+                    user_id,
+                    external_event.external_event_id,
+                    name,
+                    start_time,
+                    end_time,
+                    true,
+                )
+                .await?;
+                queue_external_event_changed(con, &eed).await?;
+                external_event_data.push(eed);
+            }
+        }
+    }
+
+    // whatever is left has vanished from the source
+    let mut vanished = existing
+        .into_values()
+        .filter(|eed| eed.active)
+        .filter(|eed| match window {
+            Some((min_time, max_time)) => eed.start_time < max_time && eed.end_time > min_time,
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    vanished.sort_by_key(|eed| eed.external_event_data_id);
+
+    for eed in vanished {
+        let eed = external_event_data_service::add(
+            con,
+            user_id,
+            eed.external_event_id,
+            eed.name,
+            eed.start_time,
+            eed.end_time,
+            false,
+        )
+        .await?;
+        queue_external_event_changed(con, &eed).await?;
+        external_event_data.push(eed);
+    }
+
+    Ok(external_event_data)
+}
+
+pub async fn external_event_new(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::ExternalEventNewProps,
+) -> Result<response::ExternalEventData, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // validate
+    if props.start_time < 0 {
+        return Err(response::TodoAppError::NegativeStartTime);
+    }
+    if props.start_time >= props.end_time {
+        return Err(response::TodoAppError::NegativeDuration);
+    }
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    // create event
+    let external_event = external_event_service::add(&mut sp, user.user_id, None, None, None)
+        .await
+        .map_err(report_postgres_err)?;
+
+    // create data
+    let external_event_data = external_event_data_service::add(
+        &mut sp,
+        user.user_id,
+        external_event.external_event_id,
+        props.name,
+        props.start_time,
+        props.end_time,
+        true,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+    queue_external_event_changed(&mut sp, &external_event_data)
+        .await
+        .map_err(report_postgres_err)?;
 
     sp.commit().await.map_err(report_postgres_err)?;
 
@@ -422,11 +2714,11 @@ pub async fn external_event_new(
 pub async fn external_event_data_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::ExternalEventDataNewProps,
 ) -> Result<response::ExternalEventData, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
     // validate
     if props.start_time < 0 {
@@ -436,7 +2728,7 @@ pub async fn external_event_data_new(
         return Err(response::TodoAppError::NegativeDuration);
     }
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -451,6 +2743,29 @@ pub async fn external_event_data_new(
         return Err(response::TodoAppError::ExternalEventNonexistent);
     }
 
+    // nothing else of the user's can be written until we commit
+    account_service::lock(&mut sp, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    // reject the change if it wasn't based on the current data
+    if let Some(expected_previous_id) = props.expected_previous_id {
+        let previous_external_event_data =
+            external_event_data_service::get_recent_by_external_event_id(
+                &mut sp,
+                external_event.external_event_id,
+            )
+            .await
+            .map_err(report_postgres_err)?;
+        if let Some(previous_external_event_data) = previous_external_event_data {
+            if previous_external_event_data.external_event_data_id != expected_previous_id {
+                let current =
+                    fill_external_event_data(&mut sp, previous_external_event_data).await?;
+                return Err(response::TodoAppError::ExternalEventDataConflict(current));
+            }
+        }
+    }
+
     // now we can update data
     let external_event_data = external_event_data_service::add(
         &mut sp,
@@ -463,6 +2778,9 @@ pub async fn external_event_data_new(
     )
     .await
     .map_err(report_postgres_err)?;
+    queue_external_event_changed(&mut sp, &external_event_data)
+        .await
+        .map_err(report_postgres_err)?;
 
     sp.commit().await.map_err(report_postgres_err)?;
 
@@ -470,14 +2788,462 @@ pub async fn external_event_data_new(
     fill_external_event_data(con, external_event_data).await
 }
 
+pub async fn external_event_upsert(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::ExternalEventUpsertProps,
+) -> Result<Vec<response::ExternalEventData>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // validate
+    for event in props.events.iter() {
+        if event.start_time < 0 {
+            return Err(response::TodoAppError::NegativeStartTime);
+        }
+        if event.start_time >= event.end_time {
+            return Err(response::TodoAppError::NegativeDuration);
+        }
+    }
+    let window = match (props.min_time, props.max_time) {
+        (Some(min_time), Some(max_time)) => {
+            if min_time >= max_time {
+                return Err(response::TodoAppError::NegativeDuration);
+            }
+            Some((min_time, max_time))
+        }
+        _ => None,
+    };
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let external_event_data = sync_external_events(
+        &mut sp,
+        user.user_id,
+        &props.source,
+        props
+            .events
+            .into_iter()
+            .map(|e| (e.external_uid, e.name, e.start_time, e.end_time))
+            .collect(),
+        window,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // return json
+    fill_external_event_data_batch(con, external_event_data).await
+}
+
+pub async fn external_event_import_ics(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::ExternalEventImportIcsProps,
+) -> Result<Vec<response::ExternalEventData>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // validate window
+    if props.min_time < 0 {
+        return Err(response::TodoAppError::NegativeStartTime);
+    }
+    if props.min_time >= props.max_time {
+        return Err(response::TodoAppError::NegativeDuration);
+    }
+
+    // parse file and expand recurring events inside the window
+    let events = ical::parse_events(&props.ics, props.min_time, props.max_time).map_err(|e| {
+        utils::log(utils::Event {
+            msg: e.0,
+            source: Some("ics import".to_owned()),
+            severity: utils::SeverityKind::Info,
+        });
+        response::TodoAppError::DecodeError
+    })?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let external_event_data = match props.source {
+        // sync against the events previously imported from this source
+        Some(source) => sync_external_events(
+            &mut sp,
+            user.user_id,
+            &source,
+            events
+                .into_iter()
+                .map(|e| (e.uid, e.name, e.start_time, e.end_time))
+                .collect(),
+            Some((props.min_time, props.max_time)),
+        )
+        .await
+        .map_err(report_postgres_err)?,
+        None => {
+            let mut external_event_data = vec![];
+            for event in events {
+                // create event
+                let external_event =
+                    external_event_service::add(&mut sp, user.user_id, None, None, None)
+                        .await
+                        .map_err(report_postgres_err)?;
+
+                // create data
+                let eed = external_event_data_service::add(
+                    &mut sp,
+                    user.user_id,
+                    external_event.external_event_id,
+                    event.name,
+                    event.start_time,
+                    event.end_time,
+                    true,
+                )
+                .await
+                .map_err(report_postgres_err)?;
+                queue_external_event_changed(&mut sp, &eed)
+                    .await
+                    .map_err(report_postgres_err)?;
+                external_event_data.push(eed);
+            }
+            external_event_data
+        }
+    };
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // return json
+    fill_external_event_data_batch(con, external_event_data).await
+}
+
+pub async fn goal_import_todo_txt(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::GoalImportTodoTxtProps,
+) -> Result<Vec<response::GoalData>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    let tz = parse_tz(props.tz)?;
+    let tasks = todo_txt::parse(&props.todo_txt);
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let mut goal_datas = vec![];
+    for task in tasks {
+        let now = utils::current_time_millis();
+
+        // same shape as a templated goal: worthless before the threshold and after the due date
+        let (start_times, utils) = windowed_utility(
+            priority_utility(task.priority),
+            task.threshold.map(|x| local_day_start(tz, x)),
+            task.due
+                .map(|x| local_day_start(tz, x + Duration::days(1)) - 1),
+        );
+
+        let time_utility_function =
+            time_utility_function_service::add(&mut sp, user.user_id, start_times, utils)
+                .await
+                .map_err(report_postgres_err)?;
+
+        let goal = goal_service::add_at(
+            &mut sp,
+            user.user_id,
+            task.creation_date
+                .map(|x| local_day_start(tz, x))
+                .unwrap_or(now),
+        )
+        .await
+        .map_err(report_postgres_err)?;
+
+        let status = match task.status {
+            todo_txt::Status::Pending => request::GoalDataStatusKind::Pending,
+            todo_txt::Status::Done => request::GoalDataStatusKind::Succeed,
+            todo_txt::Status::Fail => request::GoalDataStatusKind::Fail,
+            todo_txt::Status::Cancel => request::GoalDataStatusKind::Cancel,
+        };
+
+        // the data row is dated on the completion date, that's where it's read back from
+        let goal_data = goal_data_service::add_at(
+            &mut sp,
+            user.user_id,
+            task.completion_date
+                .map(|x| local_day_start(tz, x))
+                .unwrap_or(now),
+            goal.goal_id,
+            task.description,
+            Some(1000 * 60 * 60),
+            time_utility_function.time_utility_function_id,
+            status,
+        )
+        .await
+        .map_err(report_postgres_err)?;
+        queue_goal_created(&mut sp, &goal_data)
+            .await
+            .map_err(report_postgres_err)?;
+
+        // projects and contexts become named entities, reusing any with the same name
+        let mut tagged = HashSet::new();
+        let tags = task
+            .projects
+            .into_iter()
+            .map(|x| (x, request::NamedEntityKind::Project))
+            .chain(
+                task.contexts
+                    .into_iter()
+                    .map(|x| (x, request::NamedEntityKind::Context)),
+            );
+        for (name, kind) in tags {
+            if tagged.insert(name.clone()) {
+                tag_goal_by_name(&mut sp, user.user_id, goal.goal_id, name, kind).await?;
+            }
+        }
+
+        goal_datas.push(goal_data);
+    }
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // return json
+    fill_goal_data_batch(con, goal_datas).await
+}
+
+pub async fn goal_export_todo_txt(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::GoalExportTodoTxtProps,
+) -> Result<String, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    let tz = parse_tz(props.tz)?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let goal_datas = goal_data_service::get_recent(con, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let mut tasks = vec![];
+    for goal_data in goal_datas {
+        let goal = goal_service::get_by_goal_id(con, goal_data.goal_id)
+            .await
+            .map_err(report_postgres_err)?
+            .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+        let time_utility_function = time_utility_function_service::get_by_time_utility_function_id(
+            con,
+            goal_data.time_utility_function_id,
+        )
+        .await
+        .map_err(report_postgres_err)?
+        .ok_or(response::TodoAppError::TimeUtilityFunctionNonexistent)?;
+
+        let (utility, min_time, max_time) = utility_window(&time_utility_function);
+
+        let status = match goal_data.status {
+            request::GoalDataStatusKind::Pending => todo_txt::Status::Pending,
+            request::GoalDataStatusKind::Succeed => todo_txt::Status::Done,
+            request::GoalDataStatusKind::Fail => todo_txt::Status::Fail,
+            request::GoalDataStatusKind::Cancel => todo_txt::Status::Cancel,
+        };
+
+        let mut projects = vec![];
+        let mut contexts = vec![];
+        let named_entity_datas =
+            goal_entity_tag_service::get_recent_active_by_goal_id(con, goal.goal_id)
+                .await
+                .map_err(report_postgres_err)?;
+        for named_entity_data in named_entity_datas {
+            match named_entity_data.kind {
+                request::NamedEntityKind::Project => projects.push(named_entity_data.name),
+                _ => contexts.push(named_entity_data.name),
+            }
+        }
+
+        tasks.push(todo_txt::Task {
+            completion_date: match status {
+                todo_txt::Status::Pending => None,
+                _ => local_date(tz, goal_data.creation_time),
+            },
+            status,
+            priority: utility_priority(utility),
+            creation_date: local_date(tz, goal.creation_time),
+            description: goal_data.name,
+            projects,
+            contexts,
+            due: max_time.and_then(|x| local_date(tz, x)),
+            threshold: min_time.and_then(|x| local_date(tz, x)),
+        });
+    }
+
+    // return json
+    Ok(todo_txt::write(&tasks))
+}
+
+pub async fn goal_import_outline(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::GoalImportOutlineProps,
+) -> Result<Vec<response::GoalData>, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    let tz = parse_tz(props.tz)?;
+    let items = outline::parse(
+        &props.outline,
+        match props.format {
+            request::OutlineFormat::Org => outline::Format::Org,
+            request::OutlineFormat::Markdown => outline::Format::Markdown,
+        },
+    );
+
+    // validate scheduled times before touching the database
+    let mut time_spans = vec![];
+    for item in items.iter() {
+        let time_span = match item.scheduled {
+            Some(outline::Timestamp {
+                date,
+                start: Some(start),
+                end,
+            }) => {
+                let start_time = local_time(tz, date.and_time(start));
+                let end_time = match end {
+                    Some(end) => local_time(tz, date.and_time(end)),
+                    None => start_time + 1000 * 60 * 60,
+                };
+                if start_time < 0 {
+                    return Err(response::TodoAppError::NegativeStartTime);
+                }
+                if end_time <= start_time {
+                    return Err(response::TodoAppError::NegativeDuration);
+                }
+                Some((start_time, end_time))
+            }
+            _ => None,
+        };
+        time_spans.push(time_span);
+    }
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let mut goal_datas: Vec<GoalData> = vec![];
+    for (item, time_span) in items.into_iter().zip(time_spans) {
+        // a scheduled date without a time is when work can start, a deadline without one lasts the whole day
+        let min_time = item
+            .scheduled
+            .filter(|x| x.start.is_none())
+            .map(|x| local_day_start(tz, x.date));
+        let max_time = item.deadline.map(|x| match x.start {
+            Some(start) => local_time(tz, x.date.and_time(start)),
+            None => local_day_start(tz, x.date + Duration::days(1)) - 1,
+        });
+        let (start_times, utils) =
+            windowed_utility(priority_utility(item.priority), min_time, max_time);
+
+        let time_utility_function =
+            time_utility_function_service::add(&mut sp, user.user_id, start_times, utils)
+                .await
+                .map_err(report_postgres_err)?;
+
+        let goal = goal_service::add(&mut sp, user.user_id)
+            .await
+            .map_err(report_postgres_err)?;
+
+        let goal_data = goal_data_service::add(
+            &mut sp,
+            user.user_id,
+            goal.goal_id,
+            item.name,
+            Some(match time_span {
+                Some((start_time, end_time)) => end_time - start_time,
+                None => 1000 * 60 * 60,
+            }),
+            time_utility_function.time_utility_function_id,
+            match item.status {
+                outline::Status::Pending => request::GoalDataStatusKind::Pending,
+                outline::Status::Done => request::GoalDataStatusKind::Succeed,
+                outline::Status::Cancel => request::GoalDataStatusKind::Cancel,
+            },
+        )
+        .await
+        .map_err(report_postgres_err)?;
+        queue_goal_created(&mut sp, &goal_data)
+            .await
+            .map_err(report_postgres_err)?;
+
+        if let Some((start_time, end_time)) = time_span {
+            let goal_event = goal_event_service::add(
+                &mut sp,
+                user.user_id,
+                goal.goal_id,
+                start_time,
+                end_time,
+                true,
+            )
+            .await
+            .map_err(report_postgres_err)?;
+            queue_goal_event(&mut sp, &goal_event)
+                .await
+                .map_err(report_postgres_err)?;
+        }
+
+        let mut tagged = HashSet::new();
+        for tag in item.tags {
+            if tagged.insert(tag.clone()) {
+                tag_goal_by_name(
+                    &mut sp,
+                    user.user_id,
+                    goal.goal_id,
+                    tag,
+                    request::NamedEntityKind::Context,
+                )
+                .await?;
+            }
+        }
+
+        // the enclosing item waits for this one to be resolved
+        if let Some(parent) = item.parent {
+            goal_dependency_service::add(
+                &mut sp,
+                user.user_id,
+                goal_datas[parent].goal_id,
+                goal.goal_id,
+                true,
+            )
+            .await
+            .map_err(report_postgres_err)?;
+        }
+
+        goal_datas.push(goal_data);
+    }
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // return json
+    fill_goal_data_batch(con, goal_datas).await
+}
+
 pub async fn goal_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalNewProps,
 ) -> Result<response::GoalData, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
     // validate start and end time
     if let Some((start_time, end_time)) = props.time_span {
@@ -496,7 +3262,7 @@ pub async fn goal_new(
         }
     }
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -530,6 +3296,9 @@ pub async fn goal_new(
     )
     .await
     .map_err(report_postgres_err)?;
+    queue_goal_created(&mut sp, &goal_data)
+        .await
+        .map_err(report_postgres_err)?;
 
     // create goal event if provided
     if let Some((start_time, end_time)) = props.time_span {
@@ -543,6 +3312,9 @@ pub async fn goal_new(
         )
         .await
         .map_err(report_postgres_err)?;
+        queue_goal_event(&mut sp, &goal_event)
+            .await
+            .map_err(report_postgres_err)?;
     }
 
     sp.commit().await.map_err(report_postgres_err)?;
@@ -554,11 +3326,11 @@ pub async fn goal_new(
 pub async fn goal_data_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalDataNewProps,
 ) -> Result<response::GoalData, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
     // validate duration if exists
     if let Some(duration_estimate) = props.duration_estimate {
@@ -567,7 +3339,7 @@ pub async fn goal_data_new(
         }
     }
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -594,6 +3366,25 @@ pub async fn goal_data_new(
         return Err(response::TodoAppError::GoalNonexistent);
     }
 
+    // nothing else of the user's can be written until we commit
+    account_service::lock(&mut sp, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    let previous_goal_data = goal_data_service::get_recent_by_goal_id(&mut sp, goal.goal_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    // reject the change if it wasn't based on the current data
+    if let (Some(expected_previous_id), Some(previous_goal_data)) =
+        (props.expected_previous_id, &previous_goal_data)
+    {
+        if previous_goal_data.goal_data_id != expected_previous_id {
+            let current = fill_goal_data(&mut sp, previous_goal_data.clone()).await?;
+            return Err(response::TodoAppError::GoalDataConflict(current));
+        }
+    }
+
     // create goal data
     let goal_data = goal_data_service::add(
         &mut sp,
@@ -607,6 +3398,14 @@ pub async fn goal_data_new(
     .await
     .map_err(report_postgres_err)?;
 
+    if let Some(previous_goal_data) = previous_goal_data {
+        if previous_goal_data.status.clone() as i64 != goal_data.status.clone() as i64 {
+            queue_goal_status_changed(&mut sp, previous_goal_data.status, &goal_data)
+                .await
+                .map_err(report_postgres_err)?;
+        }
+    }
+
     sp.commit().await.map_err(report_postgres_err)?;
 
     // return json
@@ -616,11 +3415,11 @@ pub async fn goal_data_new(
 pub async fn goal_event_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalEventNewProps,
 ) -> Result<response::GoalEvent, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
     // validate time
     if props.start_time < 0 {
@@ -630,7 +3429,7 @@ pub async fn goal_event_new(
         return Err(response::TodoAppError::NegativeDuration);
     }
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -655,6 +3454,9 @@ pub async fn goal_event_new(
     )
     .await
     .map_err(report_postgres_err)?;
+    queue_goal_event(&mut sp, &goal_event)
+        .await
+        .map_err(report_postgres_err)?;
 
     sp.commit().await.map_err(report_postgres_err)?;
 
@@ -665,13 +3467,13 @@ pub async fn goal_event_new(
 pub async fn goal_dependency_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalDependencyNewProps,
 ) -> Result<response::GoalDependency, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -714,11 +3516,11 @@ pub async fn goal_dependency_new(
 pub async fn goal_template_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalTemplateNewProps,
 ) -> Result<response::GoalTemplateData, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
     // validate duration if exists
     if let Some(duration_estimate) = props.duration_estimate {
@@ -727,7 +3529,7 @@ pub async fn goal_template_new(
         }
     }
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -759,6 +3561,7 @@ pub async fn goal_template_new(
         props.utility,
         props.duration_estimate,
         props.user_generated_code_id,
+        props.published,
         true,
     )
     .await
@@ -773,11 +3576,11 @@ pub async fn goal_template_new(
 pub async fn goal_template_data_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalTemplateDataNewProps,
 ) -> Result<response::GoalTemplateData, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
     // validate duration if exists
     if let Some(duration_estimate) = props.duration_estimate {
@@ -786,7 +3589,7 @@ pub async fn goal_template_data_new(
         }
     }
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -796,39 +3599,151 @@ pub async fn goal_template_data_new(
         props.user_generated_code_id,
     )
     .await
-    .map_err(report_postgres_err)?
-    .ok_or(response::TodoAppError::UserGeneratedCodeNonexistent)?;
-    // validate code is owned by correct user
-    if user_generated_code.creator_user_id != user.user_id {
-        return Err(response::TodoAppError::UserGeneratedCodeNonexistent);
-    }
+    .map_err(report_postgres_err)?
+    .ok_or(response::TodoAppError::UserGeneratedCodeNonexistent)?;
+    // validate code is owned by correct user
+    if user_generated_code.creator_user_id != user.user_id {
+        return Err(response::TodoAppError::UserGeneratedCodeNonexistent);
+    }
+
+    // validate that parent template exists and belongs to you
+    let goal_template =
+        goal_template_service::get_by_goal_template_id(&mut sp, props.goal_template_id)
+            .await
+            .map_err(report_postgres_err)?
+            .ok_or(response::TodoAppError::GoalTemplateNonexistent)?;
+    // validate template is owned by correct user
+    if goal_template.creator_user_id != user.user_id {
+        return Err(response::TodoAppError::GoalTemplateNonexistent);
+    }
+
+    // nothing else of the user's can be written until we commit
+    account_service::lock(&mut sp, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    // reject the change if it wasn't based on the current data
+    if let Some(expected_previous_id) = props.expected_previous_id {
+        let previous_goal_template_data =
+            goal_template_data_service::get_recent_by_goal_template_id(
+                &mut sp,
+                goal_template.goal_template_id,
+            )
+            .await
+            .map_err(report_postgres_err)?;
+        if let Some(previous_goal_template_data) = previous_goal_template_data {
+            if previous_goal_template_data.goal_template_data_id != expected_previous_id {
+                let current = fill_goal_template_data(&mut sp, previous_goal_template_data).await?;
+                return Err(response::TodoAppError::GoalTemplateDataConflict(current));
+            }
+        }
+    }
+
+    // create goal_template data
+    let goal_template_data = goal_template_data_service::add(
+        &mut sp,
+        user.user_id,
+        props.goal_template_id,
+        props.name,
+        // TODO prevent negative utility somehow
+        props.utility,
+        props.duration_estimate,
+        props.user_generated_code_id,
+        props.published,
+        props.active,
+    )
+    .await
+    .map_err(report_postgres_err)?;
+
+    sp.commit().await.map_err(report_postgres_err)?;
+
+    // return json
+    fill_goal_template_data(con, goal_template_data).await
+}
+
+pub async fn goal_template_fork(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::GoalTemplateForkProps,
+) -> Result<response::GoalTemplateData, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    // get the current version of the template we're copying
+    let goal_template_data =
+        goal_template_data_service::get_recent_by_goal_template_id(&mut sp, props.goal_template_id)
+            .await
+            .map_err(report_postgres_err)?
+            .ok_or(response::TodoAppError::GoalTemplateNonexistent)?;
+    // you may only fork your own templates or ones that have been published
+    if goal_template_data.creator_user_id != user.user_id
+        && !(goal_template_data.published && goal_template_data.active)
+    {
+        return Err(response::TodoAppError::GoalTemplateNonexistent);
+    }
+
+    let user_generated_code = user_generated_code_service::get_by_user_generated_code_id(
+        &mut sp,
+        goal_template_data.user_generated_code_id,
+    )
+    .await
+    .map_err(report_postgres_err)?
+    .ok_or(response::TodoAppError::UserGeneratedCodeNonexistent)?;
+
+    let goal_template_patterns =
+        goal_template_pattern_service::get_active_by_goal_template_id(&mut sp, props.goal_template_id)
+            .await
+            .map_err(report_postgres_err)?;
+
+    // copy the code so that the fork doesn't change when the original does
+    let user_generated_code = user_generated_code_service::add(
+        &mut sp,
+        user.user_id,
+        user_generated_code.source_code,
+        user_generated_code.source_lang,
+        user_generated_code.wasm_cache,
+    )
+    .await
+    .map_err(report_postgres_err)?;
 
-    // validate that parent template exists and belongs to you
-    let goal_template =
-        goal_template_service::get_by_goal_template_id(&mut sp, props.goal_template_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::TodoAppError::GoalTemplateNonexistent)?;
-    // validate template is owned by correct user
-    if goal_template.creator_user_id != user.user_id {
-        return Err(response::TodoAppError::GoalTemplateNonexistent);
-    }
+    // create goal_template
+    let goal_template = goal_template_service::add(&mut sp, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
 
-    // create goal_template data
+    // create goal_template data, forks start out private
     let goal_template_data = goal_template_data_service::add(
         &mut sp,
         user.user_id,
-        props.goal_template_id,
-        props.name,
-        // TODO prevent negative utility somehow
-        props.utility,
-        props.duration_estimate,
-        props.user_generated_code_id,
-        props.active,
+        goal_template.goal_template_id,
+        goal_template_data.name,
+        goal_template_data.utility,
+        goal_template_data.duration_estimate,
+        user_generated_code.user_generated_code_id,
+        false,
+        true,
     )
     .await
     .map_err(report_postgres_err)?;
 
+    // copy patterns
+    for goal_template_pattern in goal_template_patterns {
+        goal_template_pattern_service::add(
+            &mut sp,
+            user.user_id,
+            goal_template.goal_template_id,
+            goal_template_pattern.pattern,
+            true,
+        )
+        .await
+        .map_err(report_postgres_err)?;
+    }
+
     sp.commit().await.map_err(report_postgres_err)?;
 
     // return json
@@ -838,13 +3753,13 @@ pub async fn goal_template_data_new(
 pub async fn goal_template_pattern_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalTemplatePatternNewProps,
 ) -> Result<response::GoalTemplatePattern, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -876,16 +3791,68 @@ pub async fn goal_template_pattern_new(
     fill_goal_template_pattern(con, goal_template_pattern).await
 }
 
+pub async fn goal_template_preview(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::GoalTemplatePreviewProps,
+) -> Result<response::GoalTemplatePreview, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
+
+    // validate time bounds
+    if let Some(min_time) = props.min_time {
+        if min_time < 0 {
+            return Err(response::TodoAppError::NegativeStartTime);
+        }
+    }
+    if let (Some(min_time), Some(max_time)) = (props.min_time, props.max_time) {
+        if min_time >= max_time {
+            return Err(response::TodoAppError::NegativeDuration);
+        }
+    }
+
+    // validate start and end time
+    if let Some((start_time, end_time)) = props.time_span {
+        if start_time < 0 {
+            return Err(response::TodoAppError::NegativeStartTime);
+        }
+        if start_time >= end_time {
+            return Err(response::TodoAppError::NegativeDuration);
+        }
+    }
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+
+    let preview = apply_goal_template(
+        &mut sp,
+        user.user_id,
+        props.name,
+        props.min_time,
+        props.max_time,
+        props.time_span,
+    )
+    .await?;
+
+    // this is only a preview, so throw away everything we generated
+    sp.rollback().await.map_err(report_postgres_err)?;
+
+    // return json
+    Ok(preview)
+}
+
 pub async fn goal_entity_tag_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalEntityTagNewProps,
 ) -> Result<response::GoalEntityTag, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -929,13 +3896,13 @@ pub async fn goal_entity_tag_new(
 pub async fn named_entity_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::NamedEntityNewProps,
 ) -> Result<response::NamedEntityData, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -965,13 +3932,13 @@ pub async fn named_entity_new(
 pub async fn named_entity_data_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::NamedEntityDataNewProps,
 ) -> Result<response::NamedEntityData, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -985,6 +3952,27 @@ pub async fn named_entity_data_new(
         return Err(response::TodoAppError::NamedEntityNonexistent);
     }
 
+    // nothing else of the user's can be written until we commit
+    account_service::lock(&mut sp, user.user_id)
+        .await
+        .map_err(report_postgres_err)?;
+
+    // reject the change if it wasn't based on the current data
+    if let Some(expected_previous_id) = props.expected_previous_id {
+        let previous_named_entity_data = named_entity_data_service::get_recent_by_named_entity_id(
+            &mut sp,
+            named_entity.named_entity_id,
+        )
+        .await
+        .map_err(report_postgres_err)?;
+        if let Some(previous_named_entity_data) = previous_named_entity_data {
+            if previous_named_entity_data.named_entity_data_id != expected_previous_id {
+                let current = fill_named_entity_data(&mut sp, previous_named_entity_data).await?;
+                return Err(response::TodoAppError::NamedEntityDataConflict(current));
+            }
+        }
+    }
+
     // create named_entity data
     let named_entity_data = named_entity_data_service::add(
         &mut sp,
@@ -1006,13 +3994,13 @@ pub async fn named_entity_data_new(
 pub async fn named_entity_pattern_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::NamedEntityPatternNewProps,
 ) -> Result<response::NamedEntityPattern, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     let mut sp = con.transaction().await.map_err(report_postgres_err)?;
 
@@ -1046,18 +4034,18 @@ pub async fn named_entity_pattern_new(
 pub async fn time_utility_function_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::TimeUtilityFunctionNewProps,
 ) -> Result<response::TimeUtilityFunction, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
     // check that utils length == start_times length
     if props.start_times.len() != props.utils.len() {
         return Err(response::TodoAppError::TimeUtilityFunctionNotValid);
     }
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     // create tuf
     let time_utility_function =
@@ -1071,13 +4059,13 @@ pub async fn time_utility_function_new(
 pub async fn user_generated_code_new(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::UserGeneratedCodeNewProps,
 ) -> Result<response::UserGeneratedCode, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key).await?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
 
     // create ugc
     let user_generated_code = user_generated_code_service::add(
@@ -1097,402 +4085,772 @@ pub async fn user_generated_code_new(
 pub async fn external_event_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::ExternalEventViewProps,
-) -> Result<Vec<response::ExternalEvent>, response::TodoAppError> {
+) -> Result<page::Page<response::ExternalEvent>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        external_event_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, external_event_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let external_events = external_event_service::query(con, props)
+    let external_events = external_event_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return external_events
     let mut resp_external_events = vec![];
-    for u in external_events
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
+    for u in external_events.items {
         resp_external_events.push(fill_external_event(con, u).await?);
     }
 
-    Ok(resp_external_events)
+    Ok(page::Page {
+        items: resp_external_events,
+        next_cursor: external_events.next_cursor,
+    })
 }
 
 pub async fn external_event_data_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::ExternalEventDataViewProps,
-) -> Result<Vec<response::ExternalEventData>, response::TodoAppError> {
+) -> Result<page::Page<response::ExternalEventData>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        external_event_data_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, external_event_data_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let external_event_data = external_event_data_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    let external_event_data =
+        external_event_data_service::query(con, user.user_id, &page_query, props)
+            .await
+            .map_err(report_postgres_err)?;
     // return users
     // return external_event_datas
-    let mut resp_external_event_datas = vec![];
-    for u in external_event_data
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_external_event_datas.push(fill_external_event_data(con, u).await?);
-    }
-
-    Ok(resp_external_event_datas)
+    Ok(page::Page {
+        items: fill_external_event_data_batch(con, external_event_data.items).await?,
+        next_cursor: external_event_data.next_cursor,
+    })
 }
 
 pub async fn goal_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalViewProps,
-) -> Result<Vec<response::Goal>, response::TodoAppError> {
+) -> Result<page::Page<response::Goal>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goals = goal_service::query(con, props)
+    let goals = goal_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return goals
     let mut resp_goals = vec![];
-    for u in goals
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
+    for u in goals.items {
         resp_goals.push(fill_goal(con, u).await?);
     }
 
-    Ok(resp_goals)
+    Ok(page::Page {
+        items: resp_goals,
+        next_cursor: goals.next_cursor,
+    })
 }
 
 pub async fn goal_data_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalDataViewProps,
-) -> Result<Vec<response::GoalData>, response::TodoAppError> {
+) -> Result<page::Page<response::GoalData>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
 
-    let con = &mut *db.lock().await;
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_data_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_data_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goal_data = goal_data_service::query(con, props)
+    let goal_data = goal_data_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return goal_datas
-    let mut resp_goal_datas = vec![];
-    for u in goal_data
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_goal_datas.push(fill_goal_data(con, u).await?);
+    Ok(page::Page {
+        items: fill_goal_data_batch(con, goal_data.items).await?,
+        next_cursor: goal_data.next_cursor,
+    })
+}
+
+static GOAL_CSV_COLUMNS: [&str; 13] = [
+    "goal_id",
+    "goal_data_id",
+    "goal_creation_time",
+    "creation_time",
+    "name",
+    "status",
+    "duration_estimate_minutes",
+    "peak_utility",
+    "start_time",
+    "deadline",
+    "event_start_time",
+    "event_end_time",
+    "tags",
+];
+
+static GOAL_EVENT_CSV_COLUMNS: [&str; 8] = [
+    "goal_event_id",
+    "goal_id",
+    "name",
+    "creation_time",
+    "start_time",
+    "end_time",
+    "duration_minutes",
+    "active",
+];
+
+// spreadsheets parse this form as a date without any help
+fn csv_time(tz: Tz, time: i64) -> String {
+    match tz.timestamp_millis_opt(time).single() {
+        Some(x) => x.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => time.to_string(),
     }
+}
 
-    Ok(resp_goal_datas)
+fn csv_status(status: &request::GoalDataStatusKind) -> &'static str {
+    match status {
+        request::GoalDataStatusKind::Pending => "PENDING",
+        request::GoalDataStatusKind::Succeed => "SUCCEED",
+        request::GoalDataStatusKind::Fail => "FAIL",
+        request::GoalDataStatusKind::Cancel => "CANCEL",
+    }
+}
+
+pub async fn goal_data_export_csv(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::GoalDataExportCsvProps,
+) -> Result<String, response::TodoAppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let tz = parse_tz(props.tz)?;
+
+    // pick the columns, in the order they were asked for
+    let all_columns: &[&str] = match props.kind {
+        request::CsvExportKind::Goal => &GOAL_CSV_COLUMNS,
+        request::CsvExportKind::GoalEvent => &GOAL_EVENT_CSV_COLUMNS,
+    };
+    let columns = match props.columns {
+        Some(columns) => columns
+            .iter()
+            .map(|c| all_columns.iter().position(|x| x == c))
+            .collect::<Option<Vec<usize>>>()
+            .ok_or(response::TodoAppError::DecodeError)?,
+        None => (0..all_columns.len()).collect(),
+    };
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+
+    // same filters as public/goal_data/view
+    let goal_datas = goal_data_service::query(
+        con,
+        user.user_id,
+        &page::PageQuery::all(),
+        request::GoalDataViewProps {
+            goal_data_id: props.goal_data_id,
+            min_creation_time: props.min_creation_time,
+            max_creation_time: props.max_creation_time,
+            creator_user_id: props.creator_user_id,
+            goal_id: props.goal_id,
+            name: props.name,
+            min_duration_estimate: props.min_duration_estimate,
+            max_duration_estimate: props.max_duration_estimate,
+            concrete: props.concrete,
+            time_utility_function_id: props.time_utility_function_id,
+            status: props.status,
+            scheduled: props.scheduled,
+            only_recent: props.only_recent,
+            sort: None,
+            cursor: None,
+            limit: None,
+            api_key: props.api_key.clone(),
+        },
+    )
+    .await
+    .map_err(report_postgres_err)?
+    .items;
+
+    let mut writer = csv::Writer::default();
+    writer.row(columns.iter().map(|&i| all_columns[i]));
+
+    let optional_time = |x: Option<i64>| x.map(|x| csv_time(tz, x)).unwrap_or_default();
+
+    match props.kind {
+        request::CsvExportKind::Goal => {
+            for goal_data in goal_datas {
+                let goal = goal_service::get_by_goal_id(con, goal_data.goal_id)
+                    .await
+                    .map_err(report_postgres_err)?
+                    .ok_or(response::TodoAppError::GoalNonexistent)?;
+
+                let time_utility_function =
+                    time_utility_function_service::get_by_time_utility_function_id(
+                        con,
+                        goal_data.time_utility_function_id,
+                    )
+                    .await
+                    .map_err(report_postgres_err)?
+                    .ok_or(response::TodoAppError::TimeUtilityFunctionNonexistent)?;
+                let (peak_utility, start_time, deadline) = utility_window(&time_utility_function);
+
+                let goal_event = goal_event_service::get_recent_by_goal_id(con, goal.goal_id)
+                    .await
+                    .map_err(report_postgres_err)?
+                    .filter(|x| x.active);
+
+                let tags = goal_entity_tag_service::get_recent_active_by_goal_id(con, goal.goal_id)
+                    .await
+                    .map_err(report_postgres_err)?;
+
+                let fields = [
+                    goal.goal_id.to_string(),
+                    goal_data.goal_data_id.to_string(),
+                    csv_time(tz, goal.creation_time),
+                    csv_time(tz, goal_data.creation_time),
+                    csv::text(&goal_data.name),
+                    csv_status(&goal_data.status).to_owned(),
+                    goal_data
+                        .duration_estimate
+                        .map(|x| (x / (1000 * 60)).to_string())
+                        .unwrap_or_default(),
+                    peak_utility.to_string(),
+                    optional_time(start_time),
+                    optional_time(deadline),
+                    optional_time(goal_event.as_ref().map(|x| x.start_time)),
+                    optional_time(goal_event.as_ref().map(|x| x.end_time)),
+                    csv::text(
+                        &tags
+                            .into_iter()
+                            .map(|x| x.name)
+                            .collect::<Vec<_>>()
+                            .join("; "),
+                    ),
+                ];
+                writer.row(columns.iter().map(|&i| &fields[i]));
+            }
+        }
+        request::CsvExportKind::GoalEvent => {
+            // every version of every event of the matching goals, so past work shows up too
+            let mut names = BTreeMap::new();
+            for goal_data in goal_datas {
+                if names.contains_key(&goal_data.goal_id) {
+                    continue;
+                }
+                let recent_goal_data =
+                    goal_data_service::get_recent_by_goal_id(con, goal_data.goal_id)
+                        .await
+                        .map_err(report_postgres_err)?
+                        .ok_or(response::TodoAppError::GoalNonexistent)?;
+                names.insert(goal_data.goal_id, recent_goal_data.name);
+            }
+
+            let goal_events = goal_event_service::query(
+                con,
+                user.user_id,
+                &page::PageQuery::all(),
+                request::GoalEventViewProps {
+                    goal_event_id: None,
+                    min_creation_time: None,
+                    max_creation_time: None,
+                    creator_user_id: Some(vec![user.user_id]),
+                    goal_id: Some(names.keys().copied().collect()),
+                    min_start_time: None,
+                    max_start_time: None,
+                    min_end_time: None,
+                    max_end_time: None,
+                    active: None,
+                    only_recent: false,
+                    sort: None,
+                    cursor: None,
+                    limit: None,
+                    api_key: props.api_key,
+                },
+            )
+            .await
+            .map_err(report_postgres_err)?
+            .items;
+
+            for goal_event in goal_events {
+                let fields = [
+                    goal_event.goal_event_id.to_string(),
+                    goal_event.goal_id.to_string(),
+                    csv::text(names.get(&goal_event.goal_id).map_or("", |x| x.as_str())),
+                    csv_time(tz, goal_event.creation_time),
+                    csv_time(tz, goal_event.start_time),
+                    csv_time(tz, goal_event.end_time),
+                    ((goal_event.end_time - goal_event.start_time) / (1000 * 60)).to_string(),
+                    goal_event.active.to_string(),
+                ];
+                writer.row(columns.iter().map(|&i| &fields[i]));
+            }
+        }
+    }
+
+    Ok(writer.finish())
 }
 
 pub async fn goal_template_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalTemplateViewProps,
-) -> Result<Vec<response::GoalTemplate>, response::TodoAppError> {
+) -> Result<page::Page<response::GoalTemplate>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_template_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_template_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goal_templates = goal_template_service::query(con, props)
+    let goal_templates = goal_template_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return goal_templates
     let mut resp_goal_templates = vec![];
-    for u in goal_templates
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
+    for u in goal_templates.items {
         resp_goal_templates.push(fill_goal_template(con, u).await?);
     }
 
-    Ok(resp_goal_templates)
+    Ok(page::Page {
+        items: resp_goal_templates,
+        next_cursor: goal_templates.next_cursor,
+    })
 }
 
 pub async fn goal_template_data_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalTemplateDataViewProps,
-) -> Result<Vec<response::GoalTemplateData>, response::TodoAppError> {
+) -> Result<page::Page<response::GoalTemplateData>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_template_data_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_template_data_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goal_template_data = goal_template_data_service::query(con, props)
+    let goal_template_data =
+        goal_template_data_service::query(con, user.user_id, &page_query, props)
+            .await
+            .map_err(report_postgres_err)?;
+
+    // return goal_template_datas
+    Ok(page::Page {
+        items: fill_goal_template_data_batch(con, goal_template_data.items).await?,
+        next_cursor: goal_template_data.next_cursor,
+    })
+}
+
+pub async fn goal_template_published_view(
+    _config: Config,
+    db: Db,
+    auth: Auth,
+    props: request::GoalTemplatePublishedViewProps,
+) -> Result<page::Page<response::PublishedGoalTemplate>, response::TodoAppError> {
+    // validate api key
+    let _user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_template_data_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_template_data_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
+    // get published templates from all users
+    let page::Page {
+        items: goal_template_data,
+        next_cursor,
+    } = goal_template_data_service::search_published(con, &page_query, props.name)
         .await
         .map_err(report_postgres_err)?;
 
-    // return goal_template_datas
-    let mut resp_goal_template_datas = vec![];
-    for u in goal_template_data
+    // return templates along with their patterns
+    let goal_template_ids: Vec<i64> = goal_template_data
+        .iter()
+        .map(|x| x.goal_template_id)
+        .collect();
+    let goal_template_patterns =
+        goal_template_pattern_service::get_active_by_goal_template_ids(con, &goal_template_ids)
+            .await
+            .map_err(report_postgres_err)?;
+
+    let mut resp_goal_template_patterns: HashMap<i64, Vec<response::GoalTemplatePattern>> =
+        HashMap::new();
+    for (goal_template_id, resp_goal_template_pattern) in goal_template_patterns
+        .iter()
+        .map(|x| x.goal_template_id)
+        .collect::<Vec<i64>>()
         .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
+        .zip(fill_goal_template_pattern_batch(con, goal_template_patterns).await?)
     {
-        resp_goal_template_datas.push(fill_goal_template_data(con, u).await?);
+        resp_goal_template_patterns
+            .entry(goal_template_id)
+            .or_default()
+            .push(resp_goal_template_pattern);
     }
 
-    Ok(resp_goal_template_datas)
+    let resp_published_goal_templates = goal_template_ids
+        .iter()
+        .zip(fill_goal_template_data_batch(con, goal_template_data).await?)
+        .map(
+            |(goal_template_id, goal_template_data)| response::PublishedGoalTemplate {
+                goal_template_data,
+                goal_template_pattern: resp_goal_template_patterns
+                    .remove(goal_template_id)
+                    .unwrap_or_default(),
+            },
+        )
+        .collect();
+
+    Ok(page::Page {
+        items: resp_published_goal_templates,
+        next_cursor,
+    })
 }
 
 pub async fn goal_template_pattern_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalTemplatePatternViewProps,
-) -> Result<Vec<response::GoalTemplatePattern>, response::TodoAppError> {
+) -> Result<page::Page<response::GoalTemplatePattern>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_template_pattern_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_template_pattern_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goal_template_pattern = goal_template_pattern_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    let goal_template_pattern =
+        goal_template_pattern_service::query(con, user.user_id, &page_query, props)
+            .await
+            .map_err(report_postgres_err)?;
 
     // return goal_template_patterns
-    let mut resp_goal_template_patterns = vec![];
-    for u in goal_template_pattern
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_goal_template_patterns.push(fill_goal_template_pattern(con, u).await?);
-    }
-
-    Ok(resp_goal_template_patterns)
+    Ok(page::Page {
+        items: fill_goal_template_pattern_batch(con, goal_template_pattern.items).await?,
+        next_cursor: goal_template_pattern.next_cursor,
+    })
 }
 
 pub async fn goal_event_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalEventViewProps,
-) -> Result<Vec<response::GoalEvent>, response::TodoAppError> {
+) -> Result<page::Page<response::GoalEvent>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_event_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_event_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goal_event = goal_event_service::query(con, props)
+    let goal_event = goal_event_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return goal_events
-    let mut resp_goal_events = vec![];
-    for u in goal_event
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_goal_events.push(fill_goal_event(con, u).await?);
-    }
-
-    Ok(resp_goal_events)
+    Ok(page::Page {
+        items: fill_goal_event_batch(con, goal_event.items).await?,
+        next_cursor: goal_event.next_cursor,
+    })
 }
 
 pub async fn goal_dependency_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalDependencyViewProps,
-) -> Result<Vec<response::GoalDependency>, response::TodoAppError> {
+) -> Result<page::Page<response::GoalDependency>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_dependency_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_dependency_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goal_dependency = goal_dependency_service::query(con, props)
+    let goal_dependency = goal_dependency_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return goal_dependencys
-    let mut resp_goal_dependencys = vec![];
-    for u in goal_dependency
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_goal_dependencys.push(fill_goal_dependency(con, u).await?);
-    }
-
-    Ok(resp_goal_dependencys)
+    Ok(page::Page {
+        items: fill_goal_dependency_batch(con, goal_dependency.items).await?,
+        next_cursor: goal_dependency.next_cursor,
+    })
 }
 
 pub async fn goal_entity_tag_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::GoalEntityTagViewProps,
-) -> Result<Vec<response::GoalEntityTag>, response::TodoAppError> {
+) -> Result<page::Page<response::GoalEntityTag>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        goal_entity_tag_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, goal_entity_tag_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let goal_entity_tag = goal_entity_tag_service::query(con, props)
+    let goal_entity_tag = goal_entity_tag_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return goal_entity_tags
-    let mut resp_goal_entity_tags = vec![];
-    for u in goal_entity_tag
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_goal_entity_tags.push(fill_goal_entity_tag(con, u).await?);
-    }
-
-    Ok(resp_goal_entity_tags)
+    Ok(page::Page {
+        items: fill_goal_entity_tag_batch(con, goal_entity_tag.items).await?,
+        next_cursor: goal_entity_tag.next_cursor,
+    })
 }
 
 pub async fn time_utility_function_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::TimeUtilityFunctionViewProps,
-) -> Result<Vec<response::TimeUtilityFunction>, response::TodoAppError> {
+) -> Result<page::Page<response::TimeUtilityFunction>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
 
-    let con = &mut *db.lock().await;
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        time_utility_function_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, time_utility_function_service::sort_key))?;
+
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let time_utility_function = time_utility_function_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    let time_utility_function =
+        time_utility_function_service::query(con, user.user_id, &page_query, props)
+            .await
+            .map_err(report_postgres_err)?;
     // return time_utility_functions
     let mut resp_time_utility_functions = vec![];
-    for u in time_utility_function
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
+    for u in time_utility_function.items {
         resp_time_utility_functions.push(fill_time_utility_function(con, u).await?);
     }
 
-    Ok(resp_time_utility_functions)
+    Ok(page::Page {
+        items: resp_time_utility_functions,
+        next_cursor: time_utility_function.next_cursor,
+    })
 }
 
 pub async fn user_generated_code_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::UserGeneratedCodeViewProps,
-) -> Result<Vec<response::UserGeneratedCode>, response::TodoAppError> {
+) -> Result<page::Page<response::UserGeneratedCode>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        user_generated_code_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, user_generated_code_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let user_generated_code = user_generated_code_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    let user_generated_code =
+        user_generated_code_service::query(con, user.user_id, &page_query, props)
+            .await
+            .map_err(report_postgres_err)?;
     // return user_generated_codes
     let mut resp_user_generated_codes = vec![];
-    for u in user_generated_code
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
+    for u in user_generated_code.items {
         resp_user_generated_codes.push(fill_user_generated_code(con, u).await?);
     }
 
-    Ok(resp_user_generated_codes)
+    Ok(page::Page {
+        items: resp_user_generated_codes,
+        next_cursor: user_generated_code.next_cursor,
+    })
 }
 
 pub async fn named_entity_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::NamedEntityViewProps,
-) -> Result<Vec<response::NamedEntity>, response::TodoAppError> {
+) -> Result<page::Page<response::NamedEntity>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        named_entity_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, named_entity_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let named_entitys = named_entity_service::query(con, props)
+    let named_entitys = named_entity_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return named_entitys
     let mut resp_named_entitys = vec![];
-    for u in named_entitys
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
+    for u in named_entitys.items {
         resp_named_entitys.push(fill_named_entity(con, u).await?);
     }
 
-    Ok(resp_named_entitys)
+    Ok(page::Page {
+        items: resp_named_entitys,
+        next_cursor: named_entitys.next_cursor,
+    })
 }
 
 pub async fn named_entity_data_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::NamedEntityDataViewProps,
-) -> Result<Vec<response::NamedEntityData>, response::TodoAppError> {
+) -> Result<page::Page<response::NamedEntityData>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        named_entity_data_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, named_entity_data_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let named_entity_data = named_entity_data_service::query(con, props)
+    let named_entity_data = named_entity_data_service::query(con, user.user_id, &page_query, props)
         .await
         .map_err(report_postgres_err)?;
 
     // return named_entity_datas
-    let mut resp_named_entity_datas = vec![];
-    for u in named_entity_data
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_named_entity_datas.push(fill_named_entity_data(con, u).await?);
-    }
-
-    Ok(resp_named_entity_datas)
+    Ok(page::Page {
+        items: fill_named_entity_data_batch(con, named_entity_data.items).await?,
+        next_cursor: named_entity_data.next_cursor,
+    })
 }
 
 pub async fn named_entity_pattern_view(
     _config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth: Auth,
     props: request::NamedEntityPatternViewProps,
-) -> Result<Vec<response::NamedEntityPattern>, response::TodoAppError> {
+) -> Result<page::Page<response::NamedEntityPattern>, response::TodoAppError> {
     // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+    let user = get_user_if_api_key_valid(&auth, props.api_key.clone()).await?;
+
+    let page_query = page::PageQuery::new(
+        props.sort.clone(),
+        props.cursor.clone(),
+        props.limit,
+        named_entity_pattern_service::sort_key,
+    )
+    .ok_or_else(|| report_page_query_err(&props.sort, named_entity_pattern_service::sort_key))?;
 
-    let con = &mut *db.lock().await;
+    let con = &mut **db.get().await.map_err(report_pool_err)?;
     // get users
-    let named_entity_pattern = named_entity_pattern_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    let named_entity_pattern =
+        named_entity_pattern_service::query(con, user.user_id, &page_query, props)
+            .await
+            .map_err(report_postgres_err)?;
 
     // return named_entity_patterns
-    let mut resp_named_entity_patterns = vec![];
-    for u in named_entity_pattern
-        .into_iter()
-        .filter(|u| u.creator_user_id == user.user_id)
-    {
-        resp_named_entity_patterns.push(fill_named_entity_pattern(con, u).await?);
-    }
-
-    Ok(resp_named_entity_patterns)
+    Ok(page::Page {
+        items: fill_named_entity_pattern_batch(con, named_entity_pattern.items).await?,
+        next_cursor: named_entity_pattern.next_cursor,
+    })
 }
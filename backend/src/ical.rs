@@ -0,0 +1,1211 @@
+// Just enough of RFC 5545 (iCalendar) to move events in and out of the app.
+// All times handed to and returned from this module are unix millis.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::{HashMap, HashSet};
+
+// stop expanding a recurrence after this many instances in the window, even if it has no end
+static MAX_RECURRENCE_INSTANCES: usize = 10000;
+
+// longer intervals than this are rejected, so that stepping through periods can't overflow
+static MAX_RECURRENCE_INTERVAL: i64 = 1000;
+
+// a single event occurrence, recurring events produce one per instance
+#[derive(Clone, Debug)]
+pub struct Event {
+  // stable across imports: the UID, plus the original start time for instances of a recurrence
+  pub uid: String,
+  pub name: String,
+  pub start_time: i64,
+  pub end_time: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct IcalError(pub String);
+
+fn err<T>(msg: impl Into<String>) -> Result<T, IcalError> {
+  Err(IcalError(msg.into()))
+}
+
+// one content line, e.g. `DTSTART;TZID=Europe/Paris:20230101T090000`
+#[derive(Clone, Debug)]
+struct Property {
+  name: String,
+  params: Vec<(String, String)>,
+  value: String,
+}
+
+impl Property {
+  fn param(&self, name: &str) -> Option<&str> {
+    self
+      .params
+      .iter()
+      .find(|(k, _)| k == name)
+      .map(|(_, v)| v.as_str())
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Zone {
+  Utc,
+  Tz(Tz),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DateTimeValue {
+  local: NaiveDateTime,
+  zone: Zone,
+  all_day: bool,
+}
+
+// long lines are folded by inserting a CRLF followed by a space or tab
+fn unfold(source: &str) -> Vec<String> {
+  let mut lines: Vec<String> = vec![];
+  for line in source.lines() {
+    let line = line.trim_end_matches('\r');
+    match line.chars().next() {
+      Some(' ') | Some('\t') if !lines.is_empty() => {
+        lines.last_mut().unwrap().push_str(&line[1..]);
+      }
+      _ if line.is_empty() => {}
+      _ => lines.push(line.to_owned()),
+    }
+  }
+  lines
+}
+
+fn parse_property(line: &str) -> Result<Property, IcalError> {
+  // the value starts at the first colon that isn't inside a quoted parameter
+  let mut in_quotes = false;
+  let mut colon = None;
+  for (i, c) in line.char_indices() {
+    match c {
+      '"' => in_quotes = !in_quotes,
+      ':' if !in_quotes => {
+        colon = Some(i);
+        break;
+      }
+      _ => {}
+    }
+  }
+  let colon = match colon {
+    Some(c) => c,
+    None => return err(format!("missing value: {}", line)),
+  };
+
+  let (head, value) = (&line[..colon], &line[colon + 1..]);
+  let mut parts = head.split(';');
+  let name = parts.next().unwrap_or_default().to_ascii_uppercase();
+  let params = parts
+    .filter_map(|p| p.split_once('='))
+    .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_owned()))
+    .collect();
+
+  Ok(Property {
+    name,
+    params,
+    value: value.to_owned(),
+  })
+}
+
+fn unescape_text(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    if c == '\\' {
+      match chars.next() {
+        Some('n') | Some('N') => out.push('\n'),
+        Some(c) => out.push(c),
+        None => {}
+      }
+    } else {
+      out.push(c);
+    }
+  }
+  out
+}
+
+// accepts IANA names, optionally behind a vendor prefix like `/mozilla.org/20050126_1/`,
+// and the windows names that Outlook and Exchange use.
+// VTIMEZONE definitions aren't read, so a zone we can't name is an error rather than a guess
+fn parse_zone(name: &str) -> Result<Zone, IcalError> {
+  let name = name.trim();
+  if let Some((_, iana)) = WINDOWS_ZONES
+    .iter()
+    .find(|(w, _)| w.eq_ignore_ascii_case(name))
+  {
+    return iana
+      .parse::<Tz>()
+      .map(Zone::Tz)
+      .or_else(|_| err(format!("unknown time zone: {}", name)));
+  }
+
+  let mut rest = name;
+  loop {
+    if let Ok(tz) = rest.parse::<Tz>() {
+      return Ok(Zone::Tz(tz));
+    }
+    match rest.split_once('/') {
+      Some((_, r)) => rest = r,
+      None => return err(format!("unknown time zone: {}", name)),
+    }
+  }
+}
+
+fn parse_date_time_str(
+  value: &str,
+  tzid: Option<&str>,
+  default_zone: Zone,
+) -> Result<DateTimeValue, IcalError> {
+  if value.len() == 8 {
+    return match NaiveDate::parse_from_str(value, "%Y%m%d") {
+      Ok(d) => Ok(DateTimeValue {
+        local: d.and_time(NaiveTime::MIN),
+        zone: default_zone,
+        all_day: true,
+      }),
+      Err(_) => err(format!("invalid date: {}", value)),
+    };
+  }
+
+  let (value, utc) = match value.strip_suffix('Z') {
+    Some(v) => (v, true),
+    None => (value, false),
+  };
+
+  let local = match NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+    Ok(v) => v,
+    Err(_) => return err(format!("invalid date-time: {}", value)),
+  };
+
+  let zone = match tzid {
+    _ if utc => Zone::Utc,
+    Some(tzid) => parse_zone(tzid)?,
+    None => default_zone,
+  };
+
+  Ok(DateTimeValue {
+    local,
+    zone,
+    all_day: false,
+  })
+}
+
+fn parse_date_time(prop: &Property, default_zone: Zone) -> Result<DateTimeValue, IcalError> {
+  parse_date_time_str(prop.value.trim(), prop.param("TZID"), default_zone)
+}
+
+fn to_millis(local: NaiveDateTime, zone: Zone) -> i64 {
+  match zone {
+    Zone::Utc => Utc.from_utc_datetime(&local).timestamp_millis(),
+    Zone::Tz(tz) => match tz.from_local_datetime(&local).earliest() {
+      Some(v) => v.timestamp_millis(),
+      // the local time falls in a DST gap, so it's really an hour later
+      None => tz
+        .from_local_datetime(
+          &(local
+            .checked_add_signed(Duration::hours(1))
+            .unwrap_or(local)),
+        )
+        .earliest()
+        .map(|v| v.timestamp_millis())
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local).timestamp_millis()),
+    },
+  }
+}
+
+// parses durations like `P1D`, `PT1H30M` or `-P2W`
+fn parse_duration(value: &str) -> Result<Duration, IcalError> {
+  let (negative, rest) = match value.strip_prefix('-') {
+    Some(v) => (true, v),
+    None => (false, value.strip_prefix('+').unwrap_or(value)),
+  };
+  let rest = match rest.strip_prefix('P') {
+    Some(v) => v,
+    None => return err(format!("invalid duration: {}", value)),
+  };
+
+  let mut total = Duration::zero();
+  let mut num = String::new();
+  for c in rest.chars() {
+    match c {
+      '0'..='9' => num.push(c),
+      'T' => {}
+      'W' | 'D' | 'H' | 'M' | 'S' => {
+        let n: i64 = match num.parse() {
+          Ok(n) => n,
+          Err(_) => return err(format!("invalid duration: {}", value)),
+        };
+        let part = match c {
+          'W' => Duration::try_weeks(n),
+          'D' => Duration::try_days(n),
+          'H' => Duration::try_hours(n),
+          'M' => Duration::try_minutes(n),
+          _ => Duration::try_seconds(n),
+        };
+        total = match part.and_then(|part| total.checked_add(&part)) {
+          Some(total) => total,
+          None => return err(format!("duration out of range: {}", value)),
+        };
+        num.clear();
+      }
+      _ => return err(format!("invalid duration: {}", value)),
+    }
+  }
+
+  Ok(if negative { -total } else { total })
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+  match value {
+    "MO" => Some(Weekday::Mon),
+    "TU" => Some(Weekday::Tue),
+    "WE" => Some(Weekday::Wed),
+    "TH" => Some(Weekday::Thu),
+    "FR" => Some(Weekday::Fri),
+    "SA" => Some(Weekday::Sat),
+    "SU" => Some(Weekday::Sun),
+    _ => None,
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Frequency {
+  Daily,
+  Weekly,
+  Monthly,
+  Yearly,
+}
+
+#[derive(Clone, Debug)]
+struct RecurrenceRule {
+  frequency: Frequency,
+  interval: i64,
+  count: Option<usize>,
+  until: Option<i64>,
+  // an ordinal of Some(2) with Weekday::Mon means "the second monday"
+  by_day: Vec<(Option<i64>, Weekday)>,
+  by_month_day: Vec<i64>,
+  by_month: Vec<u32>,
+}
+
+fn parse_rrule(value: &str, zone: Zone) -> Result<RecurrenceRule, IcalError> {
+  let mut frequency = None;
+  let mut rule = RecurrenceRule {
+    frequency: Frequency::Daily,
+    interval: 1,
+    count: None,
+    until: None,
+    by_day: vec![],
+    by_month_day: vec![],
+    by_month: vec![],
+  };
+
+  let invalid = || IcalError(format!("invalid recurrence rule: {}", value));
+
+  for part in value.split(';').filter(|x| !x.is_empty()) {
+    let (k, v) = part.split_once('=').ok_or_else(invalid)?;
+    match k.to_ascii_uppercase().as_str() {
+      "FREQ" => {
+        frequency = Some(match v {
+          "DAILY" => Frequency::Daily,
+          "WEEKLY" => Frequency::Weekly,
+          "MONTHLY" => Frequency::Monthly,
+          "YEARLY" => Frequency::Yearly,
+          _ => return err(format!("unsupported frequency: {}", v)),
+        })
+      }
+      "INTERVAL" => rule.interval = v.parse().map_err(|_| invalid())?,
+      "COUNT" => rule.count = Some(v.parse().map_err(|_| invalid())?),
+      "UNTIL" => {
+        let until = parse_date_time_str(v, None, zone)?;
+        rule.until = Some(to_millis(until.local, until.zone))
+      }
+      "BYDAY" => {
+        for d in v.split(',') {
+          // the weekday is the last two characters, anything before them is the ordinal
+          if d.len() < 2 || !d.is_char_boundary(d.len() - 2) {
+            return Err(invalid());
+          }
+          let (ordinal, day) = d.split_at(d.len() - 2);
+          let day = parse_weekday(day).ok_or_else(invalid)?;
+          let ordinal = match ordinal {
+            "" => None,
+            o => match o.trim_start_matches('+').parse() {
+              Ok(0) | Err(_) => return Err(invalid()),
+              Ok(n) => Some(n),
+            },
+          };
+          rule.by_day.push((ordinal, day));
+        }
+      }
+      "BYMONTHDAY" => {
+        for d in v.split(',') {
+          match d.parse() {
+            Ok(d @ (-31..=-1 | 1..=31)) => rule.by_month_day.push(d),
+            _ => return Err(invalid()),
+          }
+        }
+      }
+      "BYMONTH" => {
+        for m in v.split(',') {
+          match m.parse() {
+            Ok(m @ 1..=12) => rule.by_month.push(m),
+            _ => return Err(invalid()),
+          }
+        }
+      }
+      // these would change which instances there are, so we'd rather not import the event at all than get it wrong
+      "BYSETPOS" | "BYYEARDAY" | "BYWEEKNO" | "BYHOUR" | "BYMINUTE" | "BYSECOND" => {
+        return err(format!("unsupported recurrence rule part: {}", k))
+      }
+      // WKST only matters for weekly rules with BYDAY and an interval, which is rare enough that we ignore it
+      _ => {}
+    }
+  }
+
+  rule.frequency = frequency.ok_or_else(invalid)?;
+  if !(1..=MAX_RECURRENCE_INTERVAL).contains(&rule.interval) {
+    return Err(invalid());
+  }
+  Ok(rule)
+}
+
+// saturates rather than overflowing, the dates of such years don't exist anyway
+fn add_months(year: i32, month: u32, n: i64) -> (i32, u32) {
+  let m = (year as i64) * 12 + (month as i64 - 1) + n;
+  (
+    m.div_euclid(12).clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+    (m.rem_euclid(12) + 1) as u32,
+  )
+}
+
+// None if the month is out of range
+fn days_in_month(year: i32, month: u32) -> Option<u32> {
+  let (ny, nm) = add_months(year, month, 1);
+  Some(NaiveDate::from_ymd_opt(ny, nm, 1)?.pred_opt()?.day())
+}
+
+// picks the days matching BYDAY out of `days`, which are in order.
+// an ordinal counts matching weekdays from the start (or from the end if negative) of `days`
+fn by_day_dates(by_day: &[(Option<i64>, Weekday)], days: &[NaiveDate]) -> Vec<NaiveDate> {
+  let mut dates = vec![];
+  for (ordinal, weekday) in by_day {
+    let matching: Vec<NaiveDate> = days
+      .iter()
+      .copied()
+      .filter(|d| d.weekday() == *weekday)
+      .collect();
+    match ordinal {
+      None => dates.extend(matching),
+      Some(n) if *n > 0 => dates.extend(matching.get(*n as usize - 1)),
+      Some(n) => dates.extend(
+        matching
+          .len()
+          .checked_sub(n.unsigned_abs() as usize)
+          .and_then(|i| matching.get(i)),
+      ),
+    }
+  }
+  dates
+}
+
+// the candidate dates within one month, falling back to `day` when the rule doesn't pick any
+fn month_dates(rule: &RecurrenceRule, year: i32, month: u32, day: u32) -> Vec<NaiveDate> {
+  let last = match days_in_month(year, month) {
+    Some(last) => last as i64,
+    None => return vec![],
+  };
+  if !rule.by_month_day.is_empty() {
+    let mut dates: Vec<NaiveDate> = rule
+      .by_month_day
+      .iter()
+      .map(|&d| if d < 0 { last + 1 + d } else { d })
+      .filter_map(|d| u32::try_from(d).ok())
+      .filter_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+      .collect();
+    // BYDAY only narrows the days down, as in friday the 13th
+    if !rule.by_day.is_empty() {
+      dates.retain(|d| rule.by_day.iter().any(|(_, w)| *w == d.weekday()));
+    }
+    dates
+  } else if !rule.by_day.is_empty() {
+    let days: Vec<NaiveDate> = (1..=last)
+      .filter_map(|d| NaiveDate::from_ymd_opt(year, month, d as u32))
+      .collect();
+    by_day_dates(&rule.by_day, &days)
+  } else {
+    NaiveDate::from_ymd_opt(year, month, day)
+      .into_iter()
+      .collect()
+  }
+}
+
+// all candidate dates in the `period`th period after the start, in order
+fn period_dates(rule: &RecurrenceRule, start: NaiveDate, period: i64) -> Vec<NaiveDate> {
+  let mut dates = match rule.frequency {
+    Frequency::Daily => {
+      let d = match Duration::try_days(period * rule.interval)
+        .and_then(|d| start.checked_add_signed(d))
+      {
+        Some(d) => d,
+        // past the last date there is
+        None => return vec![],
+      };
+      let by_day = rule.by_day.is_empty() || rule.by_day.iter().any(|(_, w)| *w == d.weekday());
+      let by_month_day = rule.by_month_day.is_empty()
+        || month_dates(rule, d.year(), d.month(), d.day()).contains(&d);
+      if by_day && by_month_day {
+        vec![d]
+      } else {
+        vec![]
+      }
+    }
+    Frequency::Weekly => {
+      let week_start = match Duration::try_weeks(period * rule.interval).and_then(|w| {
+        start
+          .checked_sub_signed(Duration::days(start.weekday().num_days_from_monday() as i64))?
+          .checked_add_signed(w)
+      }) {
+        Some(d) => d,
+        None => return vec![],
+      };
+      let weekdays = if rule.by_day.is_empty() {
+        vec![start.weekday()]
+      } else {
+        rule.by_day.iter().map(|(_, w)| *w).collect()
+      };
+      weekdays
+        .into_iter()
+        .filter_map(|w| {
+          week_start.checked_add_signed(Duration::days(w.num_days_from_monday() as i64))
+        })
+        .collect()
+    }
+    Frequency::Monthly => {
+      let (year, month) = add_months(start.year(), start.month(), period * rule.interval);
+      month_dates(rule, year, month, start.day())
+    }
+    Frequency::Yearly => {
+      let year = match i32::try_from(period * rule.interval)
+        .ok()
+        .and_then(|n| start.year().checked_add(n))
+      {
+        Some(year) => year,
+        None => return vec![],
+      };
+      if rule.by_month.is_empty() && rule.by_month_day.is_empty() && !rule.by_day.is_empty() {
+        // without BYMONTH, ordinals count through the whole year, as in the 20th monday
+        let days: Vec<NaiveDate> = NaiveDate::from_ymd_opt(year, 1, 1)
+          .into_iter()
+          .flat_map(|d| d.iter_days())
+          .take_while(|d| d.year() == year)
+          .collect();
+        by_day_dates(&rule.by_day, &days)
+      } else {
+        let months = if !rule.by_month.is_empty() {
+          rule.by_month.clone()
+        } else if !rule.by_month_day.is_empty() {
+          (1..=12).collect()
+        } else {
+          vec![start.month()]
+        };
+        months
+          .into_iter()
+          .flat_map(|m| month_dates(rule, year, m, start.day()))
+          .collect()
+      }
+    }
+  };
+
+  if rule.frequency != Frequency::Yearly && !rule.by_month.is_empty() {
+    dates.retain(|d| rule.by_month.contains(&d.month()));
+  }
+  dates.sort();
+  dates.dedup();
+  dates
+}
+
+// the period to start expanding from so that nothing from `min_date` on is missed.
+// rules with a COUNT have to be walked from the start to know which instances are left
+fn first_period(rule: &RecurrenceRule, start: NaiveDate, min_date: Option<NaiveDate>) -> i64 {
+  let min_date = match min_date {
+    Some(d) if rule.count.is_none() && d > start => d,
+    _ => return 0,
+  };
+  let behind = match rule.frequency {
+    Frequency::Daily => (min_date - start).num_days(),
+    Frequency::Weekly => (min_date - start).num_days() / 7,
+    Frequency::Monthly => {
+      (min_date.year() - start.year()) as i64 * 12 + min_date.month() as i64 - start.month() as i64
+    }
+    Frequency::Yearly => (min_date.year() - start.year()) as i64,
+  };
+  // back off a couple of periods, as min_date is in UTC rather than the event's zone
+  (behind / rule.interval - 2).max(0)
+}
+
+// returns the start times of every instance of the rule that starts in [min_start, max_time)
+fn expand(rule: &RecurrenceRule, start: DateTimeValue, min_start: i64, max_time: i64) -> Vec<i64> {
+  let min_date = Utc
+    .timestamp_millis_opt(min_start)
+    .single()
+    .map(|x| x.date_naive());
+
+  let mut starts = vec![];
+  // instances so far, including the ones before the window, for COUNT
+  let mut generated = 0;
+  let mut period = first_period(rule, start.local.date(), min_date);
+  // guard against rules that never produce a date, like the 30th of february
+  let mut empty_periods = 0;
+
+  'periods: while starts.len() < MAX_RECURRENCE_INSTANCES && empty_periods < 1000 {
+    let dates = period_dates(rule, start.local.date(), period);
+    period += 1;
+
+    if dates.is_empty() {
+      empty_periods += 1;
+      continue;
+    }
+    empty_periods = 0;
+
+    for date in dates {
+      let local = date.and_time(start.local.time());
+      if local < start.local {
+        continue;
+      }
+      let millis = to_millis(local, start.zone);
+      if rule.until.is_some_and(|until| millis > until) || millis >= max_time {
+        break 'periods;
+      }
+      if rule.count.is_some_and(|count| generated >= count) {
+        break 'periods;
+      }
+      generated += 1;
+      if millis >= min_start {
+        starts.push(millis);
+      }
+    }
+  }
+
+  starts
+}
+
+#[derive(Clone, Debug, Default)]
+struct VEvent {
+  props: Vec<Property>,
+}
+
+impl VEvent {
+  fn get(&self, name: &str) -> Option<&Property> {
+    self.props.iter().find(|p| p.name == name)
+  }
+
+  fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> {
+    self.props.iter().filter(move |p| p.name == name)
+  }
+}
+
+// splits the source into VEVENTs, ignoring nested components such as VALARM.
+// also returns the calendar's default time zone, if it specifies one
+fn parse_vevents(source: &str) -> Result<(Vec<VEvent>, Option<Zone>), IcalError> {
+  let mut stack: Vec<String> = vec![];
+  let mut events = vec![];
+  let mut current: Option<VEvent> = None;
+  let mut default_zone = None;
+
+  for line in unfold(source) {
+    let prop = parse_property(&line)?;
+    match prop.name.as_str() {
+      "BEGIN" => {
+        let component = prop.value.to_ascii_uppercase();
+        if component == "VEVENT" {
+          current = Some(VEvent::default());
+        }
+        stack.push(component);
+      }
+      "END" => {
+        let component = prop.value.to_ascii_uppercase();
+        if stack.pop().as_deref() != Some(component.as_str()) {
+          return err(format!("unbalanced END:{}", component));
+        }
+        if component == "VEVENT" {
+          events.extend(current.take());
+        }
+      }
+      "X-WR-TIMEZONE" if stack.last().map(|x| x.as_str()) == Some("VCALENDAR") => {
+        default_zone = Some(parse_zone(&prop.value)?);
+      }
+      _ => {
+        if stack.last().map(|x| x.as_str()) == Some("VEVENT") {
+          if let Some(ref mut e) = current {
+            e.props.push(prop);
+          }
+        }
+      }
+    }
+  }
+
+  if !stack.is_empty() {
+    return err("unterminated component");
+  }
+
+  Ok((events, default_zone))
+}
+
+// Parses an iCalendar file and returns every event occurrence overlapping [min_time, max_time).
+// Recurring events are expanded, honoring EXDATE and RECURRENCE-ID overrides.
+// Cancelled and zero length events are skipped.
+pub fn parse_events(source: &str, min_time: i64, max_time: i64) -> Result<Vec<Event>, IcalError> {
+  let (vevents, default_zone) = parse_vevents(source)?;
+  let default_zone = default_zone.unwrap_or(Zone::Utc);
+
+  // instances of a recurring event that were moved or edited individually
+  let mut overridden: HashMap<String, HashSet<i64>> = HashMap::new();
+  for vevent in &vevents {
+    if let (Some(uid), Some(recurrence_id)) = (vevent.get("UID"), vevent.get("RECURRENCE-ID")) {
+      let recurrence_id = parse_date_time(recurrence_id, default_zone)?;
+      overridden
+        .entry(uid.value.clone())
+        .or_default()
+        .insert(to_millis(recurrence_id.local, recurrence_id.zone));
+    }
+  }
+
+  let mut events = vec![];
+  for vevent in &vevents {
+    if vevent
+      .get("STATUS")
+      .is_some_and(|s| s.value.eq_ignore_ascii_case("CANCELLED"))
+    {
+      continue;
+    }
+
+    let start = match vevent.get("DTSTART") {
+      Some(p) => parse_date_time(p, default_zone)?,
+      None => return err("VEVENT without DTSTART"),
+    };
+    let start_millis = to_millis(start.local, start.zone);
+
+    let duration = match (vevent.get("DTEND"), vevent.get("DURATION")) {
+      (Some(end), _) => {
+        let end = parse_date_time(end, start.zone)?;
+        to_millis(end.local, end.zone) - start_millis
+      }
+      (None, Some(d)) => parse_duration(d.value.trim())?.num_milliseconds(),
+      // all day events without an end last the whole day
+      (None, None) if start.all_day => Duration::days(1).num_milliseconds(),
+      (None, None) => 0,
+    };
+
+    if duration <= 0 {
+      continue;
+    }
+
+    let name = vevent
+      .get("SUMMARY")
+      .map(|p| unescape_text(&p.value))
+      .unwrap_or_default();
+
+    let uid = vevent
+      .get("UID")
+      .map(|p| p.value.trim().to_owned())
+      .unwrap_or_else(|| format!("{}-{}", start_millis, name));
+
+    let starts: Vec<(String, i64)> = match vevent.get("RRULE") {
+      // an override of a single instance is never itself recurring
+      Some(rrule) if vevent.get("RECURRENCE-ID").is_none() => {
+        let rule = parse_rrule(rrule.value.trim(), start.zone)?;
+
+        let mut excluded: HashSet<i64> = HashSet::new();
+        for exdate in vevent.get_all("EXDATE") {
+          for value in exdate.value.split(',') {
+            let v = parse_date_time_str(value.trim(), exdate.param("TZID"), start.zone)?;
+            // date only exclusions refer to the instance starting on that day
+            let local = if v.all_day {
+              v.local.date().and_time(start.local.time())
+            } else {
+              v.local
+            };
+            excluded.insert(to_millis(local, v.zone));
+          }
+        }
+
+        let overrides = vevent.get("UID").and_then(|uid| overridden.get(&uid.value));
+
+        expand(&rule, start, min_time.saturating_sub(duration), max_time)
+          .into_iter()
+          .filter(|s| !excluded.contains(s))
+          .filter(|s| !overrides.is_some_and(|o| o.contains(s)))
+          .map(|s| (format!("{}/{}", uid, s), s))
+          .collect()
+      }
+      _ => match vevent.get("RECURRENCE-ID") {
+        // an override is identified by the instance it replaces
+        Some(recurrence_id) => {
+          let r = parse_date_time(recurrence_id, default_zone)?;
+          vec![(format!("{}/{}", uid, to_millis(r.local, r.zone)), start_millis)]
+        }
+        None => vec![(uid, start_millis)],
+      },
+    };
+
+    for (uid, start_time) in starts {
+      let end_time = match start_time.checked_add(duration) {
+        Some(end_time) => end_time,
+        None => return err(format!("event {} ends out of range", uid)),
+      };
+      if end_time > min_time && start_time < max_time {
+        events.push(Event {
+          uid,
+          name: name.clone(),
+          start_time,
+          end_time,
+        });
+      }
+    }
+  }
+
+  events.sort_by_key(|e| e.start_time);
+  Ok(events)
+}
+
+
+fn escape_text(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace(';', "\\;")
+    .replace(',', "\\,")
+    .replace('\n', "\\n")
+}
+
+// fails for times chrono can't represent, which only come from bogus data
+pub fn format_utc(millis: i64) -> Result<String, IcalError> {
+  match Utc.timestamp_millis_opt(millis).single() {
+    Some(v) => Ok(v.format("%Y%m%dT%H%M%SZ").to_string()),
+    None => err(format!("time out of range: {}", millis)),
+  }
+}
+
+// lines longer than 75 octets must be folded, without splitting a utf-8 character
+fn push_line(out: &mut String, line: &str) {
+  let mut len = 0;
+  for c in line.chars() {
+    if len + c.len_utf8() > 75 {
+      out.push_str("\r\n ");
+      len = 1;
+    }
+    out.push(c);
+    len += c.len_utf8();
+  }
+  out.push_str("\r\n");
+}
+
+// Serializes events into an iCalendar file, with all times in UTC.
+// Events with times that can't be written are left out.
+pub fn write_calendar(name: &str, events: &[Event]) -> String {
+  let now = Utc::now().format("%Y%m%dT%H%M%SZ");
+
+  let mut out = String::new();
+  push_line(&mut out, "BEGIN:VCALENDAR");
+  push_line(&mut out, "VERSION:2.0");
+  push_line(&mut out, "PRODID:-//innexgo//todo-app-service//EN");
+  push_line(&mut out, "CALSCALE:GREGORIAN");
+  push_line(&mut out, &format!("X-WR-CALNAME:{}", escape_text(name)));
+  for event in events {
+    let (start_time, end_time) = match (format_utc(event.start_time), format_utc(event.end_time)) {
+      (Ok(start_time), Ok(end_time)) => (start_time, end_time),
+      _ => continue,
+    };
+    push_line(&mut out, "BEGIN:VEVENT");
+    push_line(&mut out, &format!("UID:{}", escape_text(&event.uid)));
+    push_line(&mut out, &format!("DTSTAMP:{}", now));
+    push_line(&mut out, &format!("DTSTART:{}", start_time));
+    push_line(&mut out, &format!("DTEND:{}", end_time));
+    push_line(&mut out, &format!("SUMMARY:{}", escape_text(&event.name)));
+    push_line(&mut out, "END:VEVENT");
+  }
+  push_line(&mut out, "END:VCALENDAR");
+  out
+}
+
+// windows time zone names and the IANA zone each stands for, from CLDR's windowsZones.xml
+static WINDOWS_ZONES: &[(&str, &str)] = &[
+  ("Dateline Standard Time", "Etc/GMT+12"),
+  ("UTC-11", "Etc/GMT+11"),
+  ("Aleutian Standard Time", "America/Adak"),
+  ("Hawaiian Standard Time", "Pacific/Honolulu"),
+  ("Marquesas Standard Time", "Pacific/Marquesas"),
+  ("Alaskan Standard Time", "America/Anchorage"),
+  ("UTC-09", "Etc/GMT+9"),
+  ("Pacific Standard Time (Mexico)", "America/Tijuana"),
+  ("UTC-08", "Etc/GMT+8"),
+  ("Pacific Standard Time", "America/Los_Angeles"),
+  ("US Mountain Standard Time", "America/Phoenix"),
+  ("Mountain Standard Time (Mexico)", "America/Mazatlan"),
+  ("Mountain Standard Time", "America/Denver"),
+  ("Yukon Standard Time", "America/Whitehorse"),
+  ("Central America Standard Time", "America/Guatemala"),
+  ("Central Standard Time", "America/Chicago"),
+  ("Easter Island Standard Time", "Pacific/Easter"),
+  ("Central Standard Time (Mexico)", "America/Mexico_City"),
+  ("Canada Central Standard Time", "America/Regina"),
+  ("SA Pacific Standard Time", "America/Bogota"),
+  ("Eastern Standard Time (Mexico)", "America/Cancun"),
+  ("Eastern Standard Time", "America/New_York"),
+  ("Haiti Standard Time", "America/Port-au-Prince"),
+  ("Cuba Standard Time", "America/Havana"),
+  ("US Eastern Standard Time", "America/Indiana/Indianapolis"),
+  ("Turks And Caicos Standard Time", "America/Grand_Turk"),
+  ("Paraguay Standard Time", "America/Asuncion"),
+  ("Atlantic Standard Time", "America/Halifax"),
+  ("Venezuela Standard Time", "America/Caracas"),
+  ("Central Brazilian Standard Time", "America/Cuiaba"),
+  ("SA Western Standard Time", "America/La_Paz"),
+  ("Pacific SA Standard Time", "America/Santiago"),
+  ("Newfoundland Standard Time", "America/St_Johns"),
+  ("Tocantins Standard Time", "America/Araguaina"),
+  ("E. South America Standard Time", "America/Sao_Paulo"),
+  ("SA Eastern Standard Time", "America/Cayenne"),
+  ("Argentina Standard Time", "America/Argentina/Buenos_Aires"),
+  ("Greenland Standard Time", "America/Nuuk"),
+  ("Montevideo Standard Time", "America/Montevideo"),
+  ("Magallanes Standard Time", "America/Punta_Arenas"),
+  ("Saint Pierre Standard Time", "America/Miquelon"),
+  ("Bahia Standard Time", "America/Bahia"),
+  ("UTC-02", "Etc/GMT+2"),
+  ("Mid-Atlantic Standard Time", "Etc/GMT+2"),
+  ("Azores Standard Time", "Atlantic/Azores"),
+  ("Cape Verde Standard Time", "Atlantic/Cape_Verde"),
+  ("UTC", "Etc/UTC"),
+  ("GMT Standard Time", "Europe/London"),
+  ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+  ("Sao Tome Standard Time", "Africa/Sao_Tome"),
+  ("Morocco Standard Time", "Africa/Casablanca"),
+  ("W. Europe Standard Time", "Europe/Berlin"),
+  ("Central Europe Standard Time", "Europe/Budapest"),
+  ("Romance Standard Time", "Europe/Paris"),
+  ("Central European Standard Time", "Europe/Warsaw"),
+  ("W. Central Africa Standard Time", "Africa/Lagos"),
+  ("Jordan Standard Time", "Asia/Amman"),
+  ("GTB Standard Time", "Europe/Bucharest"),
+  ("Middle East Standard Time", "Asia/Beirut"),
+  ("Egypt Standard Time", "Africa/Cairo"),
+  ("E. Europe Standard Time", "Europe/Chisinau"),
+  ("Syria Standard Time", "Asia/Damascus"),
+  ("West Bank Standard Time", "Asia/Hebron"),
+  ("South Africa Standard Time", "Africa/Johannesburg"),
+  ("FLE Standard Time", "Europe/Kiev"),
+  ("Israel Standard Time", "Asia/Jerusalem"),
+  ("South Sudan Standard Time", "Africa/Juba"),
+  ("Kaliningrad Standard Time", "Europe/Kaliningrad"),
+  ("Sudan Standard Time", "Africa/Khartoum"),
+  ("Libya Standard Time", "Africa/Tripoli"),
+  ("Namibia Standard Time", "Africa/Windhoek"),
+  ("Arabic Standard Time", "Asia/Baghdad"),
+  ("Turkey Standard Time", "Europe/Istanbul"),
+  ("Arab Standard Time", "Asia/Riyadh"),
+  ("Belarus Standard Time", "Europe/Minsk"),
+  ("Russian Standard Time", "Europe/Moscow"),
+  ("E. Africa Standard Time", "Africa/Nairobi"),
+  ("Volgograd Standard Time", "Europe/Volgograd"),
+  ("Iran Standard Time", "Asia/Tehran"),
+  ("Arabian Standard Time", "Asia/Dubai"),
+  ("Astrakhan Standard Time", "Europe/Astrakhan"),
+  ("Azerbaijan Standard Time", "Asia/Baku"),
+  ("Russia Time Zone 3", "Europe/Samara"),
+  ("Mauritius Standard Time", "Indian/Mauritius"),
+  ("Saratov Standard Time", "Europe/Saratov"),
+  ("Georgian Standard Time", "Asia/Tbilisi"),
+  ("Caucasus Standard Time", "Asia/Yerevan"),
+  ("Afghanistan Standard Time", "Asia/Kabul"),
+  ("West Asia Standard Time", "Asia/Tashkent"),
+  ("Ekaterinburg Standard Time", "Asia/Yekaterinburg"),
+  ("Pakistan Standard Time", "Asia/Karachi"),
+  ("Qyzylorda Standard Time", "Asia/Qyzylorda"),
+  ("India Standard Time", "Asia/Kolkata"),
+  ("Sri Lanka Standard Time", "Asia/Colombo"),
+  ("Nepal Standard Time", "Asia/Kathmandu"),
+  ("Central Asia Standard Time", "Asia/Almaty"),
+  ("Bangladesh Standard Time", "Asia/Dhaka"),
+  ("Omsk Standard Time", "Asia/Omsk"),
+  ("Myanmar Standard Time", "Asia/Yangon"),
+  ("SE Asia Standard Time", "Asia/Bangkok"),
+  ("Altai Standard Time", "Asia/Barnaul"),
+  ("W. Mongolia Standard Time", "Asia/Hovd"),
+  ("North Asia Standard Time", "Asia/Krasnoyarsk"),
+  ("N. Central Asia Standard Time", "Asia/Novosibirsk"),
+  ("Tomsk Standard Time", "Asia/Tomsk"),
+  ("China Standard Time", "Asia/Shanghai"),
+  ("North Asia East Standard Time", "Asia/Irkutsk"),
+  ("Singapore Standard Time", "Asia/Singapore"),
+  ("W. Australia Standard Time", "Australia/Perth"),
+  ("Taipei Standard Time", "Asia/Taipei"),
+  ("Ulaanbaatar Standard Time", "Asia/Ulaanbaatar"),
+  ("Aus Central W. Standard Time", "Australia/Eucla"),
+  ("Transbaikal Standard Time", "Asia/Chita"),
+  ("Tokyo Standard Time", "Asia/Tokyo"),
+  ("North Korea Standard Time", "Asia/Pyongyang"),
+  ("Korea Standard Time", "Asia/Seoul"),
+  ("Yakutsk Standard Time", "Asia/Yakutsk"),
+  ("Cen. Australia Standard Time", "Australia/Adelaide"),
+  ("AUS Central Standard Time", "Australia/Darwin"),
+  ("E. Australia Standard Time", "Australia/Brisbane"),
+  ("AUS Eastern Standard Time", "Australia/Sydney"),
+  ("West Pacific Standard Time", "Pacific/Port_Moresby"),
+  ("Tasmania Standard Time", "Australia/Hobart"),
+  ("Vladivostok Standard Time", "Asia/Vladivostok"),
+  ("Lord Howe Standard Time", "Australia/Lord_Howe"),
+  ("Bougainville Standard Time", "Pacific/Bougainville"),
+  ("Russia Time Zone 10", "Asia/Srednekolymsk"),
+  ("Magadan Standard Time", "Asia/Magadan"),
+  ("Norfolk Standard Time", "Pacific/Norfolk"),
+  ("Sakhalin Standard Time", "Asia/Sakhalin"),
+  ("Central Pacific Standard Time", "Pacific/Guadalcanal"),
+  ("Russia Time Zone 11", "Asia/Kamchatka"),
+  ("New Zealand Standard Time", "Pacific/Auckland"),
+  ("UTC+12", "Etc/GMT-12"),
+  ("Fiji Standard Time", "Pacific/Fiji"),
+  ("Chatham Islands Standard Time", "Pacific/Chatham"),
+  ("UTC+13", "Etc/GMT-13"),
+  ("Tonga Standard Time", "Pacific/Tongatapu"),
+  ("Samoa Standard Time", "Pacific/Apia"),
+  ("Line Islands Standard Time", "Pacific/Kiritimati"),
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // unix millis of a UTC `YYYYMMDDTHHMMSS` time
+  fn utc(value: &str) -> i64 {
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").unwrap();
+    Utc.from_utc_datetime(&local).timestamp_millis()
+  }
+
+  fn calendar(body: &str) -> String {
+    format!(
+      "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}\r\nEND:VCALENDAR\r\n",
+      body.trim().replace('\n', "\r\n")
+    )
+  }
+
+  fn starts(body: &str, min_time: i64, max_time: i64) -> Vec<i64> {
+    parse_events(&calendar(body), min_time, max_time)
+      .unwrap()
+      .into_iter()
+      .map(|e| e.start_time)
+      .collect()
+  }
+
+  #[test]
+  fn rejects_durations_out_of_range() {
+    // too many weeks, a sum that is too long, and an end after the last time there is
+    for duration in [
+      "P9999999999999W",
+      "P106751991167DT8H",
+      "P106751991167D",
+      "P1W",
+    ] {
+      let result = parse_events(
+        &calendar(&format!(
+          "BEGIN:VEVENT\nUID:a\nDTSTART:20240101T090000Z\nDURATION:{}\nEND:VEVENT",
+          duration
+        )),
+        i64::MIN,
+        i64::MAX,
+      );
+      assert_eq!(result.is_ok(), duration == "P1W", "{}", duration);
+    }
+  }
+
+  #[test]
+  fn unfolds_lines() {
+    let events = parse_events(
+      &calendar(
+        "BEGIN:VEVENT\nUID:a\nDTSTART:20240101T090000Z\nDTEND:20240101T100000Z\nSUMMARY:a long\n  name\n\tcontinued\nEND:VEVENT",
+      ),
+      i64::MIN,
+      i64::MAX,
+    )
+    .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].name, "a long namecontinued");
+  }
+
+  #[test]
+  fn all_day_events_last_a_day() {
+    let events = parse_events(
+      &calendar("BEGIN:VEVENT\nUID:a\nDTSTART;VALUE=DATE:20240101\nEND:VEVENT"),
+      i64::MIN,
+      i64::MAX,
+    )
+    .unwrap();
+    assert_eq!(events[0].start_time, utc("20240101T000000"));
+    assert_eq!(events[0].end_time, utc("20240102T000000"));
+  }
+
+  #[test]
+  fn honors_tzid() {
+    let body = |tzid: &str| {
+      format!(
+        "BEGIN:VEVENT\nUID:a\nDTSTART;TZID={}:20240701T090000\nDURATION:PT1H\nEND:VEVENT",
+        tzid
+      )
+    };
+    let berlin = utc("20240701T070000");
+    assert_eq!(starts(&body("Europe/Berlin"), i64::MIN, i64::MAX), [berlin]);
+    assert_eq!(
+      starts(&body("\"W. Europe Standard Time\""), i64::MIN, i64::MAX),
+      [berlin]
+    );
+    assert_eq!(
+      starts(
+        &body("/mozilla.org/20050126_1/Europe/Berlin"),
+        i64::MIN,
+        i64::MAX
+      ),
+      [berlin]
+    );
+    assert!(parse_events(
+      &calendar(&body("Nowhere Standard Time")),
+      i64::MIN,
+      i64::MAX
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn every_windows_zone_is_known() {
+    for (windows, iana) in WINDOWS_ZONES {
+      assert!(
+        iana.parse::<Tz>().is_ok(),
+        "{} maps to unknown {}",
+        windows,
+        iana
+      );
+    }
+  }
+
+  #[test]
+  fn excludes_exdates() {
+    let body = "BEGIN:VEVENT\nUID:a\nDTSTART;TZID=Europe/Berlin:20240101T090000\nDURATION:PT1H\nRRULE:FREQ=DAILY;COUNT=4\nEXDATE;TZID=Europe/Berlin:20240102T090000\nEXDATE;VALUE=DATE:20240103\nEND:VEVENT";
+    assert_eq!(
+      starts(body, i64::MIN, i64::MAX),
+      [utc("20240101T080000"), utc("20240104T080000")]
+    );
+  }
+
+  #[test]
+  fn expands_byday_ordinals() {
+    // the last friday of every month
+    let body = "BEGIN:VEVENT\nUID:a\nDTSTART:20240126T090000Z\nDURATION:PT1H\nRRULE:FREQ=MONTHLY;BYDAY=-1FR;COUNT=3\nEND:VEVENT";
+    assert_eq!(
+      starts(body, i64::MIN, i64::MAX),
+      [
+        utc("20240126T090000"),
+        utc("20240223T090000"),
+        utc("20240329T090000")
+      ]
+    );
+
+    // thanksgiving
+    let body = "BEGIN:VEVENT\nUID:a\nDTSTART;VALUE=DATE:20231123\nRRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=4TH;COUNT=3\nEND:VEVENT";
+    assert_eq!(
+      starts(body, i64::MIN, i64::MAX),
+      [
+        utc("20231123T000000"),
+        utc("20241128T000000"),
+        utc("20251127T000000")
+      ]
+    );
+
+    // the 10th monday of the year
+    let body = "BEGIN:VEVENT\nUID:a\nDTSTART:20240304T090000Z\nDURATION:PT1H\nRRULE:FREQ=YEARLY;BYDAY=10MO;COUNT=2\nEND:VEVENT";
+    assert_eq!(
+      starts(body, i64::MIN, i64::MAX),
+      [utc("20240304T090000"), utc("20250310T090000")]
+    );
+
+    // friday the 13th
+    let body = "BEGIN:VEVENT\nUID:a\nDTSTART:20240913T090000Z\nDURATION:PT1H\nRRULE:FREQ=YEARLY;BYMONTHDAY=13;BYDAY=FR;COUNT=2\nEND:VEVENT";
+    assert_eq!(
+      starts(body, i64::MIN, i64::MAX),
+      [utc("20240913T090000"), utc("20241213T090000")]
+    );
+  }
+
+  #[test]
+  fn rejects_bad_byday() {
+    for byday in ["é", "1é", "0MO", "XX", "M"] {
+      let body = format!(
+        "BEGIN:VEVENT\nUID:a\nDTSTART:20240101T090000Z\nDURATION:PT1H\nRRULE:FREQ=WEEKLY;BYDAY={}\nEND:VEVENT",
+        byday
+      );
+      assert!(
+        parse_events(&calendar(&body), i64::MIN, i64::MAX).is_err(),
+        "{}",
+        byday
+      );
+    }
+  }
+
+  #[test]
+  fn rejects_unsupported_rules() {
+    let body = "BEGIN:VEVENT\nUID:a\nDTSTART:20240101T090000Z\nDURATION:PT1H\nRRULE:FREQ=MONTHLY;BYDAY=MO,TU;BYSETPOS=-1\nEND:VEVENT";
+    assert!(parse_events(&calendar(body), i64::MIN, i64::MAX).is_err());
+  }
+
+  #[test]
+  fn keeps_events_overlapping_the_window() {
+    let body =
+      "BEGIN:VEVENT\nUID:a\nDTSTART:20240101T090000Z\nDURATION:PT1H\nRRULE:FREQ=DAILY\nEND:VEVENT";
+    // an instance ending at the start of the window is out, one overlapping it is in
+    assert_eq!(
+      starts(body, utc("20240102T100000"), utc("20240103T090000")),
+      Vec::<i64>::new()
+    );
+    assert_eq!(
+      starts(body, utc("20240102T095959"), utc("20240103T090001")),
+      [utc("20240102T090000"), utc("20240103T090000")]
+    );
+  }
+
+  #[test]
+  fn caps_instances_in_the_window() {
+    // a rule far older than the cap still has its instances in a later window
+    let body =
+      "BEGIN:VEVENT\nUID:a\nDTSTART:19500101T090000Z\nDURATION:PT1H\nRRULE:FREQ=DAILY\nEND:VEVENT";
+    assert_eq!(
+      starts(body, utc("20240101T000000"), utc("20240103T000000")),
+      [utc("20240101T090000"), utc("20240102T090000")]
+    );
+
+    // and COUNT is still counted from the start
+    let body = "BEGIN:VEVENT\nUID:a\nDTSTART:19500101T090000Z\nDURATION:PT1H\nRRULE:FREQ=YEARLY;COUNT=75\nEND:VEVENT";
+    assert_eq!(
+      starts(body, utc("20200101T000000"), utc("20300101T000000")),
+      [
+        utc("20200101T090000"),
+        utc("20210101T090000"),
+        utc("20220101T090000"),
+        utc("20230101T090000"),
+        utc("20240101T090000")
+      ]
+    );
+  }
+
+  #[test]
+  fn leaves_out_events_it_cant_write() {
+    let event = |uid: &str, start_time: i64| Event {
+      uid: uid.to_owned(),
+      name: "a".to_owned(),
+      start_time,
+      end_time: start_time + 1000,
+    };
+    let ics = write_calendar("a", &[event("ok", 0), event("bogus", i64::MAX - 1000)]);
+    assert!(ics.contains("UID:ok"));
+    assert!(!ics.contains("UID:bogus"));
+  }
+}
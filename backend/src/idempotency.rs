@@ -0,0 +1,201 @@
+// Lets clients on flaky networks safely retry requests that create something.
+// A request sent with an Idempotency-Key header claims the key for the user before it is handled,
+// and its response is stored with the key. A retry with the same key gets the stored response instead of
+// creating the thing again, while the same key with a different request is rejected.
+// Keys are scoped to the user and kept for a day. Requests that fail don't keep their key, so they can be retried.
+// The response is stored after the handler committed, so if we go down in between, the request may have been handled
+// without a response to show for it. Such a key stays in progress until it expires rather than risk handling it twice.
+
+use super::auth::Auth;
+use super::handlers;
+use super::idempotency_key_service;
+use super::utils;
+use super::Config;
+use super::Db;
+use deadpool_postgres::PoolError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::time::Duration;
+use todo_app_service_api::response::TodoAppError;
+
+// how long a key is remembered
+static RETENTION: i64 = 24 * 60 * 60 * 1000;
+// the longest key we accept
+static MAX_KEY_LEN: usize = 255;
+// how often old keys are pruned
+static PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// what identifies a request: the path, and the props without the api key,
+// so that a retry with a refreshed api key is still the same request
+fn request_hash(path: &str, props: &serde_json::Value) -> String {
+  let mut props = props.clone();
+  if let Some(props) = props.as_object_mut() {
+    props.remove("apiKey");
+  }
+  utils::to_hex(&Sha256::digest(format!("{}\n{}", path, props).as_bytes()))
+}
+
+// handles the request once per key, see above. without a key, it is simply handled
+pub async fn handle<PropsType, ResponseType, F>(
+  config: Config,
+  db: Db,
+  auth: Auth,
+  path: &str,
+  idempotency_key: Option<String>,
+  props: PropsType,
+  handler: fn(Config, Db, Auth, PropsType) -> F,
+) -> Result<ResponseType, TodoAppError>
+where
+  F: Future<Output = Result<ResponseType, TodoAppError>>,
+  PropsType: Serialize,
+  ResponseType: Serialize + DeserializeOwned,
+{
+  let idempotency_key = match idempotency_key {
+    Some(idempotency_key) => idempotency_key,
+    None => return handler(config, db, auth, props).await,
+  };
+  if idempotency_key.is_empty() || idempotency_key.len() > MAX_KEY_LEN {
+    return Err(TodoAppError::DecodeError);
+  }
+
+  let props_json = serde_json::to_value(&props).map_err(|_| TodoAppError::DecodeError)?;
+  let api_key = props_json
+    .get("apiKey")
+    .and_then(|x| x.as_str())
+    .ok_or(TodoAppError::DecodeError)?
+    .to_owned();
+  let request_hash = request_hash(path, &props_json);
+
+  // validate api key
+  let user = handlers::get_user_if_api_key_valid(&auth, api_key).await?;
+
+  // the connection is given back before the request is handled, it checks out its own
+  let claim = {
+    let con = &mut **db.get().await.map_err(handlers::report_pool_err)?;
+    let claim = idempotency_key_service::claim(
+      con,
+      user.user_id,
+      &idempotency_key,
+      &request_hash,
+      utils::current_time_millis() - RETENTION,
+    )
+    .await
+    .map_err(handlers::report_postgres_err)?;
+
+    match claim {
+      Some(idempotency_key_id) => idempotency_key_id,
+      None => {
+        // the key was used before, or is being used right now
+        let previous =
+          idempotency_key_service::get_by_idempotency_key(con, user.user_id, &idempotency_key)
+            .await
+            .map_err(handlers::report_postgres_err)?
+            .ok_or(TodoAppError::IdempotencyKeyInProgress)?;
+        if previous.request_hash != request_hash {
+          return Err(TodoAppError::IdempotencyKeyReused);
+        }
+        return match previous.response {
+          Some(response) => serde_json::from_str(&response).map_err(|e| {
+            utils::log(utils::Event {
+              msg: e.to_string(),
+              source: Some("idempotency key".to_owned()),
+              severity: utils::SeverityKind::Error,
+            });
+            TodoAppError::InternalServerError
+          }),
+          None => Err(TodoAppError::IdempotencyKeyInProgress),
+        };
+      }
+    }
+  };
+
+  let result = handler(config, db.clone(), auth, props).await;
+
+  // whatever happens now, the request was handled, so its result is returned
+  let response = match &result {
+    Ok(response) => match serde_json::to_string(response) {
+      Ok(response) => Some(response),
+      // a retry would be handled again, but that beats a key that is stuck for a day
+      Err(e) => {
+        utils::log(utils::Event {
+          msg: e.to_string(),
+          source: Some("idempotency key".to_owned()),
+          severity: utils::SeverityKind::Error,
+        });
+        None
+      }
+    },
+    Err(_) => None,
+  };
+  let recorded: Result<_, PoolError> = async {
+    let con = &mut **db.get().await?;
+    match response {
+      Some(response) => idempotency_key_service::complete(con, claim, &response).await?,
+      // failed requests can be retried with the same key, and so can those whose response can't be stored
+      None => idempotency_key_service::release(con, claim).await?,
+    }
+    Ok(())
+  }
+  .await;
+
+  // a key that is never completed stays in progress until it expires
+  if let Err(e) = recorded {
+    utils::log(utils::Event {
+      msg: e.to_string(),
+      source: Some("idempotency key".to_owned()),
+      severity: utils::SeverityKind::Error,
+    });
+  }
+
+  result
+}
+
+// deletes the keys older than the retention period
+pub async fn run_prune(db: Db) {
+  let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+  loop {
+    ticker.tick().await;
+
+    let result: Result<_, PoolError> = async {
+      let con = &mut **db.get().await?;
+      let expired_before = utils::current_time_millis() - RETENTION;
+      Ok(idempotency_key_service::delete_before(con, expired_before).await?)
+    }
+    .await;
+
+    if let Err(e) = result {
+      utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("idempotency key pruning".to_owned()),
+        severity: utils::SeverityKind::Error,
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn request_hash_ignores_the_api_key() {
+    let a = json!({"apiKey": "old", "name": "x"});
+    let b = json!({"apiKey": "refreshed", "name": "x"});
+    let c = json!({"apiKey": "old", "name": "y"});
+    assert_eq!(
+      request_hash("/public/goal/new", &a),
+      request_hash("/public/goal/new", &b)
+    );
+    assert_ne!(
+      request_hash("/public/goal/new", &a),
+      request_hash("/public/goal/new", &c)
+    );
+    assert_ne!(
+      request_hash("/public/goal/new", &a),
+      request_hash("/public/named_entity/new", &a)
+    );
+  }
+}
@@ -0,0 +1,124 @@
+use super::db_types::*;
+use super::utils::current_time_millis;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for IdempotencyKey {
+  // select * from idempotency_key order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> IdempotencyKey {
+    IdempotencyKey {
+      idempotency_key_id: row.get("idempotency_key_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      idempotency_key: row.get("idempotency_key"),
+      request_hash: row.get("request_hash"),
+      response: row.get("response"),
+    }
+  }
+}
+
+// Claims the key for a request that is about to be handled, and returns the id of the claim,
+// or None if the key is already taken.
+// A key that was used before `expired_before` is free again.
+pub async fn claim(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  idempotency_key: &str,
+  request_hash: &str,
+  expired_before: i64,
+) -> Result<Option<i64>, tokio_postgres::Error> {
+  con
+    .execute(
+      "DELETE FROM idempotency_key
+       WHERE creator_user_id = $1
+       AND idempotency_key = $2
+       AND creation_time < $3
+      ",
+      &[&creator_user_id, &idempotency_key, &expired_before],
+    )
+    .await?;
+
+  let creation_time = current_time_millis();
+
+  let idempotency_key_id = con
+    .query_opt(
+      "INSERT INTO
+       idempotency_key(
+           creation_time,
+           creator_user_id,
+           idempotency_key,
+           request_hash
+       )
+       VALUES($1, $2, $3, $4)
+       ON CONFLICT DO NOTHING
+       RETURNING idempotency_key_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &idempotency_key,
+        &request_hash,
+      ],
+    )
+    .await?
+    .map(|x| x.get(0));
+
+  Ok(idempotency_key_id)
+}
+
+pub async fn get_by_idempotency_key(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  idempotency_key: &str,
+) -> Result<Option<IdempotencyKey>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM idempotency_key WHERE creator_user_id=$1 AND idempotency_key=$2",
+      &[&creator_user_id, &idempotency_key],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// records the response to the claimed request
+pub async fn complete(
+  con: &mut impl GenericClient,
+  idempotency_key_id: i64,
+  response: &str,
+) -> Result<(), tokio_postgres::Error> {
+  con
+    .execute(
+      "UPDATE idempotency_key SET response = $1 WHERE idempotency_key_id = $2",
+      &[&response, &idempotency_key_id],
+    )
+    .await?;
+  Ok(())
+}
+
+// gives up the claim, so that the request can be retried with the same key
+pub async fn release(
+  con: &mut impl GenericClient,
+  idempotency_key_id: i64,
+) -> Result<(), tokio_postgres::Error> {
+  con
+    .execute(
+      "DELETE FROM idempotency_key WHERE idempotency_key_id = $1",
+      &[&idempotency_key_id],
+    )
+    .await?;
+  Ok(())
+}
+
+// deletes the keys used before creation_time, returns how many were deleted
+pub async fn delete_before(
+  con: &mut impl GenericClient,
+  creation_time: i64,
+) -> Result<u64, tokio_postgres::Error> {
+  con
+    .execute(
+      "DELETE FROM idempotency_key WHERE creation_time < $1",
+      &[&creation_time],
+    )
+    .await
+}
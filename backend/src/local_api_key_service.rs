@@ -0,0 +1,76 @@
+use super::db_types::*;
+use super::utils::current_time_millis;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for LocalApiKey {
+  // select * from local_api_key order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> LocalApiKey {
+    LocalApiKey {
+      local_api_key_id: row.get("local_api_key_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      key_hash: row.get("key_hash"),
+      duration: row.get("duration"),
+      active: row.get("active"),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  key_hash: String,
+  duration: i64,
+  active: bool,
+) -> Result<LocalApiKey, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let local_api_key_id = con
+    .query_one(
+      "INSERT INTO
+       local_api_key(
+           creation_time,
+           creator_user_id,
+           key_hash,
+           duration,
+           active
+       )
+       VALUES($1, $2, $3, $4, $5)
+       RETURNING local_api_key_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &key_hash,
+        &duration,
+        &active,
+      ],
+    )
+    .await?
+    .get(0);
+
+  // return local_api_key
+  Ok(LocalApiKey {
+    local_api_key_id,
+    creation_time,
+    creator_user_id,
+    key_hash,
+    duration,
+    active,
+  })
+}
+
+pub async fn get_recent_by_key_hash(
+  con: &mut impl GenericClient,
+  key_hash: &str,
+) -> Result<Option<LocalApiKey>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_local_api_key WHERE key_hash=$1",
+      &[&key_hash],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
@@ -0,0 +1,178 @@
+// Lets the backend run without the auth service, for self-hosting with nothing but Postgres.
+// Users sign up with an email and password, and log in to get api keys, all through our own endpoints
+// under public/local_auth. Both are kept in our own database: passwords hashed with argon2,
+// and api keys hashed with sha256 like the other secrets, as they are random and need to be looked up.
+// A user's local_user_id is used as their user id everywhere else.
+//
+// Logging in takes the same time whether or not the email is registered, so it can't be used to find out,
+// and after a few failed logins an email is locked for a while, registered or not.
+
+use super::auth::{AuthProvider, ValidApiKey};
+use super::local_api_key_service;
+use super::local_user_service;
+use super::utils;
+use super::Db;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_trait::async_trait;
+use auth_service_api::response::{AuthError, Info, User};
+use moka::future::Cache;
+use rand::RngCore;
+use std::time::Duration;
+
+// the shortest password we accept
+pub static MIN_PASSWORD_LEN: usize = 8;
+
+// checked against when the email isn't registered, so that it takes as long as a wrong password.
+// made with the default parameters, like every other hash
+pub static DUMMY_PASSWORD_HASH: &str =
+  "$argon2id$v=19$m=19456,t=2,p=1$4+ocvRNzGUXObg2fE3JXWA$XGp/X3ns9xJ4K227NodcD1N1SVbo++L0m0L/S5K5iE0";
+
+// how many logins may fail before the email is locked
+static MAX_FAILED_LOGINS: u32 = 10;
+// the email is locked until this long after the last failed login
+static LOGIN_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+// the most emails whose failed logins are remembered
+static LOGIN_ATTEMPTS_CAPACITY: u64 = 100_000;
+
+// counts the recent failed logins of each email, shared by the handlers through Config.
+// kept in memory, so each backend instance counts on its own
+#[derive(Clone)]
+pub struct LoginAttempts(Cache<String, u32>);
+
+impl LoginAttempts {
+  pub fn new() -> LoginAttempts {
+    LoginAttempts(
+      Cache::builder()
+        .max_capacity(LOGIN_ATTEMPTS_CAPACITY)
+        .time_to_live(LOGIN_LOCKOUT)
+        .build(),
+    )
+  }
+
+  pub async fn locked(&self, email: &str) -> bool {
+    self.0.get(email).await.unwrap_or(0) >= MAX_FAILED_LOGINS
+  }
+
+  pub async fn failed(&self, email: &str) {
+    let failures = self.0.get(email).await.unwrap_or(0);
+    self.0.insert(email.to_owned(), failures + 1).await;
+  }
+
+  pub async fn succeeded(&self, email: &str) {
+    self.0.invalidate(email).await;
+  }
+}
+
+pub struct LocalAuth {
+  pub db: Db,
+  pub app_pub_origin: String,
+}
+
+fn report_err(e: impl ToString) -> AuthError {
+  utils::log(utils::Event {
+    msg: e.to_string(),
+    source: Some("local auth".to_owned()),
+    severity: utils::SeverityKind::Error,
+  });
+  AuthError::InternalServerError
+}
+
+#[async_trait]
+impl AuthProvider for LocalAuth {
+  async fn validate_api_key(&self, api_key: String) -> Result<ValidApiKey, AuthError> {
+    let con = &mut **self.db.get().await.map_err(report_err)?;
+
+    let key_hash = utils::hash_token(&api_key);
+    let api_key = local_api_key_service::get_recent_by_key_hash(con, &key_hash)
+      .await
+      .map_err(report_err)?
+      .ok_or(AuthError::ApiKeyNonexistent)?;
+
+    let expiry = api_key.creation_time + api_key.duration;
+    if !api_key.active || expiry <= utils::current_time_millis() {
+      return Err(AuthError::ApiKeyUnauthorized);
+    }
+
+    let user = local_user_service::get_by_local_user_id(con, api_key.creator_user_id)
+      .await
+      .map_err(report_err)?
+      .ok_or(AuthError::ApiKeyNonexistent)?;
+
+    Ok(ValidApiKey {
+      user: User {
+        user_id: user.local_user_id,
+        creation_time: user.creation_time,
+      },
+      expiry: Some(expiry),
+    })
+  }
+
+  // the frontend reaches our public api on its own origin
+  async fn info(&self) -> Result<Info, AuthError> {
+    Ok(Info {
+      service: format!("{} local auth", crate::SERVICE_NAME),
+      version_major: 0,
+      version_minor: 0,
+      version_rev: 1,
+      app_pub_api_href: format!("{}/public/local_auth/", self.app_pub_origin),
+      app_authenticator_href: self.app_pub_origin.clone(),
+    })
+  }
+}
+
+// hashing is slow on purpose, so it is kept off the async threads
+pub async fn hash_password(password: String) -> Result<String, String> {
+  tokio::task::spawn_blocking(move || {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).map_err(|e| e.to_string())?;
+    Argon2::default()
+      .hash_password(password.as_bytes(), &salt)
+      .map(|x| x.to_string())
+      .map_err(|e| e.to_string())
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+pub async fn verify_password(password: String, password_hash: String) -> Result<bool, String> {
+  tokio::task::spawn_blocking(move || {
+    let password_hash = PasswordHash::new(&password_hash).map_err(|e| e.to_string())?;
+    Ok(
+      Argon2::default()
+        .verify_password(password.as_bytes(), &password_hash)
+        .is_ok(),
+    )
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn dummy_hash_is_checked_like_a_real_one() {
+    assert_eq!(
+      verify_password("hunter22".to_owned(), DUMMY_PASSWORD_HASH.to_owned()).await,
+      Ok(false)
+    );
+    // same parameters as new hashes, so it takes as long
+    let hash = hash_password("hunter22".to_owned()).await.unwrap();
+    let params = |x: &str| x.split('$').take(4).collect::<Vec<_>>().join("$");
+    assert_eq!(params(&hash), params(DUMMY_PASSWORD_HASH));
+  }
+
+  #[tokio::test]
+  async fn locks_emails_after_failed_logins() {
+    let attempts = LoginAttempts::new();
+    for _ in 0..MAX_FAILED_LOGINS {
+      assert!(!attempts.locked("a@example.com").await);
+      attempts.failed("a@example.com").await;
+    }
+    assert!(attempts.locked("a@example.com").await);
+    assert!(!attempts.locked("b@example.com").await);
+  }
+}
@@ -0,0 +1,76 @@
+use super::db_types::*;
+use super::utils::current_time_millis;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for LocalUser {
+  // select * from local_user order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> LocalUser {
+    LocalUser {
+      local_user_id: row.get("local_user_id"),
+      creation_time: row.get("creation_time"),
+      email: row.get("email"),
+      password_hash: row.get("password_hash"),
+    }
+  }
+}
+
+// returns None if there already is a user with the email
+pub async fn add(
+  con: &mut impl GenericClient,
+  email: String,
+  password_hash: String,
+) -> Result<Option<LocalUser>, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let local_user_id = con
+    .query_opt(
+      "INSERT INTO
+       local_user(
+           creation_time,
+           email,
+           password_hash
+       )
+       VALUES($1, $2, $3)
+       ON CONFLICT (email) DO NOTHING
+       RETURNING local_user_id
+      ",
+      &[&creation_time, &email, &password_hash],
+    )
+    .await?
+    .map(|x| x.get(0));
+
+  // return local_user
+  Ok(local_user_id.map(|local_user_id| LocalUser {
+    local_user_id,
+    creation_time,
+    email,
+    password_hash,
+  }))
+}
+
+pub async fn get_by_local_user_id(
+  con: &mut impl GenericClient,
+  local_user_id: i64,
+) -> Result<Option<LocalUser>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM local_user WHERE local_user_id=$1",
+      &[&local_user_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+pub async fn get_by_email(
+  con: &mut impl GenericClient,
+  email: &str,
+) -> Result<Option<LocalUser>, tokio_postgres::Error> {
+  let result = con
+    .query_opt("SELECT * FROM local_user WHERE email=$1", &[&email])
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
@@ -0,0 +1,334 @@
+// Sends mail through the mail service: reminders, and a daily agenda digest for the users that want one.
+// Both go to the address in the user's mail setting.
+// A digest is recorded before it is sent, so each day's digest goes out at most once.
+// If sending it fails, the error is logged and that day's digest is skipped.
+
+use super::db_types::*;
+use super::external_event_data_service;
+use super::goal_data_service;
+use super::goal_event_service;
+use super::handlers;
+use super::mail_digest_service;
+use super::mail_setting_service;
+use super::reminder_channel::{Channel, ReminderMessage};
+use super::time_utility_function_service;
+use super::utils;
+use super::Db;
+use async_trait::async_trait;
+use chrono::{NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use deadpool_postgres::PoolError;
+use mail_service_api::client::MailService;
+use mail_service_api::request::MailNewProps;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use todo_app_service_api::request;
+
+// how many unscheduled goals the digest suggests
+static DIGEST_SUGGESTIONS: usize = 5;
+
+fn escape_html(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn parse_tz(tz: &str) -> Tz {
+  tz.parse().unwrap_or(Tz::UTC)
+}
+
+fn format_time(tz: Tz, time: i64) -> String {
+  match tz.timestamp_millis_opt(time).single() {
+    Some(x) => x.format("%a %b %-d, %H:%M").to_string(),
+    None => time.to_string(),
+  }
+}
+
+fn format_clock(tz: Tz, time: i64) -> String {
+  match tz.timestamp_millis_opt(time).single() {
+    Some(x) => x.format("%H:%M").to_string(),
+    None => time.to_string(),
+  }
+}
+
+// utility is linear between the points of the function, and flat before the first and after the last
+fn utility_at(time_utility_function: &TimeUtilityFunction, time: i64) -> i64 {
+  let points: Vec<(i64, i64)> = time_utility_function
+    .start_times
+    .iter()
+    .copied()
+    .zip(time_utility_function.utils.iter().copied())
+    .collect();
+
+  match points.iter().position(|&(t, _)| t > time) {
+    Some(0) => points[0].1,
+    Some(i) => {
+      let ((t0, u0), (t1, u1)) = (points[i - 1], points[i]);
+      u0 + ((u1 - u0) as i128 * (time - t0) as i128 / (t1 - t0) as i128) as i64
+    }
+    None => points.last().map(|&(_, u)| u).unwrap_or(0),
+  }
+}
+
+pub struct MailChannel {
+  pub mail_service: MailService,
+}
+
+#[async_trait]
+impl Channel for MailChannel {
+  fn kind(&self) -> request::ReminderChannelKind {
+    request::ReminderChannelKind::Mail
+  }
+
+  async fn send(&self, db: &Db, message: &ReminderMessage) -> Result<(), String> {
+    let reminder = &message.reminder;
+
+    let mail_setting = {
+      let con = &mut **db.get().await.map_err(|e| e.to_string())?;
+      mail_setting_service::get_recent_by_creator_user_id(con, reminder.creator_user_id)
+        .await
+        .map_err(|e| e.to_string())?
+    };
+    let mail_setting = mail_setting.ok_or_else(|| "no mail address set".to_owned())?;
+    let tz = parse_tz(&mail_setting.tz);
+
+    let name = escape_html(&message.goal_data.name);
+    let (title, content) = match &message.goal_event {
+      Some(goal_event) => (
+        format!(
+          "Reminder: {} starts at {}",
+          message.goal_data.name,
+          format_time(tz, goal_event.start_time)
+        ),
+        format!(
+          "<p><b>{}</b> is scheduled from {} to {}.</p>",
+          name,
+          format_time(tz, goal_event.start_time),
+          format_clock(tz, goal_event.end_time)
+        ),
+      ),
+      None => (
+        format!(
+          "Reminder: {} is due at {}",
+          message.goal_data.name,
+          format_time(tz, reminder.due_time)
+        ),
+        format!(
+          "<p><b>{}</b> is worth less after {}.</p>",
+          name,
+          format_time(tz, reminder.due_time)
+        ),
+      ),
+    };
+
+    self
+      .mail_service
+      .mail_new(MailNewProps {
+        request_id: reminder.reminder_id,
+        destination: mail_setting.address,
+        topic: "reminder".to_owned(),
+        title,
+        content,
+      })
+      .await
+      .map_err(|e| e.to_string())?;
+
+    Ok(())
+  }
+}
+
+pub async fn run_digest(db: Db, mail_service: MailService, interval: Duration) {
+  let mut ticker = tokio::time::interval(interval);
+  loop {
+    ticker.tick().await;
+
+    let now = utils::current_time_millis();
+
+    if let Err(e) = send_digests(&db, &mail_service, now).await {
+      utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("mail digest".to_owned()),
+        severity: utils::SeverityKind::Error,
+      });
+    }
+  }
+}
+
+async fn send_digests(db: &Db, mail_service: &MailService, now: i64) -> Result<(), PoolError> {
+  let mail_settings = {
+    let con = &mut **db.get().await?;
+    mail_setting_service::get_recent_with_digest(con).await?
+  };
+
+  for mail_setting in mail_settings {
+    let tz = parse_tz(&mail_setting.tz);
+    let today = match handlers::local_date(tz, now) {
+      Some(today) => today,
+      None => continue,
+    };
+    // not time for today's digest yet
+    if now < handlers::local_day_start(tz, today) + mail_setting.digest_time.unwrap_or(0) {
+      continue;
+    }
+
+    let digest = {
+      let con = &mut **db.get().await?;
+      match mail_digest_service::add_if_absent(con, mail_setting.creator_user_id, today.to_string())
+        .await?
+      {
+        Some(mail_digest_id) => Some((
+          mail_digest_id,
+          agenda(con, mail_setting.creator_user_id, tz, today, now).await?,
+        )),
+        None => None,
+      }
+    };
+
+    if let Some((mail_digest_id, content)) = digest {
+      let result = mail_service
+        .mail_new(MailNewProps {
+          request_id: mail_digest_id,
+          destination: mail_setting.address.clone(),
+          topic: "agenda_digest".to_owned(),
+          title: format!("Your agenda for {}", today.format("%A, %B %-d")),
+          content,
+        })
+        .await;
+
+      if let Err(e) = result {
+        utils::log(utils::Event {
+          msg: e.to_string(),
+          source: Some(format!("mail digest: {}", mail_setting.creator_user_id)),
+          severity: utils::SeverityKind::Error,
+        });
+      }
+    }
+  }
+
+  Ok(())
+}
+
+// today's events, overdue goals, and the most useful goals that aren't scheduled yet, as html
+async fn agenda(
+  con: &mut impl tokio_postgres::GenericClient,
+  user_id: i64,
+  tz: Tz,
+  today: NaiveDate,
+  now: i64,
+) -> Result<String, tokio_postgres::Error> {
+  let day_start = handlers::local_day_start(tz, today);
+  let day_end = today
+    .succ_opt()
+    .map(|x| handlers::local_day_start(tz, x))
+    .unwrap_or(day_start + 24 * 60 * 60 * 1000);
+
+  let goal_events = goal_event_service::get_recent_active_with_name(con, user_id).await?;
+  let scheduled: HashSet<i64> = goal_events.iter().map(|(x, _)| x.goal_id).collect();
+
+  // (start_time, end_time, name) of everything happening today
+  let mut events: Vec<(i64, i64, String)> = goal_events
+    .into_iter()
+    .filter(|(x, _)| x.start_time < day_end && x.end_time > day_start)
+    .map(|(x, name)| (x.start_time, x.end_time, name))
+    .collect();
+  events.extend(
+    external_event_data_service::get_recent_active(con, user_id)
+      .await?
+      .into_iter()
+      .filter(|x| x.start_time < day_end && x.end_time > day_start)
+      .map(|x| (x.start_time, x.end_time, x.name)),
+  );
+  events.sort();
+
+  let goal_data: Vec<GoalData> = goal_data_service::get_recent(con, user_id)
+    .await?
+    .into_iter()
+    .filter(|x| x.status.clone() as i64 == request::GoalDataStatusKind::Pending as i64)
+    .collect();
+
+  let time_utility_function_ids: Vec<i64> = goal_data
+    .iter()
+    .map(|x| x.time_utility_function_id)
+    .collect();
+
+  let time_utility_functions: HashMap<i64, TimeUtilityFunction> =
+    time_utility_function_service::get_by_time_utility_function_ids(
+      con,
+      &time_utility_function_ids,
+    )
+    .await?
+    .into_iter()
+    .map(|x| (x.time_utility_function_id, x))
+    .collect();
+
+  // (deadline, name) of goals past their deadline
+  let mut overdue = vec![];
+  // (utility, name) of goals that could be scheduled
+  let mut unscheduled = vec![];
+  for goal_data in goal_data {
+    let time_utility_function =
+      match time_utility_functions.get(&goal_data.time_utility_function_id) {
+        Some(x) => x,
+        None => continue,
+      };
+    let (_, _, max_time) = handlers::utility_window(time_utility_function);
+    match max_time {
+      Some(max_time) if max_time < now => overdue.push((max_time, goal_data.name)),
+      _ if !scheduled.contains(&goal_data.goal_id) => {
+        let utility = utility_at(time_utility_function, now);
+        if utility > 0 {
+          unscheduled.push((utility, goal_data.name));
+        }
+      }
+      _ => {}
+    }
+  }
+  overdue.sort();
+  unscheduled.sort_by_key(|x| std::cmp::Reverse(x.0));
+  unscheduled.truncate(DIGEST_SUGGESTIONS);
+
+  let mut out = String::new();
+
+  out.push_str("<h3>Today</h3>\n");
+  if events.is_empty() {
+    out.push_str("<p>Nothing scheduled.</p>\n");
+  } else {
+    out.push_str("<ul>\n");
+    for (start_time, end_time, name) in events {
+      out.push_str(&format!(
+        "<li>{} - {}: {}</li>\n",
+        format_clock(tz, start_time),
+        format_clock(tz, end_time),
+        escape_html(&name)
+      ));
+    }
+    out.push_str("</ul>\n");
+  }
+
+  if !overdue.is_empty() {
+    out.push_str("<h3>Overdue</h3>\n<ul>\n");
+    for (max_time, name) in overdue {
+      out.push_str(&format!(
+        "<li>{} (due {})</li>\n",
+        escape_html(&name),
+        format_time(tz, max_time)
+      ));
+    }
+    out.push_str("</ul>\n");
+  }
+
+  if !unscheduled.is_empty() {
+    out.push_str("<h3>Not scheduled yet</h3>\n<ul>\n");
+    for (utility, name) in unscheduled {
+      out.push_str(&format!(
+        "<li>{} ({} utils)</li>\n",
+        escape_html(&name),
+        utility
+      ));
+    }
+    out.push_str("</ul>\n");
+  }
+
+  Ok(out)
+}
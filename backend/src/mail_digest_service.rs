@@ -0,0 +1,30 @@
+use super::utils::current_time_millis;
+use tokio_postgres::GenericClient;
+
+// records that the day's digest is being sent, and returns its id, or None if it already was
+pub async fn add_if_absent(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  digest_date: String,
+) -> Result<Option<i64>, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let mail_digest_id = con
+    .query_opt(
+      "INSERT INTO
+       mail_digest(
+           creation_time,
+           creator_user_id,
+           digest_date
+       )
+       VALUES($1, $2, $3)
+       ON CONFLICT DO NOTHING
+       RETURNING mail_digest_id
+      ",
+      &[&creation_time, &creator_user_id, &digest_date],
+    )
+    .await?
+    .map(|x| x.get(0));
+
+  Ok(mail_digest_id)
+}
@@ -0,0 +1,146 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for MailSetting {
+  // select * from mail_setting order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> MailSetting {
+    MailSetting {
+      mail_setting_id: row.get("mail_setting_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      address: row.get("address"),
+      digest_time: row.get("digest_time"),
+      tz: row.get("tz"),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  address: String,
+  digest_time: Option<i64>,
+  tz: String,
+) -> Result<MailSetting, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let mail_setting_id = con
+    .query_one(
+      "INSERT INTO
+       mail_setting(
+           creation_time,
+           creator_user_id,
+           address,
+           digest_time,
+           tz
+       )
+       VALUES($1, $2, $3, $4, $5)
+       RETURNING mail_setting_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &address,
+        &digest_time,
+        &tz,
+      ],
+    )
+    .await?
+    .get(0);
+
+  // return mail_setting
+  Ok(MailSetting {
+    mail_setting_id,
+    creation_time,
+    creator_user_id,
+    address,
+    digest_time,
+    tz,
+  })
+}
+
+pub async fn get_recent_by_creator_user_id(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+) -> Result<Option<MailSetting>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_mail_setting WHERE creator_user_id=$1",
+      &[&creator_user_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// the current setting of every user that wants a digest
+pub async fn get_recent_with_digest(
+  con: &mut impl GenericClient,
+) -> Result<Vec<MailSetting>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT * FROM recent_mail_setting WHERE digest_time IS NOT NULL",
+      &[],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("ms.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::MailSettingViewProps,
+) -> Result<Page<MailSetting>, tokio_postgres::Error> {
+  let sql = [
+    if props.only_recent {
+      "SELECT ms.* FROM recent_mail_setting ms"
+    } else {
+      "SELECT ms.* FROM mail_setting ms"
+    },
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR ms.mail_setting_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR ms.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR ms.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR ms.creator_user_id = ANY($4))",
+    " AND ms.creator_user_id = $5",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "ms", "mail_setting_id", 6);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.mail_setting_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}
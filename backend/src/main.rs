@@ -1,16 +1,21 @@
 use clap::Parser;
 use std::error::Error;
 use std::sync::Arc;
-use tokio_postgres::{Client, NoTls};
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
+use tokio_postgres::NoTls;
 use warp::Filter;
 
-use tokio::sync::Mutex;
-
 mod utils;
 
 use auth_service_api::client::AuthService;
+use mail_service_api::client::MailService;
 
 // db web stuff
+mod account_service;
+mod caldav_subscription_service;
+mod caldav_sync_service;
+mod calendar_feed_service;
+mod change_event_service;
 mod external_event_data_service;
 mod external_event_service;
 mod goal_data_service;
@@ -21,15 +26,44 @@ mod goal_entity_tag_service;
 mod goal_template_data_service;
 mod goal_template_pattern_service;
 mod goal_template_service;
+mod idempotency_key_service;
+mod local_api_key_service;
+mod local_user_service;
+mod mail_digest_service;
+mod mail_setting_service;
 mod named_entity_data_service;
 mod named_entity_pattern_service;
 mod named_entity_service;
+mod reminder_delivery_service;
+mod reminder_service;
+mod reminder_setting_service;
+mod sync_service;
 mod time_utility_function_service;
 mod user_generated_code_service;
+mod webhook_delivery_service;
+mod webhook_event_service;
+mod webhook_service;
 
 mod api;
+mod auth;
+mod caldav;
+mod caldav_client;
+mod change_stream;
+mod csv;
 mod db_types;
 mod handlers;
+mod ical;
+mod idempotency;
+mod local_auth;
+mod mail_client;
+mod migrate;
+mod outline;
+mod page;
+mod reminder_channel;
+mod reminder_scheduler;
+mod secret_box;
+mod todo_txt;
+mod webhook_client;
 
 static SERVICE_NAME: &str = "todo-app-service";
 
@@ -39,31 +73,107 @@ struct Opts {
   app_pub_origin: String,
   #[clap(long)]
   database_url: String,
+  #[clap(long, default_value_t = 16)]
+  database_pool_size: usize,
+  #[clap(long, default_value_t = 30)]
+  database_timeout_secs: u64,
+  #[clap(long)]
+  auth_service_url: Option<String>,
   #[clap(long)]
-  auth_service_url: String,
+  local_auth_signup: bool,
+  #[clap(long, default_value_t = 10000)]
+  auth_cache_size: u64,
+  #[clap(long, default_value_t = 60)]
+  auth_cache_ttl_secs: u64,
+  #[clap(long, default_value_t = 10)]
+  auth_cache_negative_ttl_secs: u64,
+  #[clap(long, default_value_t = 300)]
+  auth_cache_stale_secs: u64,
   #[clap(long)]
   port: u16,
+  #[clap(long)]
+  mail_service_url: Option<String>,
+  #[clap(long, default_value_t = 300)]
+  caldav_sync_interval_secs: u64,
+  #[clap(long)]
+  caldav_allow_private_addresses: bool,
+  // also read from the environment, so that it needn't show up in the process list
+  #[clap(long, env = "TODO_APP_SECRET_KEY", hide_env_values = true)]
+  secret_key: Option<String>,
+  #[clap(long, default_value_t = 5)]
+  webhook_interval_secs: u64,
+  #[clap(long, default_value_t = 60)]
+  reminder_interval_secs: u64,
 }
 
 #[derive(Clone)]
 pub struct Config {
   pub app_pub_origin: String,
+  // users and api keys are kept in our own database instead of the auth service
+  pub local_auth: bool,
+  // whether anyone may sign up as a local user
+  pub local_auth_signup: bool,
+  // seals secrets we need to read back, without it there are no caldav subscriptions
+  pub secret_box: Option<secret_box::SecretBox>,
+  // lets caldav subscriptions and webhooks reach loopback, private and link local addresses
+  pub caldav_allow_private_addresses: bool,
+  // failed local logins, to slow down password guessing
+  pub login_attempts: local_auth::LoginAttempts,
 }
 
-pub type Db = Arc<Mutex<Client>>;
+// handlers and workers check out a connection for as long as they need one
+pub type Db = Pool;
 
 #[tokio::main]
 async fn main() {
   let Opts {
     database_url,
+    database_pool_size,
+    database_timeout_secs,
     app_pub_origin,
     auth_service_url,
+    local_auth_signup,
+    auth_cache_size,
+    auth_cache_ttl_secs,
+    auth_cache_negative_ttl_secs,
+    auth_cache_stale_secs,
     port,
+    mail_service_url,
+    caldav_sync_interval_secs,
+    caldav_allow_private_addresses,
+    secret_key,
+    webhook_interval_secs,
+    reminder_interval_secs,
   } = Opts::parse();
 
-  let (client, connection) = loop {
-    match tokio_postgres::connect(&database_url, NoTls).await {
-      Ok(v) => break v,
+  let database_config: tokio_postgres::Config =
+    database_url.parse().expect("invalid database url");
+
+  // connections are checked with a query before being handed out again,
+  // broken ones are thrown away and replaced by new connections
+  let manager = Manager::from_config(
+    database_config.clone(),
+    NoTls,
+    ManagerConfig {
+      recycling_method: RecyclingMethod::Verified,
+    },
+  );
+
+  let timeout = Some(std::time::Duration::from_secs(database_timeout_secs));
+
+  let db: Db = Pool::builder(manager)
+    .max_size(database_pool_size)
+    .wait_timeout(timeout)
+    .create_timeout(timeout)
+    .recycle_timeout(timeout)
+    .runtime(Runtime::Tokio1)
+    .build()
+    .unwrap();
+
+  // wait for the database to come up
+  loop {
+    match db.get().await {
+      Ok(_) => break,
       Err(e) => utils::log(utils::Event {
         msg: e.to_string(),
         source: e.source().map(|x| x.to_string()),
@@ -72,21 +182,89 @@ async fn main() {
     }
 
     // sleep for 5 seconds
-    std::thread::sleep(std::time::Duration::from_secs(5));
-  };
+    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+  }
 
-  // The connection object performs the actual communication with the database,
-  // so spawn it off to run on its own.
-  tokio::spawn(async move {
-    if let Err(e) = connection.await {
-      eprintln!("connection error: {}", e);
-    }
-  });
+  // nothing can work with an outdated schema
+  if let Err(e) = migrate::run(&db).await {
+    utils::log(utils::Event {
+      msg: e.to_string(),
+      source: Some("migrate".to_owned()),
+      severity: utils::SeverityKind::Fatal,
+    });
+    std::process::exit(1);
+  }
 
-  let db: Db = Arc::new(Mutex::new(client));
+  let secret_box = secret_key.map(|x| secret_box::SecretBox::new(&x).expect("invalid secret key"));
 
-  // open connection to auth service
-  let auth_service = AuthService::new(&auth_service_url);
+  // pull in subscribed calendars in the background
+  if let Some(secret_box) = secret_box.clone() {
+    tokio::spawn(caldav_client::run(
+      db.clone(),
+      std::time::Duration::from_secs(caldav_sync_interval_secs),
+      secret_box,
+      caldav_allow_private_addresses,
+    ));
+  }
+
+  // send queued webhook events in the background
+  tokio::spawn(webhook_client::run(
+    db.clone(),
+    std::time::Duration::from_secs(webhook_interval_secs),
+    caldav_allow_private_addresses,
+  ));
+
+  let mut reminder_channels: Vec<Box<dyn reminder_channel::Channel>> =
+    vec![Box::new(reminder_channel::WebhookChannel)];
+
+  // mail is only sent if there is a mail service to send it through
+  if let Some(mail_service_url) = mail_service_url {
+    let mail_service = MailService::new(&mail_service_url);
+    reminder_channels.push(Box::new(mail_client::MailChannel {
+      mail_service: mail_service.clone(),
+    }));
+    // send daily digests in the background
+    tokio::spawn(mail_client::run_digest(
+      db.clone(),
+      mail_service,
+      std::time::Duration::from_secs(reminder_interval_secs),
+    ));
+  }
+
+  // queue and send reminders in the background
+  tokio::spawn(reminder_scheduler::run(
+    db.clone(),
+    std::time::Duration::from_secs(reminder_interval_secs),
+    reminder_channels,
+  ));
+
+  // pass the changes announced by the database on to the open change streams
+  let changes = change_stream::channel();
+  tokio::spawn(change_stream::run(database_config, changes.clone()));
+  tokio::spawn(change_stream::run_prune(db.clone()));
+
+  // forget old idempotency keys in the background
+  tokio::spawn(idempotency::run_prune(db.clone()));
+
+  // open connection to auth service, without one users and api keys are kept in our own database
+  let local_auth = auth_service_url.is_none();
+  let auth_provider: Arc<dyn auth::AuthProvider> = match auth_service_url {
+    Some(auth_service_url) => Arc::new(AuthService::new(&auth_service_url)),
+    None => Arc::new(local_auth::LocalAuth {
+      db: db.clone(),
+      app_pub_origin: app_pub_origin.clone(),
+    }),
+  };
+
+  // validated api keys are cached in front of it
+  let auth = auth::Auth::new(
+    auth_provider,
+    auth_cache_size,
+    std::time::Duration::from_secs(auth_cache_ttl_secs),
+    std::time::Duration::from_secs(auth_cache_negative_ttl_secs),
+    std::time::Duration::from_secs(auth_cache_stale_secs),
+  );
+  tokio::spawn(auth::run_metrics(auth.clone()));
 
   let log = warp::log::custom(|info| {
     // Use a log macro, or slog, or println, or whatever!
@@ -97,7 +275,16 @@ async fn main() {
     });
   });
 
-  let api = api::api(Config { app_pub_origin}, db, auth_service);
+  let config = Config {
+    app_pub_origin,
+    local_auth,
+    local_auth_signup,
+    secret_box,
+    caldav_allow_private_addresses,
+    login_attempts: local_auth::LoginAttempts::new(),
+  };
+
+  let api = api::api(config, db, auth, changes);
 
   warp::serve(api.with(log)).run(([0, 0, 0, 0], port)).await;
 }
@@ -0,0 +1,37 @@
+// Brings the database schema up to date on startup.
+// The migrations in sql/migrations are embedded in the binary. The ones that haven't been applied yet are run
+// in version order, each in its own transaction, and refinery_schema_history records which ones were applied.
+// To change the schema, add a new V<n>__<name>.sql file. Never edit one that has already been released.
+
+use super::utils;
+use super::Db;
+use std::error::Error;
+
+refinery::embed_migrations!("sql/migrations");
+
+// held while migrating, so that instances starting at the same time take turns.
+// the two key form doesn't collide with the per user locks
+static LOCK: (i32, i32) = (0, 0);
+
+pub async fn run(db: &Db) -> Result<(), Box<dyn Error + Send + Sync>> {
+  let mut con = db.get().await?;
+  let client: &mut tokio_postgres::Client = &mut con;
+
+  client
+    .execute("SELECT pg_advisory_lock($1, $2)", &[&LOCK.0, &LOCK.1])
+    .await?;
+  let report = migrations::runner().run_async(client).await;
+  client
+    .execute("SELECT pg_advisory_unlock($1, $2)", &[&LOCK.0, &LOCK.1])
+    .await?;
+
+  for migration in report?.applied_migrations() {
+    utils::log(utils::Event {
+      msg: format!("applied migration {}", migration),
+      source: Some("migrate".to_owned()),
+      severity: utils::SeverityKind::Info,
+    });
+  }
+
+  Ok(())
+}
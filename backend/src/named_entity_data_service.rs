@@ -1,4 +1,5 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use std::convert::From;
 use std::convert::TryInto;
@@ -82,10 +83,59 @@ pub async fn get_by_named_entity_data_id(
   Ok(result)
 }
 
+// names are unique per user, so this finds the entity a tag like `+project` refers to
+pub async fn get_recent_active_by_name(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  name: &str,
+) -> Result<Option<NamedEntityData>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT ned.* FROM recent_named_entity_data ned
+       WHERE ned.creator_user_id = $1
+       AND ned.name = $2
+       AND ned.active
+       ORDER BY ned.named_entity_data_id DESC
+       LIMIT 1
+      ",
+      &[&creator_user_id, &name],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+pub async fn get_recent_by_named_entity_id(
+  con: &mut impl GenericClient,
+  named_entity_id: i64,
+) -> Result<Option<NamedEntityData>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM recent_named_entity_data WHERE named_entity_id=$1",
+      &[&named_entity_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("ned.creation_time"),
+    request::ViewSortKind::Name => Some("ned.name"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::NamedEntityDataViewProps,
-) -> Result<Vec<NamedEntityData>, tokio_postgres::Error> {
+) -> Result<Page<NamedEntityData>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT ned.* FROM recent_named_entity_data ned"
@@ -101,13 +151,14 @@ pub async fn query(
     " AND ($6::text[]    IS NULL OR ned.name = ANY($6))",
     " AND ($7::bigint[]  IS NULL OR ned.kind = ANY($7))",
     " AND ($8::bool      IS NULL OR ned.active = $8)",
-    " ORDER BY ned.named_entity_data_id",
+    " AND ned.creator_user_id = $9",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "ned", "named_entity_data_id", 10);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -121,12 +172,13 @@ pub async fn query(
           .kind
           .map(|x| x.into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
         &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
@@ -1,6 +1,8 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use std::convert::From;
+use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for NamedEntityPattern {
@@ -75,10 +77,44 @@ pub async fn get_by_named_entity_pattern_id(
   Ok(result)
 }
 
+// returns all active patterns that occur somewhere in the given name (case insensitive)
+pub async fn get_matching(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  name: &str,
+) -> Result<Vec<NamedEntityPattern>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT nep.* FROM recent_named_entity_pattern nep
+       WHERE nep.creator_user_id = $1
+       AND nep.active
+       AND strpos(lower($2), lower(nep.pattern)) > 0
+       ORDER BY nep.named_entity_pattern_id
+      ",
+      &[&creator_user_id, &name],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("nep.creation_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: todo_app_service_api::request::NamedEntityPatternViewProps,
-) -> Result<Vec<NamedEntityPattern>, tokio_postgres::Error> {
+) -> Result<Page<NamedEntityPattern>, tokio_postgres::Error> {
   let sql = [
     if props.only_recent {
       "SELECT nep.* FROM recent_named_entity_pattern nep"
@@ -93,13 +129,14 @@ pub async fn query(
     " AND ($5::bigint[]  IS NULL OR nep.named_entity_id = ANY($5))",
     " AND ($6::text[]    IS NULL OR nep.pattern = ANY($6))",
     " AND ($7::bool      IS NULL OR nep.active = $7)",
-    " ORDER BY nep.named_entity_pattern_id",
+    " AND nep.creator_user_id = $8",
   ]
   .join("\n");
+  let sql = page.wrap(&sql, "nep", "named_entity_pattern_id", 9);
 
   let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -110,12 +147,13 @@ pub async fn query(
         &props.named_entity_id,
         &props.pattern,
         &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
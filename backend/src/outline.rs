@@ -0,0 +1,375 @@
+// Turns an Org-mode outline or a nested Markdown checklist into a tree of items.
+// Headings and list items are items, anything else (body text, drawers) is skipped,
+// apart from the SCHEDULED and DEADLINE timestamps Org puts on the line below a heading.
+
+use chrono::{NaiveDate, NaiveTime};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+  Org,
+  Markdown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+  Pending,
+  Done,
+  Cancel,
+}
+
+// an Org timestamp like `<2023-01-05 Thu 10:00-11:30>`, times are local
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+  pub date: NaiveDate,
+  pub start: Option<NaiveTime>,
+  pub end: Option<NaiveTime>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Item {
+  // index of the enclosing item, which always comes earlier
+  pub parent: Option<usize>,
+  pub name: String,
+  pub status: Status,
+  // the letter of an Org priority cookie like `[#A]`
+  pub priority: Option<char>,
+  pub tags: Vec<String>,
+  pub scheduled: Option<Timestamp>,
+  pub deadline: Option<Timestamp>,
+}
+
+static TODO_KEYWORDS: [(&str, Status); 6] = [
+  ("TODO", Status::Pending),
+  ("NEXT", Status::Pending),
+  ("WAITING", Status::Pending),
+  ("DONE", Status::Done),
+  ("CANCELED", Status::Cancel),
+  ("CANCELLED", Status::Cancel),
+];
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+  NaiveTime::parse_from_str(s, "%H:%M").ok()
+}
+
+fn parse_timestamp(s: &str) -> Option<Timestamp> {
+  let mut words = s.split_whitespace();
+  let date = NaiveDate::parse_from_str(words.next()?, "%Y-%m-%d").ok()?;
+  let mut start = None;
+  let mut end = None;
+  // the rest is a day name, a time or time range, and repeaters or warnings we don't use
+  for word in words {
+    if !word.starts_with(|c: char| c.is_ascii_digit()) {
+      continue;
+    }
+    match word.split_once('-') {
+      Some((a, b)) => {
+        start = parse_time(a);
+        end = parse_time(b);
+      }
+      None => start = parse_time(word),
+    }
+  }
+  Some(Timestamp { date, start, end })
+}
+
+// removes `KEYWORD: <...>` from the text and returns the timestamp
+fn take_timestamp(text: &mut String, keyword: &str) -> Option<Timestamp> {
+  let begin = text.find(keyword)?;
+  let rest = text[begin + keyword.len()..].trim_start();
+  let inner = rest.strip_prefix('<')?;
+  let close = inner.find('>')?;
+  let timestamp = parse_timestamp(&inner[..close]);
+  let end = text.len() - inner.len() + close + 1;
+  text.replace_range(begin..end, " ");
+  timestamp
+}
+
+// leading whitespace, with tabs counted as four columns
+fn indent_width(line: &str) -> usize {
+  line
+    .chars()
+    .take_while(|c| c.is_whitespace())
+    .map(|c| if c == '\t' { 4 } else { 1 })
+    .sum()
+}
+
+// `- `, `+ `, `* `, `1. ` and `1) ` all start list items
+fn strip_bullet(s: &str) -> Option<&str> {
+  if let Some(rest) = s
+    .strip_prefix("- ")
+    .or_else(|| s.strip_prefix("+ "))
+    .or_else(|| s.strip_prefix("* "))
+  {
+    return Some(rest);
+  }
+  let digits = s.find(|c: char| !c.is_ascii_digit())?;
+  if digits == 0 {
+    return None;
+  }
+  s[digits..]
+    .strip_prefix(". ")
+    .or_else(|| s[digits..].strip_prefix(") "))
+}
+
+// a heading's level, and the text after the marker
+fn strip_heading(line: &str, format: Format) -> Option<(usize, &str)> {
+  let marker = match format {
+    Format::Org => '*',
+    Format::Markdown => '#',
+  };
+  let level = line.chars().take_while(|&c| c == marker).count();
+  if level == 0 {
+    return None;
+  }
+  let rest = &line[level..];
+  match rest.strip_prefix(' ') {
+    Some(rest) => Some((level, rest)),
+    None if rest.is_empty() => Some((level, rest)),
+    None => None,
+  }
+}
+
+fn is_tag_word(word: &str) -> bool {
+  word.len() > 2
+    && word.starts_with(':')
+    && word.ends_with(':')
+    && word[1..word.len() - 1]
+      .split(':')
+      .all(|t| !t.is_empty() && !t.contains(char::is_whitespace))
+}
+
+// `[1/3]` and `[33%]` statistics cookies
+fn is_statistics_cookie(word: &str) -> bool {
+  word
+    .strip_prefix('[')
+    .and_then(|w| w.strip_suffix(']'))
+    .map(|w| {
+      !w.is_empty()
+        && w
+          .chars()
+          .all(|c| c.is_ascii_digit() || c == '/' || c == '%')
+    })
+    .unwrap_or(false)
+}
+
+fn new_item(text: &str, format: Format, heading: bool, status: Status) -> Item {
+  let mut text = text.to_owned();
+  let scheduled = take_timestamp(&mut text, "SCHEDULED:");
+  let deadline = take_timestamp(&mut text, "DEADLINE:");
+
+  let mut words: Vec<&str> = text.split_whitespace().collect();
+  let mut status = status;
+  let mut priority = None;
+  let mut tags = vec![];
+
+  if format == Format::Org && heading {
+    if let Some((_, s)) = words
+      .first()
+      .and_then(|w| TODO_KEYWORDS.iter().find(|(k, _)| k == w))
+    {
+      status = *s;
+      words.remove(0);
+    }
+    // org tags trail the heading, like `:work:urgent:`
+    if let Some(word) = words.last().copied().filter(|w| is_tag_word(w)) {
+      words.pop();
+      tags.extend(
+        word
+          .split(':')
+          .filter(|t| !t.is_empty())
+          .map(|t| t.to_owned()),
+      );
+    }
+  }
+
+  let mut name = vec![];
+  for word in words {
+    if let Some(p) = word
+      .strip_prefix("[#")
+      .and_then(|w| w.strip_suffix(']'))
+      .filter(|w| w.len() == 1 && w.chars().all(|c| c.is_ascii_uppercase()))
+    {
+      priority = p.chars().next();
+    } else if is_statistics_cookie(word) {
+      continue;
+    } else if let Some(tag) = word
+      .strip_prefix('#')
+      .filter(|t| format == Format::Markdown && !t.is_empty() && !t.starts_with('#'))
+    {
+      // markdown has no tags of its own, so we use the common `#tag` convention
+      tags.push(tag.to_owned());
+    } else {
+      name.push(word);
+    }
+  }
+
+  Item {
+    parent: None,
+    name: name.join(" "),
+    status,
+    priority,
+    tags,
+    scheduled,
+    deadline,
+  }
+}
+
+pub fn parse(source: &str, format: Format) -> Vec<Item> {
+  let mut items: Vec<Item> = vec![];
+  // the open items, along with how deep they are nested and whether they are headings
+  let mut stack: Vec<(usize, bool, usize)> = vec![];
+  // list items are nested beneath the heading they appear under
+  let mut heading_depth = 0;
+
+  for line in source.lines() {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+      continue;
+    }
+
+    let (depth, heading, item) = if let Some((level, text)) = strip_heading(line, format) {
+      heading_depth = level * 1000;
+      (
+        heading_depth,
+        true,
+        new_item(text, format, true, Status::Pending),
+      )
+    } else if let Some(text) = strip_bullet(trimmed) {
+      let (status, text) = match text.get(..4) {
+        Some("[ ] ") | Some("[-] ") => (Status::Pending, &text[4..]),
+        Some("[x] ") | Some("[X] ") => (Status::Done, &text[4..]),
+        _ => match text {
+          "[ ]" | "[-]" => (Status::Pending, ""),
+          "[x]" | "[X]" => (Status::Done, ""),
+          _ => (Status::Pending, text),
+        },
+      };
+      (
+        heading_depth + 1 + indent_width(line),
+        false,
+        new_item(text, format, false, status),
+      )
+    } else {
+      // a planning line belongs to the item above it
+      if let Some(item) = items.last_mut() {
+        let mut text = trimmed.to_owned();
+        if let Some(scheduled) = take_timestamp(&mut text, "SCHEDULED:") {
+          item.scheduled = Some(scheduled);
+        }
+        if let Some(deadline) = take_timestamp(&mut text, "DEADLINE:") {
+          item.deadline = Some(deadline);
+        }
+      }
+      continue;
+    };
+
+    // a heading closes every list above it, even ones under a shallower heading
+    while stack
+      .last()
+      .is_some_and(|&(d, h, _)| d >= depth || (heading && !h))
+    {
+      stack.pop();
+    }
+    let parent = stack.last().map(|&(_, _, i)| i);
+    stack.push((depth, heading, items.len()));
+    items.push(Item { parent, ..item });
+  }
+
+  items
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // (enclosing item, item) by name, which is how imports turn into goal dependencies
+  fn edges(items: &[Item]) -> Vec<(&str, &str)> {
+    items
+      .iter()
+      .filter_map(|x| x.parent.map(|p| (items[p].name.as_str(), x.name.as_str())))
+      .collect()
+  }
+
+  #[test]
+  fn nests_org_headings_and_lists() {
+    let items = parse(
+      "* TODO [#A] Move out :home:\n\
+       SCHEDULED: <2024-01-05 Fri 10:00-11:30>\n\
+       ** DONE Pack\n\
+       - [ ] books\n\
+       - [X] clothes\n\
+       \x20 - socks\n\
+       ** Hand back keys\n\
+       Some body text.\n\
+       * Unrelated\n",
+      Format::Org,
+    );
+    let names: Vec<&str> = items.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(
+      names,
+      [
+        "Move out",
+        "Pack",
+        "books",
+        "clothes",
+        "socks",
+        "Hand back keys",
+        "Unrelated"
+      ]
+    );
+    assert_eq!(
+      edges(&items),
+      [
+        ("Move out", "Pack"),
+        ("Pack", "books"),
+        ("Pack", "clothes"),
+        ("clothes", "socks"),
+        ("Move out", "Hand back keys"),
+      ]
+    );
+
+    assert_eq!(items[0].priority, Some('A'));
+    assert_eq!(items[0].tags, ["home"]);
+    assert_eq!(
+      items[0].scheduled,
+      Some(Timestamp {
+        date: NaiveDate::from_ymd_opt(2024, 1, 5).unwrap(),
+        start: parse_time("10:00"),
+        end: parse_time("11:30"),
+      })
+    );
+    assert_eq!(items[1].status, Status::Done);
+    assert_eq!(items[3].status, Status::Done);
+  }
+
+  #[test]
+  fn nests_markdown_checklists() {
+    let items = parse(
+      "# Trip #travel\n\
+       - [ ] Book flights\n\
+       \t- [x] Compare prices\n\
+       - [ ] Pack\n\
+       ## Later\n\
+       1. Write postcards\n",
+      Format::Markdown,
+    );
+    assert_eq!(items[0].tags, ["travel"]);
+    assert_eq!(
+      edges(&items),
+      [
+        ("Trip", "Book flights"),
+        ("Book flights", "Compare prices"),
+        ("Trip", "Pack"),
+        ("Trip", "Later"),
+        ("Later", "Write postcards"),
+      ]
+    );
+  }
+
+  #[test]
+  fn closes_lists_at_shallower_headings() {
+    // a list under a deep heading doesn't swallow the next, shallower heading
+    let items = parse("## Deep\n- item\n# Top\n- other\n", Format::Markdown);
+    assert_eq!(edges(&items), [("Deep", "item"), ("Top", "other")]);
+  }
+}
@@ -0,0 +1,242 @@
+// Keyset pagination for the view queries.
+// Rows are ordered by a sort key and then by their id, and the cursor holds the (sort key, id) of the last row
+// that was returned, so a page never skips or repeats rows when rows are added in the meantime.
+// The cursor is opaque to clients, it is only valid for the sort it was made with.
+
+use base64::Engine;
+use todo_app_service_api::request;
+use tokio_postgres::row::Row;
+
+// the most rows a single page may hold
+pub static MAX_LIMIT: i64 = 1000;
+
+pub struct Page<T> {
+  pub items: Vec<T>,
+  // where the next page starts, if there are more rows
+  pub next_cursor: Option<String>,
+}
+
+pub struct PageQuery {
+  sort: Option<request::ViewSortKind>,
+  // sql expression to order by, the id is used if there is none
+  key: Option<&'static str>,
+  limit: Option<i64>,
+  // the parameters of a wrapped query, in order
+  pub after_key: Option<String>,
+  pub after_id: Option<i64>,
+  // one more than the limit, to find out whether there is a next page
+  pub fetch_limit: Option<i64>,
+}
+
+fn encode_cursor(sort: &Option<request::ViewSortKind>, key: &str, id: i64) -> String {
+  let sort = sort.clone().map(|x| x as i64).unwrap_or(-1);
+  base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", sort, id, key))
+}
+
+fn decode_cursor(cursor: &str) -> Option<(i64, i64, String)> {
+  let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+    .decode(cursor)
+    .ok()?;
+  let decoded = String::from_utf8(decoded).ok()?;
+  let mut parts = decoded.splitn(3, ':');
+  let sort = parts.next()?.parse().ok()?;
+  let id = parts.next()?.parse().ok()?;
+  let key = parts.next()?.to_owned();
+  Some((sort, id, key))
+}
+
+// every sort a view could support, in the order they are listed to clients
+static SORTS: [request::ViewSortKind; 5] = [
+  request::ViewSortKind::CreationTime,
+  request::ViewSortKind::Name,
+  request::ViewSortKind::StartTime,
+  request::ViewSortKind::DurationEstimate,
+  request::ViewSortKind::Utility,
+];
+
+// the sorts a view supports, for telling clients which ones they can use instead
+pub fn supported_sorts(
+  sort_key: fn(&request::ViewSortKind) -> Option<&'static str>,
+) -> Vec<request::ViewSortKind> {
+  SORTS
+    .iter()
+    .filter(|x| sort_key(x).is_some())
+    .cloned()
+    .collect()
+}
+
+impl PageQuery {
+  // `sort_key` gives the expression for each sort the view supports.
+  // None if the sort isn't supported, the cursor is malformed or was made for another sort, or the limit isn't positive
+  pub fn new(
+    sort: Option<request::ViewSortKind>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    sort_key: fn(&request::ViewSortKind) -> Option<&'static str>,
+  ) -> Option<PageQuery> {
+    let key = match &sort {
+      Some(sort) => Some(sort_key(sort)?),
+      None => None,
+    };
+
+    let (after_key, after_id) = match cursor {
+      Some(cursor) => {
+        let (cursor_sort, id, key) = decode_cursor(&cursor)?;
+        if cursor_sort != sort.clone().map(|x| x as i64).unwrap_or(-1) {
+          return None;
+        }
+        (Some(key), Some(id))
+      }
+      None => (None, None),
+    };
+
+    let limit = match limit {
+      Some(limit) if limit < 1 => return None,
+      Some(limit) => Some(limit.min(MAX_LIMIT)),
+      None => None,
+    };
+
+    Some(PageQuery {
+      sort,
+      key,
+      after_key,
+      after_id,
+      limit,
+      fetch_limit: limit.map(|x| x + 1),
+    })
+  }
+
+  // every row, in id order
+  pub fn all() -> PageQuery {
+    PageQuery {
+      sort: None,
+      key: None,
+      after_key: None,
+      after_id: None,
+      limit: None,
+      fetch_limit: None,
+    }
+  }
+
+  // wraps a query that selects `alias.*` so that it returns this page of it.
+  // the sort keys refer to `alias`, and the page's parameters (after_key, after_id and fetch_limit) start at $`first_param`
+  pub fn wrap(&self, sql: &str, alias: &str, id: &str, first_param: usize) -> String {
+    let id = format!("{}.{}", alias, id);
+    let key = self.key.unwrap_or(id.as_str());
+    let key_type = match self.sort {
+      Some(request::ViewSortKind::Name) => "text",
+      _ => "bigint",
+    };
+    [
+      format!(
+        "SELECT {}.*, ({})::text page_key, {} page_id FROM ({}) {}",
+        alias, key, id, sql, alias
+      ),
+      format!(
+        " WHERE (${}::text IS NULL OR ({}, {}) > (CAST(${} AS {}), ${}::bigint))",
+        first_param,
+        key,
+        id,
+        first_param,
+        key_type,
+        first_param + 1
+      ),
+      format!(" ORDER BY {}, {}", key, id),
+      format!(" LIMIT ${}::bigint", first_param + 2),
+    ]
+    .join("\n")
+  }
+
+  // turns the rows of a wrapped query into a page
+  pub fn page<T: From<Row>>(&self, mut rows: Vec<Row>) -> Page<T> {
+    let next_cursor = match self.limit {
+      Some(limit) if rows.len() as i64 > limit => {
+        rows.truncate(limit as usize);
+        rows.last().map(|row| {
+          encode_cursor(
+            &self.sort,
+            &row.get::<_, String>("page_key"),
+            row.get("page_id"),
+          )
+        })
+      }
+      _ => None,
+    };
+
+    Page {
+      items: rows.into_iter().map(|row| row.into()).collect(),
+      next_cursor,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use request::ViewSortKind;
+
+  fn sort_key(sort: &ViewSortKind) -> Option<&'static str> {
+    match sort {
+      ViewSortKind::CreationTime => Some("x.creation_time"),
+      ViewSortKind::Name => Some("x.name"),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn round_trips_cursors() {
+    // names may contain the separator
+    let cursor = encode_cursor(&Some(ViewSortKind::Name), "a:b:c", 42);
+    assert_eq!(
+      decode_cursor(&cursor),
+      Some((ViewSortKind::Name as i64, 42, "a:b:c".to_owned()))
+    );
+    assert_eq!(
+      decode_cursor(&encode_cursor(&None, "7", 7)),
+      Some((-1, 7, "7".to_owned()))
+    );
+
+    let page = PageQuery::new(Some(ViewSortKind::Name), Some(cursor), Some(10), sort_key).unwrap();
+    assert_eq!(page.after_key.as_deref(), Some("a:b:c"));
+    assert_eq!(page.after_id, Some(42));
+    assert_eq!(page.fetch_limit, Some(11));
+  }
+
+  #[test]
+  fn rejects_cursors_for_another_sort() {
+    let cursor = encode_cursor(&Some(ViewSortKind::Name), "a", 1);
+    assert!(PageQuery::new(
+      Some(ViewSortKind::CreationTime),
+      Some(cursor.clone()),
+      None,
+      sort_key
+    )
+    .is_none());
+    assert!(PageQuery::new(None, Some(cursor), None, sort_key).is_none());
+
+    let cursor = encode_cursor(&None, "1", 1);
+    assert!(PageQuery::new(Some(ViewSortKind::Name), Some(cursor), None, sort_key).is_none());
+  }
+
+  #[test]
+  fn rejects_bad_queries() {
+    // malformed cursors
+    for cursor in ["", "!!!", "bm9wZQ"] {
+      assert!(
+        PageQuery::new(None, Some(cursor.to_owned()), None, sort_key).is_none(),
+        "{}",
+        cursor
+      );
+    }
+    // sorts the view doesn't support
+    assert!(PageQuery::new(Some(ViewSortKind::Utility), None, None, sort_key).is_none());
+    assert_eq!(
+      supported_sorts(sort_key),
+      [ViewSortKind::CreationTime, ViewSortKind::Name]
+    );
+    // limits that aren't positive, while big ones are capped
+    assert!(PageQuery::new(None, None, Some(0), sort_key).is_none());
+    let page = PageQuery::new(None, None, Some(MAX_LIMIT * 2), sort_key).unwrap();
+    assert_eq!(page.fetch_limit, Some(MAX_LIMIT + 1));
+  }
+}
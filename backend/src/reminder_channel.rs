@@ -0,0 +1,63 @@
+// The ways a reminder can reach a user.
+// Each channel is one of the ReminderChannelKinds a user can pick in their reminder setting.
+
+use super::db_types::*;
+use super::webhook_event_service;
+use super::Db;
+use async_trait::async_trait;
+use todo_app_service_api::request;
+
+// what a reminder is about, looked up when it is sent
+pub struct ReminderMessage {
+  pub reminder: Reminder,
+  pub goal_data: GoalData,
+  pub goal_event: Option<GoalEvent>,
+}
+
+#[async_trait]
+pub trait Channel: Send + Sync {
+  fn kind(&self) -> request::ReminderChannelKind;
+  // an Err is retried later, so only return it if nothing was sent
+  async fn send(&self, db: &Db, message: &ReminderMessage) -> Result<(), String>;
+}
+
+// queues a REMINDER_DUE event for each of the user's webhooks that takes them
+pub struct WebhookChannel;
+
+#[async_trait]
+impl Channel for WebhookChannel {
+  fn kind(&self) -> request::ReminderChannelKind {
+    request::ReminderChannelKind::Webhook
+  }
+
+  async fn send(&self, db: &Db, message: &ReminderMessage) -> Result<(), String> {
+    let reminder = &message.reminder;
+    let payload = serde_json::json!({
+      "reminderId": reminder.reminder_id,
+      "kind": reminder.kind,
+      "goalId": reminder.goal_id,
+      "goalEventId": reminder.goal_event_id,
+      "name": message.goal_data.name,
+      "dueTime": reminder.due_time,
+      "fireTime": reminder.fire_time,
+      "startTime": message.goal_event.as_ref().map(|x| x.start_time),
+      "endTime": message.goal_event.as_ref().map(|x| x.end_time),
+    })
+    .to_string();
+
+    let con = &mut **db.get().await.map_err(|e| e.to_string())?;
+    let queued = webhook_event_service::add_for_user(
+      con,
+      reminder.creator_user_id,
+      request::WebhookEventKind::ReminderDue,
+      payload,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match queued {
+      0 => Err("no active webhook takes REMINDER_DUE events".to_owned()),
+      _ => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,135 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use std::convert::TryInto;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for ReminderDelivery {
+  // select * from reminder_delivery order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> ReminderDelivery {
+    ReminderDelivery {
+      reminder_delivery_id: row.get("reminder_delivery_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      reminder_id: row.get("reminder_id"),
+      channel: (row.get::<_, i64>("channel") as u8).try_into().unwrap(),
+      attempt: row.get("attempt"),
+      status: (row.get::<_, i64>("status") as u8).try_into().unwrap(),
+      error: row.get("error"),
+    }
+  }
+}
+
+// returns None if the row is a SENDING claim and the attempt was already claimed
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  reminder_id: i64,
+  channel: request::ReminderChannelKind,
+  attempt: i64,
+  status: request::ReminderDeliveryStatus,
+  error: Option<String>,
+) -> Result<Option<ReminderDelivery>, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let reminder_delivery_id = con
+    .query_opt(
+      "INSERT INTO
+       reminder_delivery(
+           creation_time,
+           creator_user_id,
+           reminder_id,
+           channel,
+           attempt,
+           status,
+           error
+       )
+       VALUES($1, $2, $3, $4, $5, $6, $7)
+       ON CONFLICT DO NOTHING
+       RETURNING reminder_delivery_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &reminder_id,
+        &(channel.clone() as i64),
+        &attempt,
+        &(status.clone() as i64),
+        &error,
+      ],
+    )
+    .await?
+    .map(|x| x.get(0));
+
+  // return reminder_delivery
+  Ok(
+    reminder_delivery_id.map(|reminder_delivery_id| ReminderDelivery {
+      reminder_delivery_id,
+      creation_time,
+      creator_user_id,
+      reminder_id,
+      channel,
+      attempt,
+      status,
+      error,
+    }),
+  )
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("rd.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::ReminderDeliveryViewProps,
+) -> Result<Page<ReminderDelivery>, tokio_postgres::Error> {
+  let sql = [
+    "SELECT rd.* FROM reminder_delivery rd",
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR rd.reminder_delivery_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR rd.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR rd.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR rd.creator_user_id = ANY($4))",
+    " AND ($5::bigint[] IS NULL OR rd.reminder_id = ANY($5))",
+    " AND ($6::bigint[] IS NULL OR rd.channel = ANY($6))",
+    " AND ($7::bigint[] IS NULL OR rd.status = ANY($7))",
+    " AND rd.creator_user_id = $8",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "rd", "reminder_delivery_id", 9);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.reminder_delivery_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &props.reminder_id,
+        &props
+          .channel
+          .map(|x| x.into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
+        &props
+          .status
+          .map(|x| x.into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}
@@ -0,0 +1,307 @@
+// Queues reminders and sends them through the channels users picked.
+// Users get reminded a set time before each of their scheduled goal events starts,
+// and a set time before a pending goal's utility drops below a threshold.
+//
+// Every tick works out which reminders the current goals and settings call for, and queues the ones due soon.
+// A queued reminder that is no longer called for (the event moved, the goal was finished, the setting changed)
+// is cancelled instead of being sent.
+// Each attempt is claimed in the database before the channel is called, so a reminder is never sent twice,
+// even if the server restarts. An attempt that was cut short by a restart is not retried.
+
+use super::db_types::*;
+use super::goal_data_service;
+use super::goal_event_service;
+use super::reminder_channel::{Channel, ReminderMessage};
+use super::reminder_delivery_service;
+use super::reminder_service;
+use super::reminder_setting_service;
+use super::time_utility_function_service;
+use super::utils;
+use super::Db;
+use deadpool_postgres::PoolError;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use todo_app_service_api::request;
+
+// failed attempts are retried until this many have failed
+static MAX_ATTEMPTS: i64 = 5;
+// the wait after the first failure, doubling after each one
+static RETRY_BASE: i64 = 30 * 1000;
+// a reminder running late is still sent until this long after the thing it is about
+static LATE_GRACE: i64 = 5 * 60 * 1000;
+// reminders that fired longer ago than this are not looked at anymore
+static MAX_REMINDER_AGE: i64 = 24 * 60 * 60 * 1000;
+
+// kind, goal_id, goal_event_id, due_time, fire_time
+type ReminderKey = (i64, i64, Option<i64>, i64, i64);
+
+fn key(reminder: &Reminder) -> ReminderKey {
+  (
+    reminder.kind.clone() as i64,
+    reminder.goal_id,
+    reminder.goal_event_id,
+    reminder.due_time,
+    reminder.fire_time,
+  )
+}
+
+pub async fn run(db: Db, interval: Duration, channels: Vec<Box<dyn Channel>>) {
+  let mut ticker = tokio::time::interval(interval);
+  loop {
+    ticker.tick().await;
+
+    let now = utils::current_time_millis();
+    // queue a little ahead, so reminders don't wait for the next tick
+    let lookahead = 2 * interval.as_millis() as i64;
+
+    if let Err(e) = tick(&db, &channels, now, lookahead).await {
+      utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("reminder scheduler".to_owned()),
+        severity: utils::SeverityKind::Error,
+      });
+    }
+  }
+}
+
+// the first time after `after` that the utility falls below the threshold.
+// utility is linear between the points of the function, and flat before the first and after the last
+fn utility_drop_time(
+  time_utility_function: &TimeUtilityFunction,
+  threshold: i64,
+  after: i64,
+) -> Option<i64> {
+  let points: Vec<(i64, i64)> = time_utility_function
+    .start_times
+    .iter()
+    .copied()
+    .zip(time_utility_function.utils.iter().copied())
+    .collect();
+
+  for w in points.windows(2) {
+    let ((t0, u0), (t1, u1)) = (w[0], w[1]);
+    if u0 >= threshold && u1 < threshold {
+      // where the line between the two points crosses the threshold
+      let t = t0 + ((t1 - t0) as i128 * (u0 - threshold) as i128 / (u0 - u1) as i128) as i64;
+      if t > after {
+        return Some(t);
+      }
+    }
+  }
+  None
+}
+
+// every reminder the current goals and settings call for, that isn't too late to send
+async fn wanted(db: &Db, now: i64) -> Result<Vec<Reminder>, PoolError> {
+  let con = &mut **db.get().await?;
+
+  let settings: HashMap<i64, ReminderSetting> = reminder_setting_service::get_recent(con)
+    .await?
+    .into_iter()
+    .map(|x| (x.creator_user_id, x))
+    .collect();
+
+  let mut wanted = vec![];
+
+  let goal_event_users: Vec<i64> = settings
+    .values()
+    .filter(|x| x.goal_event_offset.is_some())
+    .map(|x| x.creator_user_id)
+    .collect();
+
+  for goal_event in
+    goal_event_service::get_recent_active_pending(con, &goal_event_users, now - LATE_GRACE).await?
+  {
+    let offset = settings[&goal_event.creator_user_id]
+      .goal_event_offset
+      .unwrap_or(0);
+    wanted.push(Reminder {
+      reminder_id: 0,
+      creation_time: now,
+      creator_user_id: goal_event.creator_user_id,
+      kind: request::ReminderKind::GoalEventStart,
+      goal_id: goal_event.goal_id,
+      goal_event_id: Some(goal_event.goal_event_id),
+      due_time: goal_event.start_time,
+      fire_time: goal_event.start_time - offset,
+    });
+  }
+
+  let utility_users: Vec<i64> = settings
+    .values()
+    .filter(|x| x.utility_threshold.is_some() && x.utility_offset.is_some())
+    .map(|x| x.creator_user_id)
+    .collect();
+
+  let goal_data = goal_data_service::get_recent_pending(con, &utility_users).await?;
+
+  let time_utility_function_ids: Vec<i64> = goal_data
+    .iter()
+    .map(|x| x.time_utility_function_id)
+    .collect();
+
+  let time_utility_functions: HashMap<i64, TimeUtilityFunction> =
+    time_utility_function_service::get_by_time_utility_function_ids(
+      con,
+      &time_utility_function_ids,
+    )
+    .await?
+    .into_iter()
+    .map(|x| (x.time_utility_function_id, x))
+    .collect();
+
+  for goal_data in goal_data {
+    let setting = &settings[&goal_data.creator_user_id];
+    let (threshold, offset) = match (setting.utility_threshold, setting.utility_offset) {
+      (Some(threshold), Some(offset)) => (threshold, offset),
+      _ => continue,
+    };
+    let drop_time = time_utility_functions
+      .get(&goal_data.time_utility_function_id)
+      .and_then(|x| utility_drop_time(x, threshold, now - LATE_GRACE));
+    if let Some(drop_time) = drop_time {
+      wanted.push(Reminder {
+        reminder_id: 0,
+        creation_time: now,
+        creator_user_id: goal_data.creator_user_id,
+        kind: request::ReminderKind::UtilityDrop,
+        goal_id: goal_data.goal_id,
+        goal_event_id: None,
+        due_time: drop_time,
+        fire_time: drop_time - offset,
+      });
+    }
+  }
+
+  Ok(wanted)
+}
+
+async fn tick(
+  db: &Db,
+  channels: &[Box<dyn Channel>],
+  now: i64,
+  lookahead: i64,
+) -> Result<(), PoolError> {
+  let wanted = wanted(db, now).await?;
+
+  {
+    let con = &mut **db.get().await?;
+    for reminder in wanted.iter().filter(|x| x.fire_time <= now + lookahead) {
+      reminder_service::add_if_absent(
+        con,
+        reminder.creator_user_id,
+        reminder.kind.clone(),
+        reminder.goal_id,
+        reminder.goal_event_id,
+        reminder.due_time,
+        reminder.fire_time,
+      )
+      .await?;
+    }
+  }
+
+  let wanted: HashSet<ReminderKey> = wanted.iter().map(key).collect();
+
+  let kinds: Vec<request::ReminderChannelKind> = channels.iter().map(|x| x.kind()).collect();
+
+  let unsent = {
+    let con = &mut **db.get().await?;
+    reminder_service::get_unsent(con, now - MAX_REMINDER_AGE, now, &kinds, MAX_ATTEMPTS).await?
+  };
+
+  for (reminder, kind, failures, last_attempt_time) in unsent {
+    // wait out the backoff before trying again
+    if let Some(last_attempt_time) = last_attempt_time {
+      if failures > 0 && now < last_attempt_time + (RETRY_BASE << (failures - 1).clamp(0, 20)) {
+        continue;
+      }
+    }
+    let channel = match channels
+      .iter()
+      .find(|x| x.kind() as i64 == kind.clone() as i64)
+    {
+      Some(channel) => channel.as_ref(),
+      None => continue,
+    };
+    let still_wanted = wanted.contains(&key(&reminder));
+    deliver(db, channel, reminder, failures + 1, still_wanted).await?;
+  }
+
+  Ok(())
+}
+
+async fn deliver(
+  db: &Db,
+  channel: &dyn Channel,
+  reminder: Reminder,
+  attempt: i64,
+  still_wanted: bool,
+) -> Result<(), PoolError> {
+  let message = {
+    let con = &mut **db.get().await?;
+
+    if !still_wanted {
+      reminder_delivery_service::add(
+        con,
+        reminder.creator_user_id,
+        reminder.reminder_id,
+        channel.kind(),
+        attempt,
+        request::ReminderDeliveryStatus::Cancelled,
+        None,
+      )
+      .await?;
+      return Ok(());
+    }
+
+    // claim the attempt, if someone else already did they'll send it
+    let claim = reminder_delivery_service::add(
+      con,
+      reminder.creator_user_id,
+      reminder.reminder_id,
+      channel.kind(),
+      attempt,
+      request::ReminderDeliveryStatus::Sending,
+      None,
+    )
+    .await?;
+    if claim.is_none() {
+      return Ok(());
+    }
+
+    let goal_data = goal_data_service::get_recent_by_goal_id(con, reminder.goal_id).await?;
+    let goal_event = match reminder.goal_event_id {
+      Some(goal_event_id) => goal_event_service::get_by_goal_event_id(con, &goal_event_id).await?,
+      None => None,
+    };
+    goal_data.map(|goal_data| ReminderMessage {
+      reminder: reminder.clone(),
+      goal_data,
+      goal_event,
+    })
+  };
+
+  let result = match message {
+    Some(message) => channel.send(db, &message).await,
+    None => Err("goal does not exist".to_owned()),
+  };
+
+  let (status, error) = match result {
+    Ok(()) => (request::ReminderDeliveryStatus::Sent, None),
+    Err(e) => (request::ReminderDeliveryStatus::Failed, Some(e)),
+  };
+
+  let con = &mut **db.get().await?;
+  reminder_delivery_service::add(
+    con,
+    reminder.creator_user_id,
+    reminder.reminder_id,
+    channel.kind(),
+    attempt,
+    status,
+    error,
+  )
+  .await?;
+
+  Ok(())
+}
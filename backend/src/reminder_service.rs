@@ -0,0 +1,222 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use std::convert::TryInto;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Reminder {
+  // select * from reminder order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> Reminder {
+    Reminder {
+      reminder_id: row.get("reminder_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      kind: (row.get::<_, i64>("kind") as u8).try_into().unwrap(),
+      goal_id: row.get("goal_id"),
+      goal_event_id: row.get("goal_event_id"),
+      due_time: row.get("due_time"),
+      fire_time: row.get("fire_time"),
+    }
+  }
+}
+
+// queues a reminder, unless the same one is already queued
+pub async fn add_if_absent(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  kind: request::ReminderKind,
+  goal_id: i64,
+  goal_event_id: Option<i64>,
+  due_time: i64,
+  fire_time: i64,
+) -> Result<Option<Reminder>, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let reminder_id = con
+    .query_opt(
+      "INSERT INTO
+       reminder(
+           creation_time,
+           creator_user_id,
+           kind,
+           goal_id,
+           goal_event_id,
+           due_time,
+           fire_time
+       )
+       VALUES($1, $2, $3, $4, $5, $6, $7)
+       ON CONFLICT DO NOTHING
+       RETURNING reminder_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &(kind.clone() as i64),
+        &goal_id,
+        &goal_event_id,
+        &due_time,
+        &fire_time,
+      ],
+    )
+    .await?
+    .map(|x| x.get(0));
+
+  // return reminder
+  Ok(reminder_id.map(|reminder_id| Reminder {
+    reminder_id,
+    creation_time,
+    creator_user_id,
+    kind,
+    goal_id,
+    goal_event_id,
+    due_time,
+    fire_time,
+  }))
+}
+
+pub async fn get_by_reminder_id(
+  con: &mut impl GenericClient,
+  reminder_id: i64,
+) -> Result<Option<Reminder>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM reminder WHERE reminder_id=$1",
+      &[&reminder_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+pub async fn get_by_reminder_ids(
+  con: &mut impl GenericClient,
+  reminder_ids: &[i64],
+) -> Result<Vec<Reminder>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM reminder WHERE reminder_id = ANY($1)";
+  let results = con
+    .query(sql, &[&reminder_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// reminders that fired in the given window and still have to go out through one of the given channels,
+// along with the channel, how many attempts on it failed, and when the last attempt was.
+// reminders with an attempt that never finished are left alone, they may have been sent already
+pub async fn get_unsent(
+  con: &mut impl GenericClient,
+  min_fire_time: i64,
+  max_fire_time: i64,
+  channels: &[request::ReminderChannelKind],
+  max_attempts: i64,
+) -> Result<Vec<(Reminder, request::ReminderChannelKind, i64, Option<i64>)>, tokio_postgres::Error>
+{
+  let results = con
+    .query(
+      "SELECT r.*,
+         c.channel,
+         count(rd.reminder_delivery_id) FILTER (WHERE rd.status = $7) failures,
+         max(rd.creation_time) last_attempt_time
+       FROM reminder r
+       INNER JOIN recent_reminder_setting rs ON rs.creator_user_id = r.creator_user_id
+       CROSS JOIN LATERAL unnest(rs.channels) c(channel)
+       LEFT JOIN reminder_delivery rd ON rd.reminder_id = r.reminder_id AND rd.channel = c.channel
+       WHERE r.fire_time >= $1
+       AND r.fire_time <= $2
+       AND c.channel = ANY($3)
+       GROUP BY r.reminder_id, c.channel
+       HAVING count(rd.reminder_delivery_id) FILTER (WHERE rd.status IN ($6, $8)) = 0
+       AND count(rd.reminder_delivery_id) FILTER (WHERE rd.status = $5)
+         = count(rd.reminder_delivery_id) FILTER (WHERE rd.status = $7)
+       AND count(rd.reminder_delivery_id) FILTER (WHERE rd.status = $7) < $4
+       ORDER BY r.fire_time, r.reminder_id
+      ",
+      &[
+        &min_fire_time,
+        &max_fire_time,
+        &channels
+          .iter()
+          .map(|x| x.clone() as i64)
+          .collect::<Vec<i64>>(),
+        &max_attempts,
+        &(request::ReminderDeliveryStatus::Sending as i64),
+        &(request::ReminderDeliveryStatus::Sent as i64),
+        &(request::ReminderDeliveryStatus::Failed as i64),
+        &(request::ReminderDeliveryStatus::Cancelled as i64),
+      ],
+    )
+    .await?
+    .into_iter()
+    .map(|row| {
+      let channel = (row.get::<_, i64>("channel") as u8).try_into().unwrap();
+      let failures = row.get("failures");
+      let last_attempt_time = row.get("last_attempt_time");
+      (row.into(), channel, failures, last_attempt_time)
+    })
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("r.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::ReminderViewProps,
+) -> Result<Page<Reminder>, tokio_postgres::Error> {
+  let sql = [
+    "SELECT r.* FROM reminder r",
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR r.reminder_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR r.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR r.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR r.creator_user_id = ANY($4))",
+    " AND ($5::bigint[] IS NULL OR r.kind = ANY($5))",
+    " AND ($6::bigint[] IS NULL OR r.goal_id = ANY($6))",
+    " AND ($7::bigint[] IS NULL OR r.goal_event_id = ANY($7))",
+    " AND ($8::bigint   IS NULL OR r.fire_time >= $8)",
+    " AND ($9::bigint   IS NULL OR r.fire_time <= $9)",
+    " AND r.creator_user_id = $10",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "r", "reminder_id", 11);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.reminder_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &props
+          .kind
+          .map(|x| x.into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
+        &props.goal_id,
+        &props.goal_event_id,
+        &props.min_fire_time,
+        &props.max_fire_time,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}
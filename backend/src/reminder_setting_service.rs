@@ -0,0 +1,141 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use std::convert::TryInto;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for ReminderSetting {
+  // select * from reminder_setting order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> ReminderSetting {
+    ReminderSetting {
+      reminder_setting_id: row.get("reminder_setting_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      goal_event_offset: row.get("goal_event_offset"),
+      utility_threshold: row.get("utility_threshold"),
+      utility_offset: row.get("utility_offset"),
+      channels: row
+        .get::<_, Vec<i64>>("channels")
+        .into_iter()
+        .map(|x| (x as u8).try_into().unwrap())
+        .collect(),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  goal_event_offset: Option<i64>,
+  utility_threshold: Option<i64>,
+  utility_offset: Option<i64>,
+  channels: Vec<request::ReminderChannelKind>,
+) -> Result<ReminderSetting, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let reminder_setting_id = con
+    .query_one(
+      "INSERT INTO
+       reminder_setting(
+           creation_time,
+           creator_user_id,
+           goal_event_offset,
+           utility_threshold,
+           utility_offset,
+           channels
+       )
+       VALUES($1, $2, $3, $4, $5, $6)
+       RETURNING reminder_setting_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &goal_event_offset,
+        &utility_threshold,
+        &utility_offset,
+        &channels
+          .iter()
+          .map(|x| x.clone() as i64)
+          .collect::<Vec<i64>>(),
+      ],
+    )
+    .await?
+    .get(0);
+
+  // return reminder_setting
+  Ok(ReminderSetting {
+    reminder_setting_id,
+    creation_time,
+    creator_user_id,
+    goal_event_offset,
+    utility_threshold,
+    utility_offset,
+    channels,
+  })
+}
+
+// the current setting of every user that has one
+pub async fn get_recent(
+  con: &mut impl GenericClient,
+) -> Result<Vec<ReminderSetting>, tokio_postgres::Error> {
+  let results = con
+    .query("SELECT * FROM recent_reminder_setting", &[])
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("rs.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::ReminderSettingViewProps,
+) -> Result<Page<ReminderSetting>, tokio_postgres::Error> {
+  let sql = [
+    if props.only_recent {
+      "SELECT rs.* FROM recent_reminder_setting rs"
+    } else {
+      "SELECT rs.* FROM reminder_setting rs"
+    },
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR rs.reminder_setting_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR rs.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR rs.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR rs.creator_user_id = ANY($4))",
+    " AND rs.creator_user_id = $5",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "rs", "reminder_setting_id", 6);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.reminder_setting_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}
@@ -0,0 +1,73 @@
+// Encrypts the secrets we have to be able to read back, like the passwords of CalDAV subscriptions,
+// so that they don't leak with a dump or backup of the database.
+// They are sealed with AES-256-GCM under a key that only the server has (see --secret-key).
+// Each sealed value is a random nonce followed by the ciphertext, so sealing the same secret twice differs.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+
+static NONCE_LEN: usize = 12;
+
+#[derive(Clone)]
+pub struct SecretBox {
+  cipher: Aes256Gcm,
+}
+
+impl SecretBox {
+  // the key is 32 random bytes, hex encoded, e.g. from `openssl rand -hex 32`
+  pub fn new(key: &str) -> Result<SecretBox, String> {
+    let key = key.trim();
+    let bytes = (0..key.len())
+      .step_by(2)
+      .map(|i| {
+        key
+          .get(i..i + 2)
+          .and_then(|x| u8::from_str_radix(x, 16).ok())
+      })
+      .collect::<Option<Vec<u8>>>()
+      .ok_or("the secret key must be hex encoded")?;
+    let cipher =
+      Aes256Gcm::new_from_slice(&bytes).map_err(|_| "the secret key must be 32 bytes long")?;
+    Ok(SecretBox { cipher })
+  }
+
+  pub fn seal(&self, plaintext: &str) -> Result<Vec<u8>, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = self
+      .cipher
+      .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+      .map_err(|_| "failed to seal secret")?;
+    Ok([&nonce[..], &ciphertext].concat())
+  }
+
+  // fails if the value was sealed with another key or tampered with
+  pub fn open(&self, sealed: &[u8]) -> Result<String, String> {
+    if sealed.len() < NONCE_LEN {
+      return Err("sealed secret is too short".to_owned());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = self
+      .cipher
+      .decrypt(Nonce::from_slice(nonce), ciphertext)
+      .map_err(|_| "failed to open secret, was the secret key changed?")?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn opens_only_with_the_same_key() {
+    let a = SecretBox::new(&"ab".repeat(32)).unwrap();
+    let b = SecretBox::new(&"cd".repeat(32)).unwrap();
+    let sealed = a.seal("hunter2").unwrap();
+    assert_eq!(a.open(&sealed).unwrap(), "hunter2");
+    assert!(b.open(&sealed).is_err());
+    assert!(SecretBox::new("not hex").is_err());
+    assert!(SecretBox::new("abcd").is_err());
+  }
+}
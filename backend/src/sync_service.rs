@@ -0,0 +1,609 @@
+// Incremental sync for clients that keep their own copy of the user's data, like the browser extension.
+// Rows are never updated, and they are only sent up to the high-water marks of account_service::high_water_marks,
+// so a client only has to remember the highest id it has of each table to know which rows it is missing.
+// Clients that were offline upload the rows they made in the meantime, with negative temporary ids.
+// In an upload, a negative reference points to a row of the same upload, a positive one to a row on the server.
+
+use super::account_service;
+use super::account_service::{table_key, Tables};
+use super::db_types::*;
+use super::goal_data_service;
+use super::utils::current_time_millis;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use todo_app_service_api::request;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::GenericClient;
+
+// the most rows sent of each table at once
+static BATCH_SIZE: i64 = 1000;
+
+// the tables that are synced, in the order of Tables
+static TABLES: [&str; 15] = [
+  "user_generated_code",
+  "time_utility_function",
+  "goal",
+  "goal_data",
+  "goal_event",
+  "goal_dependency",
+  "goal_template",
+  "goal_template_data",
+  "goal_template_pattern",
+  "named_entity",
+  "named_entity_data",
+  "named_entity_pattern",
+  "goal_entity_tag",
+  "external_event",
+  "external_event_data",
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProps {
+  // the highest id the client has of each table, keyed like the fields of Tables.
+  // tables that are left out are sent from the start
+  #[serde(default)]
+  pub since: BTreeMap<String, i64>,
+  // rows made offline, applied before anything is sent
+  #[serde(default)]
+  pub upload: Tables,
+  pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncResult {
+  // the rows the client is missing, the uploaded ones included
+  pub tables: Tables,
+  // what to send as `since` next time
+  pub since: BTreeMap<String, i64>,
+  // whether some table had more rows than were sent, then the client should sync again right away
+  pub more: bool,
+  // the ids the uploaded rows were given, by table and temporary id
+  pub id_map: BTreeMap<String, BTreeMap<i64, i64>>,
+}
+
+#[derive(Debug)]
+pub enum SyncError {
+  // the request is malformed, or refers to rows that don't exist or aren't the user's
+  Invalid(String),
+  Postgres(tokio_postgres::Error),
+}
+
+impl From<tokio_postgres::Error> for SyncError {
+  fn from(e: tokio_postgres::Error) -> SyncError {
+    SyncError::Postgres(e)
+  }
+}
+
+// the rows of an upload that webhooks are told about, see handlers::sync
+#[derive(Default)]
+pub struct Applied {
+  pub id_map: BTreeMap<String, BTreeMap<i64, i64>>,
+  // with the status the goal had before, if it existed
+  pub goal_data: Vec<(Option<request::GoalDataStatusKind>, GoalData)>,
+  pub goal_event: Vec<GoalEvent>,
+  pub external_event_data: Vec<ExternalEventData>,
+}
+
+// maps the temporary ids of one table to the ids the rows were given
+struct Ids {
+  table: &'static str,
+  ids: HashMap<i64, i64>,
+}
+
+impl Ids {
+  fn new(table: &'static str) -> Ids {
+    Ids {
+      table,
+      ids: HashMap::new(),
+    }
+  }
+
+  // records the id an uploaded row was given
+  fn insert(&mut self, temp_id: i64, id: i64) -> Result<(), SyncError> {
+    if temp_id >= 0 {
+      return Err(SyncError::Invalid(format!(
+        "{}_id {} is not a temporary id",
+        self.table, temp_id
+      )));
+    }
+    if self.ids.insert(temp_id, id).is_some() {
+      return Err(SyncError::Invalid(format!(
+        "duplicate temporary {}_id {}",
+        self.table, temp_id
+      )));
+    }
+    Ok(())
+  }
+
+  // the server id a reference points to, which must be the user's
+  async fn resolve(
+    &self,
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    id: i64,
+  ) -> Result<i64, SyncError> {
+    if id < 0 {
+      return self
+        .ids
+        .get(&id)
+        .copied()
+        .ok_or_else(|| SyncError::Invalid(format!("unknown temporary {}_id {}", self.table, id)));
+    }
+
+    let owned = con
+      .query_opt(
+        format!(
+          "SELECT 1 FROM {} WHERE {}_id = $1 AND creator_user_id = $2",
+          self.table, self.table
+        )
+        .as_str(),
+        &[&id, &creator_user_id],
+      )
+      .await?
+      .is_some();
+
+    if owned {
+      Ok(id)
+    } else {
+      Err(SyncError::Invalid(format!(
+        "unknown {}_id {}",
+        self.table, id
+      )))
+    }
+  }
+
+  fn into_map(self) -> (String, BTreeMap<i64, i64>) {
+    (table_key(self.table), self.ids.into_iter().collect())
+  }
+}
+
+// inserts one row, returning it as it was stored
+async fn insert<T: From<tokio_postgres::Row>>(
+  con: &mut impl GenericClient,
+  sql: &str,
+  params: &[&(dyn ToSql + Sync)],
+) -> Result<T, SyncError> {
+  Ok(con.query_one(sql, params).await?.into())
+}
+
+fn validate_duration(table: &str, duration: Option<i64>) -> Result<(), SyncError> {
+  match duration {
+    Some(duration) if duration <= 0 => Err(SyncError::Invalid(format!(
+      "{} has a duration_estimate that isn't positive",
+      table
+    ))),
+    _ => Ok(()),
+  }
+}
+
+fn validate_times(table: &str, start_time: i64, end_time: i64) -> Result<(), SyncError> {
+  if start_time < 0 || end_time < start_time {
+    return Err(SyncError::Invalid(format!(
+      "{} has invalid start and end times",
+      table
+    )));
+  }
+  Ok(())
+}
+
+// inserts the uploaded rows, table by table and in the order they were uploaded in.
+// creation times from the future are clamped to now.
+// Should be run in a transaction, so that a bad row leaves nothing behind
+pub async fn apply(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  upload: Tables,
+) -> Result<Applied, SyncError> {
+  let u = creator_user_id;
+  let now = current_time_millis();
+  let mut applied = Applied::default();
+
+  let mut user_generated_code_ids = Ids::new("user_generated_code");
+  for x in upload.user_generated_code {
+    let row: UserGeneratedCode = insert(
+      con,
+      "INSERT INTO user_generated_code(creation_time, creator_user_id, source_code, source_lang, wasm_cache)
+       VALUES($1, $2, $3, $4, $5) RETURNING *",
+      &[&x.creation_time.min(now), &u, &x.source_code, &x.source_lang, &x.wasm_cache],
+    )
+    .await?;
+    user_generated_code_ids.insert(x.user_generated_code_id, row.user_generated_code_id)?;
+  }
+
+  let mut time_utility_function_ids = Ids::new("time_utility_function");
+  for x in upload.time_utility_function {
+    if x.start_times.is_empty() || x.start_times.len() != x.utils.len() {
+      return Err(SyncError::Invalid(
+        "time_utility_function needs as many start_times as utils, and at least one".to_owned(),
+      ));
+    }
+    let row: TimeUtilityFunction = insert(
+      con,
+      "INSERT INTO time_utility_function(creation_time, creator_user_id, start_times, utils)
+       VALUES($1, $2, $3, $4) RETURNING *",
+      &[&x.creation_time.min(now), &u, &x.start_times, &x.utils],
+    )
+    .await?;
+    time_utility_function_ids.insert(x.time_utility_function_id, row.time_utility_function_id)?;
+  }
+
+  let mut goal_ids = Ids::new("goal");
+  for x in upload.goal {
+    let row: Goal = insert(
+      con,
+      "INSERT INTO goal(creation_time, creator_user_id)
+       VALUES($1, $2) RETURNING *",
+      &[&x.creation_time.min(now), &u],
+    )
+    .await?;
+    goal_ids.insert(x.goal_id, row.goal_id)?;
+  }
+
+  let mut goal_data_ids = Ids::new("goal_data");
+  for x in upload.goal_data {
+    validate_duration("goal_data", x.duration_estimate)?;
+    let goal_id = goal_ids.resolve(con, u, x.goal_id).await?;
+    let previous = goal_data_service::get_recent_by_goal_id(con, goal_id).await?;
+    let time_utility_function_id = time_utility_function_ids
+      .resolve(con, u, x.time_utility_function_id)
+      .await?;
+    let row: GoalData = insert(
+      con,
+      "INSERT INTO goal_data(creation_time, creator_user_id, goal_id, name, duration_estimate, time_utility_function_id, status)
+       VALUES($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &goal_id,
+        &x.name,
+        &x.duration_estimate,
+        &time_utility_function_id,
+        &(x.status as i64),
+      ],
+    )
+    .await?;
+    goal_data_ids.insert(x.goal_data_id, row.goal_data_id)?;
+    applied
+      .goal_data
+      .push((previous.map(|previous| previous.status), row));
+  }
+
+  let mut goal_event_ids = Ids::new("goal_event");
+  for x in upload.goal_event {
+    validate_times("goal_event", x.start_time, x.end_time)?;
+    let goal_id = goal_ids.resolve(con, u, x.goal_id).await?;
+    let row: GoalEvent = insert(
+      con,
+      "INSERT INTO goal_event(creation_time, creator_user_id, goal_id, start_time, end_time, active)
+       VALUES($1, $2, $3, $4, $5, $6) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &goal_id,
+        &x.start_time,
+        &x.end_time,
+        &x.active,
+      ],
+    )
+    .await?;
+    goal_event_ids.insert(x.goal_event_id, row.goal_event_id)?;
+    applied.goal_event.push(row);
+  }
+
+  let mut goal_dependency_ids = Ids::new("goal_dependency");
+  for x in upload.goal_dependency {
+    let goal_id = goal_ids.resolve(con, u, x.goal_id).await?;
+    let dependent_goal_id = goal_ids.resolve(con, u, x.dependent_goal_id).await?;
+    let row: GoalDependency = insert(
+      con,
+      "INSERT INTO goal_dependency(creation_time, creator_user_id, goal_id, dependent_goal_id, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &goal_id,
+        &dependent_goal_id,
+        &x.active,
+      ],
+    )
+    .await?;
+    goal_dependency_ids.insert(x.goal_dependency_id, row.goal_dependency_id)?;
+  }
+
+  let mut goal_template_ids = Ids::new("goal_template");
+  for x in upload.goal_template {
+    let row: GoalTemplate = insert(
+      con,
+      "INSERT INTO goal_template(creation_time, creator_user_id)
+       VALUES($1, $2) RETURNING *",
+      &[&x.creation_time.min(now), &u],
+    )
+    .await?;
+    goal_template_ids.insert(x.goal_template_id, row.goal_template_id)?;
+  }
+
+  let mut goal_template_data_ids = Ids::new("goal_template_data");
+  for x in upload.goal_template_data {
+    validate_duration("goal_template_data", x.duration_estimate)?;
+    let goal_template_id = goal_template_ids
+      .resolve(con, u, x.goal_template_id)
+      .await?;
+    let user_generated_code_id = user_generated_code_ids
+      .resolve(con, u, x.user_generated_code_id)
+      .await?;
+    let row: GoalTemplateData = insert(
+      con,
+      "INSERT INTO goal_template_data(creation_time, creator_user_id, goal_template_id, name, utility, duration_estimate, user_generated_code_id, published, active)
+       VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &goal_template_id,
+        &x.name,
+        &x.utility,
+        &x.duration_estimate,
+        &user_generated_code_id,
+        &x.published,
+        &x.active,
+      ],
+    )
+    .await?;
+    goal_template_data_ids.insert(x.goal_template_data_id, row.goal_template_data_id)?;
+  }
+
+  let mut goal_template_pattern_ids = Ids::new("goal_template_pattern");
+  for x in upload.goal_template_pattern {
+    let goal_template_id = goal_template_ids
+      .resolve(con, u, x.goal_template_id)
+      .await?;
+    let row: GoalTemplatePattern = insert(
+      con,
+      "INSERT INTO goal_template_pattern(creation_time, creator_user_id, goal_template_id, pattern, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &goal_template_id,
+        &x.pattern,
+        &x.active,
+      ],
+    )
+    .await?;
+    goal_template_pattern_ids.insert(x.goal_template_pattern_id, row.goal_template_pattern_id)?;
+  }
+
+  let mut named_entity_ids = Ids::new("named_entity");
+  for x in upload.named_entity {
+    let row: NamedEntity = insert(
+      con,
+      "INSERT INTO named_entity(creation_time, creator_user_id)
+       VALUES($1, $2) RETURNING *",
+      &[&x.creation_time.min(now), &u],
+    )
+    .await?;
+    named_entity_ids.insert(x.named_entity_id, row.named_entity_id)?;
+  }
+
+  let mut named_entity_data_ids = Ids::new("named_entity_data");
+  for x in upload.named_entity_data {
+    let named_entity_id = named_entity_ids.resolve(con, u, x.named_entity_id).await?;
+    let row: NamedEntityData = insert(
+      con,
+      "INSERT INTO named_entity_data(creation_time, creator_user_id, named_entity_id, name, kind, active)
+       VALUES($1, $2, $3, $4, $5, $6) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &named_entity_id,
+        &x.name,
+        &(x.kind as i64),
+        &x.active,
+      ],
+    )
+    .await?;
+    named_entity_data_ids.insert(x.named_entity_data_id, row.named_entity_data_id)?;
+  }
+
+  let mut named_entity_pattern_ids = Ids::new("named_entity_pattern");
+  for x in upload.named_entity_pattern {
+    let named_entity_id = named_entity_ids.resolve(con, u, x.named_entity_id).await?;
+    let row: NamedEntityPattern = insert(
+      con,
+      "INSERT INTO named_entity_pattern(creation_time, creator_user_id, named_entity_id, pattern, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &named_entity_id,
+        &x.pattern,
+        &x.active,
+      ],
+    )
+    .await?;
+    named_entity_pattern_ids.insert(x.named_entity_pattern_id, row.named_entity_pattern_id)?;
+  }
+
+  let mut goal_entity_tag_ids = Ids::new("goal_entity_tag");
+  for x in upload.goal_entity_tag {
+    let named_entity_id = named_entity_ids.resolve(con, u, x.named_entity_id).await?;
+    let goal_id = goal_ids.resolve(con, u, x.goal_id).await?;
+    let row: GoalEntityTag = insert(
+      con,
+      "INSERT INTO goal_entity_tag(creation_time, creator_user_id, named_entity_id, goal_id, active)
+       VALUES($1, $2, $3, $4, $5) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &named_entity_id,
+        &goal_id,
+        &x.active,
+      ],
+    )
+    .await?;
+    goal_entity_tag_ids.insert(x.goal_entity_tag_id, row.goal_entity_tag_id)?;
+  }
+
+  let mut external_event_ids = Ids::new("external_event");
+  for x in upload.external_event {
+    // an event the user already has from the same source and uid is the same event,
+    // so the temporary id is given the existing one and revisions are added to it
+    let inserted = con
+      .query_opt(
+        "INSERT INTO external_event(creation_time, creator_user_id, source, external_uid, resource_name)
+         VALUES($1, $2, $3, $4, $5)
+         ON CONFLICT (creator_user_id, source, external_uid) DO NOTHING
+         RETURNING *",
+        &[&x.creation_time.min(now), &u, &x.source, &x.external_uid, &x.resource_name],
+      )
+      .await?;
+    let row: ExternalEvent = match inserted {
+      Some(row) => row.into(),
+      None => con
+        .query_one(
+          "SELECT * FROM external_event
+           WHERE creator_user_id=$1 AND source=$2 AND external_uid=$3",
+          &[&u, &x.source, &x.external_uid],
+        )
+        .await?
+        .into(),
+    };
+    external_event_ids.insert(x.external_event_id, row.external_event_id)?;
+  }
+
+  let mut external_event_data_ids = Ids::new("external_event_data");
+  for x in upload.external_event_data {
+    validate_times("external_event_data", x.start_time, x.end_time)?;
+    let external_event_id = external_event_ids
+      .resolve(con, u, x.external_event_id)
+      .await?;
+    let row: ExternalEventData = insert(
+      con,
+      "INSERT INTO external_event_data(creation_time, creator_user_id, external_event_id, name, start_time, end_time, active)
+       VALUES($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+      &[
+        &x.creation_time.min(now),
+        &u,
+        &external_event_id,
+        &x.name,
+        &x.start_time,
+        &x.end_time,
+        &x.active,
+      ],
+    )
+    .await?;
+    external_event_data_ids.insert(x.external_event_data_id, row.external_event_data_id)?;
+    applied.external_event_data.push(row);
+  }
+
+  applied.id_map = [
+    user_generated_code_ids,
+    time_utility_function_ids,
+    goal_ids,
+    goal_data_ids,
+    goal_event_ids,
+    goal_dependency_ids,
+    goal_template_ids,
+    goal_template_data_ids,
+    goal_template_pattern_ids,
+    named_entity_ids,
+    named_entity_data_ids,
+    named_entity_pattern_ids,
+    goal_entity_tag_ids,
+    external_event_ids,
+    external_event_data_ids,
+  ]
+  .into_iter()
+  .map(|ids| ids.into_map())
+  .filter(|(_, ids)| !ids.is_empty())
+  .collect();
+
+  Ok(applied)
+}
+
+// reads the rows of one table between the client's high-water mark and the server's, and moves the client's past them
+async fn read_table<T: From<tokio_postgres::Row>>(
+  con: &mut impl GenericClient,
+  table: &str,
+  creator_user_id: i64,
+  marks: &BTreeMap<&str, i64>,
+  since: &mut BTreeMap<String, i64>,
+  more: &mut bool,
+) -> Result<Vec<T>, tokio_postgres::Error> {
+  let key = table_key(table);
+  let after = since.get(&key).copied().unwrap_or(0);
+  let until = marks.get(table).copied().unwrap_or(0);
+
+  let rows = con
+    .query(
+      format!(
+        "SELECT * FROM {} WHERE creator_user_id = $1 AND {}_id > $2 AND {}_id <= $3 ORDER BY {}_id LIMIT $4",
+        table, table, table, table
+      )
+      .as_str(),
+      &[&creator_user_id, &after, &until, &BATCH_SIZE],
+    )
+    .await?;
+
+  if rows.len() as i64 == BATCH_SIZE {
+    *more = true;
+  }
+  let last = rows
+    .last()
+    .map(|row| row.get::<_, i64>(format!("{}_id", table).as_str()))
+    .unwrap_or(after);
+  since.insert(key, last);
+
+  Ok(rows.into_iter().map(|row| row.into()).collect())
+}
+
+// the user's rows after the client's high-water marks, up to the server's.
+// con must not be in a transaction, as the server's marks are read in one of their own
+pub async fn read(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  mut since: BTreeMap<String, i64>,
+) -> Result<SyncResult, SyncError> {
+  // marks for tables that aren't synced would never be answered
+  let keys: Vec<String> = TABLES.iter().map(|table| table_key(table)).collect();
+  if let Some(key) = since.keys().find(|key| !keys.contains(key)) {
+    return Err(SyncError::Invalid(format!("unknown table: {}", key)));
+  }
+
+  let marks = TABLES
+    .iter()
+    .copied()
+    .zip(account_service::high_water_marks(con, creator_user_id, &TABLES).await?)
+    .collect();
+
+  let u = creator_user_id;
+  let w = &marks;
+  let s = &mut since;
+  let mut more = false;
+  let m = &mut more;
+  let tables = Tables {
+    user_generated_code: read_table(con, "user_generated_code", u, w, s, m).await?,
+    time_utility_function: read_table(con, "time_utility_function", u, w, s, m).await?,
+    goal: read_table(con, "goal", u, w, s, m).await?,
+    goal_data: read_table(con, "goal_data", u, w, s, m).await?,
+    goal_event: read_table(con, "goal_event", u, w, s, m).await?,
+    goal_dependency: read_table(con, "goal_dependency", u, w, s, m).await?,
+    goal_template: read_table(con, "goal_template", u, w, s, m).await?,
+    goal_template_data: read_table(con, "goal_template_data", u, w, s, m).await?,
+    goal_template_pattern: read_table(con, "goal_template_pattern", u, w, s, m).await?,
+    named_entity: read_table(con, "named_entity", u, w, s, m).await?,
+    named_entity_data: read_table(con, "named_entity_data", u, w, s, m).await?,
+    named_entity_pattern: read_table(con, "named_entity_pattern", u, w, s, m).await?,
+    goal_entity_tag: read_table(con, "goal_entity_tag", u, w, s, m).await?,
+    external_event: read_table(con, "external_event", u, w, s, m).await?,
+    external_event_data: read_table(con, "external_event_data", u, w, s, m).await?,
+  };
+
+  Ok(SyncResult {
+    tables,
+    since,
+    more,
+    id_map: BTreeMap::new(),
+  })
+}
@@ -1,4 +1,5 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
@@ -65,21 +66,46 @@ pub async fn get_by_time_utility_function_id(
   Ok(result)
 }
 
+pub async fn get_by_time_utility_function_ids(
+  con: &mut impl GenericClient,
+  time_utility_function_ids: &[i64],
+) -> Result<Vec<TimeUtilityFunction>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM time_utility_function WHERE time_utility_function_id = ANY($1)";
+  let results = con
+    .query(sql, &[&time_utility_function_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("tuf.creation_time"),
+    _ => None,
+  }
+}
+
 pub async fn query(
   con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
   props: request::TimeUtilityFunctionViewProps,
-) -> Result<Vec<TimeUtilityFunction>, tokio_postgres::Error> {
+) -> Result<Page<TimeUtilityFunction>, tokio_postgres::Error> {
   let sql = "SELECT tuf.* FROM time_utility_function tuf WHERE 1 = 1
      AND ($1::bigint[] IS NULL OR tuf.time_utility_function_id = ANY($1))
      AND ($2::bigint   IS NULL OR tuf.creation_time >= $2)
      AND ($3::bigint   IS NULL OR tuf.creation_time <= $3)
      AND ($4::bigint[] IS NULL OR tuf.creator_user_id = ANY($4))
-     ORDER BY tuf.time_utility_function_id
+     AND tuf.creator_user_id = $5
      ";
+  let sql = page.wrap(sql, "tuf", "time_utility_function_id", 6);
 
-  let stmnt = con.prepare(sql).await?;
+  let stmnt = con.prepare(&sql).await?;
 
-  let results = con
+  let rows = con
     .query(
       &stmnt,
       &[
@@ -87,12 +113,13 @@ pub async fn query(
         &props.min_creation_time,
         &props.max_creation_time,
         &props.creator_user_id,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|x| x.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
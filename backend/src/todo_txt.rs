@@ -0,0 +1,291 @@
+// Reads and writes the todo.txt format (https://github.com/todotxt/todo.txt).
+// Only deals with the text, mapping tasks onto goals is left to the handlers.
+
+use chrono::NaiveDate;
+
+static DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+  Pending,
+  Done,
+  Fail,
+  Cancel,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+  pub status: Status,
+  // an uppercase letter, A is the most important
+  pub priority: Option<char>,
+  pub completion_date: Option<NaiveDate>,
+  pub creation_date: Option<NaiveDate>,
+  // the description with the tags below removed, unknown key:value tags are left in place
+  pub description: String,
+  pub projects: Vec<String>,
+  pub contexts: Vec<String>,
+  pub due: Option<NaiveDate>,
+  // the `t:` extension, the task is hidden until this date
+  pub threshold: Option<NaiveDate>,
+}
+
+fn parse_date(s: &str) -> Option<NaiveDate> {
+  // require the exact zero padded form so that round trips are stable
+  if s.len() != 10 {
+    return None;
+  }
+  NaiveDate::parse_from_str(s, DATE_FORMAT).ok()
+}
+
+fn parse_priority(s: &str) -> Option<char> {
+  let mut chars = s.chars();
+  match (chars.next(), chars.next()) {
+    (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+    _ => None,
+  }
+}
+
+fn parse_status(s: &str) -> Option<Status> {
+  match s {
+    "fail" => Some(Status::Fail),
+    "cancel" => Some(Status::Cancel),
+    _ => None,
+  }
+}
+
+pub fn parse_task(line: &str) -> Option<Task> {
+  let mut words = line.split_whitespace().peekable();
+  words.peek()?;
+
+  let mut status = Status::Pending;
+  let mut priority = None;
+  let mut completion_date = None;
+  let mut creation_date = None;
+
+  if words.peek() == Some(&"x") {
+    words.next();
+    status = Status::Done;
+    // a completed task may have a completion date, followed by a creation date
+    if let Some(date) = words.peek().and_then(|w| parse_date(w)) {
+      words.next();
+      completion_date = Some(date);
+      if let Some(date) = words.peek().and_then(|w| parse_date(w)) {
+        words.next();
+        creation_date = Some(date);
+      }
+    }
+  } else {
+    if let Some(p) = words
+      .peek()
+      .and_then(|w| w.strip_prefix('('))
+      .and_then(|w| w.strip_suffix(')'))
+      .and_then(parse_priority)
+    {
+      words.next();
+      priority = Some(p);
+    }
+    if let Some(date) = words.peek().and_then(|w| parse_date(w)) {
+      words.next();
+      creation_date = Some(date);
+    }
+  }
+
+  let mut description = vec![];
+  let mut projects = vec![];
+  let mut contexts = vec![];
+  let mut due = None;
+  let mut threshold = None;
+
+  for word in words {
+    if let Some(project) = word.strip_prefix('+').filter(|x| !x.is_empty()) {
+      projects.push(project.to_owned());
+    } else if let Some(context) = word.strip_prefix('@').filter(|x| !x.is_empty()) {
+      contexts.push(context.to_owned());
+    } else if let Some(date) = word.strip_prefix("due:").and_then(parse_date) {
+      due = Some(date);
+    } else if let Some(date) = word.strip_prefix("t:").and_then(parse_date) {
+      threshold = Some(date);
+    } else if let Some(p) = word.strip_prefix("pri:").and_then(parse_priority) {
+      // completed tasks lose their leading priority, so it is kept as a tag instead
+      priority = Some(p);
+    } else if let Some(s) = word
+      .strip_prefix("status:")
+      .and_then(parse_status)
+      .filter(|_| status == Status::Done)
+    {
+      status = s;
+    } else {
+      description.push(word);
+    }
+  }
+
+  Some(Task {
+    status,
+    priority,
+    completion_date,
+    creation_date,
+    description: description.join(" "),
+    projects,
+    contexts,
+    due,
+    threshold,
+  })
+}
+
+// blank lines are skipped
+pub fn parse(source: &str) -> Vec<Task> {
+  source.lines().filter_map(parse_task).collect()
+}
+
+// tags can't contain whitespace
+fn tag_word(s: &str) -> String {
+  s.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+pub fn write_task(task: &Task) -> String {
+  let mut head = vec![];
+
+  if task.status == Status::Pending {
+    if let Some(p) = task.priority {
+      head.push(format!("({})", p));
+    }
+  } else {
+    head.push("x".to_owned());
+    if let Some(date) = task.completion_date {
+      head.push(date.format(DATE_FORMAT).to_string());
+    }
+  }
+  // on a completed task the creation date is only recognized after a completion date
+  if task.status == Status::Pending || task.completion_date.is_some() {
+    if let Some(date) = task.creation_date {
+      head.push(date.format(DATE_FORMAT).to_string());
+    }
+  }
+
+  let description: Vec<String> = task
+    .description
+    .split_whitespace()
+    .map(|x| x.to_owned())
+    .collect();
+
+  let mut tags = vec![];
+  for project in task.projects.iter() {
+    tags.push(format!("+{}", tag_word(project)));
+  }
+  for context in task.contexts.iter() {
+    tags.push(format!("@{}", tag_word(context)));
+  }
+  if let Some(date) = task.threshold {
+    tags.push(format!("t:{}", date.format(DATE_FORMAT)));
+  }
+  if let Some(date) = task.due {
+    tags.push(format!("due:{}", date.format(DATE_FORMAT)));
+  }
+  if task.status != Status::Pending {
+    if let Some(p) = task.priority {
+      tags.push(format!("pri:{}", p));
+    }
+  }
+  match task.status {
+    Status::Fail => tags.push("status:fail".to_owned()),
+    Status::Cancel => tags.push("status:cancel".to_owned()),
+    _ => {}
+  }
+
+  let line = [&head[..], &description, &tags].concat().join(" ");
+
+  // a description starting with something like a date or a priority would be read back as one.
+  // tags may come first just as well, which avoids that. without any tags it can't be helped
+  let description = description.join(" ");
+  if !tags.is_empty() && parse_task(&line).map(|t| t.description) != Some(description.clone()) {
+    return [&head[..], &tags, &[description]].concat().join(" ");
+  }
+  line
+}
+
+pub fn write(tasks: &[Task]) -> String {
+  tasks.iter().map(|t| write_task(t) + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn date(s: &str) -> Option<NaiveDate> {
+    Some(NaiveDate::parse_from_str(s, DATE_FORMAT).unwrap())
+  }
+
+  #[test]
+  fn parses_every_field() {
+    let task =
+      parse_task("(A) 2024-01-02 call mom +family @phone due:2024-01-05 t:2024-01-03 rec:1w")
+        .unwrap();
+    assert_eq!(
+      task,
+      Task {
+        status: Status::Pending,
+        priority: Some('A'),
+        completion_date: None,
+        creation_date: date("2024-01-02"),
+        description: "call mom rec:1w".to_owned(),
+        projects: vec!["family".to_owned()],
+        contexts: vec!["phone".to_owned()],
+        due: date("2024-01-05"),
+        threshold: date("2024-01-03"),
+      }
+    );
+  }
+
+  #[test]
+  fn round_trips_losslessly() {
+    // lines in the order we write them in come back unchanged
+    let source = [
+      "(A) 2024-01-02 call mom rec:1w +family @phone t:2024-01-03 due:2024-01-05",
+      "2024-01-02 no priority",
+      "(B) no dates",
+      "x 2024-01-04 2024-01-02 done +work pri:C",
+      "x 2024-01-04 done without a creation date",
+      "x done without dates",
+      "x 2024-01-04 2024-01-02 gave up status:fail",
+      "x 2024-01-04 not needed anymore pri:A status:cancel",
+    ]
+    .map(|x| x.to_owned() + "\n")
+    .concat();
+    let tasks = parse(&source);
+    assert_eq!(tasks.len(), 8);
+    assert_eq!(write(&tasks), source);
+    assert_eq!(parse(&write(&tasks)), tasks);
+  }
+
+  #[test]
+  fn keeps_tags_written_anywhere() {
+    // tags may be anywhere in the line, they are moved to the end, but nothing is lost
+    let tasks =
+      parse("+work (A) is not a priority @desk here\n\nx 2024-01-04 due:2024-01-01 late +work\n");
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].priority, None);
+    assert_eq!(tasks[0].description, "(A) is not a priority here");
+    assert_eq!(
+      write_task(&tasks[0]),
+      "+work @desk (A) is not a priority here"
+    );
+    assert_eq!(tasks[1].due, date("2024-01-01"));
+    assert_eq!(parse(&write(&tasks)), tasks);
+  }
+
+  #[test]
+  fn joins_whitespace_in_tags() {
+    let task = Task {
+      status: Status::Pending,
+      priority: None,
+      completion_date: None,
+      creation_date: None,
+      description: "plan".to_owned(),
+      projects: vec!["summer trip".to_owned()],
+      contexts: vec![],
+      due: None,
+      threshold: None,
+    };
+    assert_eq!(write_task(&task), "plan +summer_trip");
+  }
+}
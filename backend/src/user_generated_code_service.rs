@@ -1,4 +1,5 @@
 use super::db_types::*;
+use super::page::{Page, PageQuery};
 use super::utils::current_time_millis;
 use todo_app_service_api::request;
 use tokio_postgres::GenericClient;
@@ -76,33 +77,65 @@ pub async fn get_by_user_generated_code_id(
   Ok(result)
 }
 
-pub async fn query(
+pub async fn get_by_user_generated_code_ids(
   con: &mut impl GenericClient,
-  props: request::UserGeneratedCodeViewProps,
+  user_generated_code_ids: &[i64],
 ) -> Result<Vec<UserGeneratedCode>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM user_generated_code WHERE user_generated_code_id = ANY($1)";
   let results = con
-    .query(
-      "
+    .query(sql, &[&user_generated_code_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("ugc.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::UserGeneratedCodeViewProps,
+) -> Result<Page<UserGeneratedCode>, tokio_postgres::Error> {
+  let sql = page.wrap(
+    "
         SELECT ugc.* FROM user_generated_code ugc WHERE 1 = 1
         AND ($1::bigint[] IS NULL OR ugc.user_generated_code_id = ANY($1))
         AND ($2::bigint   IS NULL OR ugc.creation_time >= $2)
         AND ($3::bigint   IS NULL OR ugc.creation_time <= $3)
         AND ($4::bigint[] IS NULL OR ugc.creator_user_id = ANY($4))
         AND ($5::text[]   IS NULL OR ugc.source_lang = ANY($5))
-        ORDER BY ugc.user_generated_code_id
+        AND ugc.creator_user_id = $6
       ",
+    "ugc",
+    "user_generated_code_id",
+    7,
+  );
+
+  let rows = con
+    .query(
+      sql.as_str(),
       &[
         &props.user_generated_code_id,
         &props.min_creation_time,
         &props.max_creation_time,
         &props.creator_user_id,
         &props.source_lang,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
       ],
     )
-    .await?
-    .into_iter()
-    .map(|row| row.into())
-    .collect();
+    .await?;
 
-  Ok(results)
+  Ok(page.page(rows))
 }
@@ -1,5 +1,8 @@
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::convert::TryFrom;
+use std::net::IpAddr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn current_time_millis() -> i64 {
@@ -10,6 +13,55 @@ pub fn current_time_millis() -> i64 {
   since_the_epoch.as_millis() as i64
 }
 
+pub fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// generates a random, url safe secret
+pub fn gen_random_token() -> String {
+  let mut bytes = [0u8; 32];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  to_hex(&bytes)
+}
+
+// secrets are only ever stored hashed
+pub fn hash_token(token: &str) -> String {
+  to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+// whether the address is on the public internet, rather than e.g. loopback, a private network or link local
+// (which includes cloud metadata endpoints)
+pub fn is_public_ip(ip: IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => {
+      let [a, b, ..] = ip.octets();
+      !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "this network" and carrier grade nat
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)))
+    }
+    IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+      Some(ip) => is_public_ip(IpAddr::V4(ip)),
+      None => {
+        let first = ip.segments()[0];
+        !(ip.is_unspecified()
+          || ip.is_loopback()
+          || ip.is_multicast()
+          // unique local
+          || (first & 0xfe00) == 0xfc00
+          // link local
+          || (first & 0xffc0) == 0xfe80)
+      }
+    },
+  }
+}
+
 // fun error handling stuff
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -0,0 +1,208 @@
+// Delivers the events queued for webhooks.
+// Events are written in the same transaction as the change they describe,
+// so nothing is sent for changes that were rolled back, and nothing is lost if we crash before sending.
+// Failed deliveries are retried with exponential backoff,
+// and a webhook whose last few deliveries all failed is turned off.
+//
+// Every event is POSTed as json like {"webhookEventId", "webhookId", "kind", "creationTime", "data"}.
+// The X-Todo-App-Signature header is `sha256=` followed by the hex HMAC-SHA256 of the body, keyed with the secret.
+// Delivery is at least once, receivers should ignore webhookEventIds they have already seen.
+//
+// Webhook urls are chosen by users, so like CalDAV subscriptions they only reach hosts on the public internet,
+// unless the operator allows private addresses, see caldav_client.
+// Webhooks are delivered to concurrently, the events of one webhook are sent one after another in order.
+
+use super::caldav_client;
+
+use super::db_types::*;
+use super::utils;
+use super::webhook_delivery_service;
+use super::webhook_event_service;
+use super::webhook_service;
+use super::Db;
+use deadpool_postgres::PoolError;
+use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// an event is given up on after this many attempts, the last retry is about an hour after the first attempt
+static MAX_ATTEMPTS: i64 = 8;
+// the wait after the first failure, doubling after each one
+static RETRY_BASE: i64 = 30 * 1000;
+// events older than this are not looked at anymore, it's well past the last retry
+static MAX_EVENT_AGE: i64 = 24 * 60 * 60 * 1000;
+// turn off a webhook once this many deliveries in a row failed
+static FAILURES_BEFORE_DISABLE: i64 = 20;
+// how many webhooks are delivered to at the same time
+static CONCURRENT_WEBHOOKS: usize = 16;
+
+pub async fn run(db: Db, interval: Duration, allow_private_addresses: bool) {
+  let client = caldav_client::client(Duration::from_secs(10), allow_private_addresses);
+
+  let mut ticker = tokio::time::interval(interval);
+  loop {
+    ticker.tick().await;
+
+    let now = utils::current_time_millis();
+
+    let events: Result<_, PoolError> = async {
+      let con = &mut **db.get().await?;
+      Ok(webhook_event_service::get_undelivered(con, now - MAX_EVENT_AGE, MAX_ATTEMPTS).await?)
+    }
+    .await;
+
+    match events {
+      Ok(events) => {
+        let mut by_webhook: BTreeMap<i64, Vec<WebhookEvent>> = BTreeMap::new();
+        for (webhook_event, attempts, last_attempt_time) in events {
+          // wait out the backoff before trying again
+          if let Some(last_attempt_time) = last_attempt_time {
+            if now < last_attempt_time + (RETRY_BASE << (attempts - 1).clamp(0, 20)) {
+              continue;
+            }
+          }
+          by_webhook
+            .entry(webhook_event.webhook_id)
+            .or_default()
+            .push(webhook_event);
+        }
+
+        futures_util::stream::iter(by_webhook.into_values())
+          .for_each_concurrent(CONCURRENT_WEBHOOKS, |webhook_events| {
+            let db = &db;
+            let client = &client;
+            async move {
+              for webhook_event in webhook_events {
+                if let Err(e) = deliver(db, client, allow_private_addresses, webhook_event).await {
+                  utils::log(utils::Event {
+                    msg: e.to_string(),
+                    source: Some("webhook delivery".to_owned()),
+                    severity: utils::SeverityKind::Error,
+                  });
+                }
+              }
+            }
+          })
+          .await;
+      }
+      Err(e) => utils::log(utils::Event {
+        msg: e.to_string(),
+        source: Some("webhook delivery".to_owned()),
+        severity: utils::SeverityKind::Error,
+      }),
+    }
+  }
+}
+
+pub fn sign(secret: &str, body: &str) -> String {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+  mac.update(body.as_bytes());
+  utils::to_hex(&mac.finalize().into_bytes())
+}
+
+// makes one attempt, and records how it went
+async fn deliver(
+  db: &Db,
+  client: &reqwest::Client,
+  allow_private_addresses: bool,
+  webhook_event: WebhookEvent,
+) -> Result<(), PoolError> {
+  let webhook = {
+    let con = &mut **db.get().await?;
+    webhook_service::get_by_webhook_id(con, webhook_event.webhook_id).await?
+  };
+  let webhook = match webhook {
+    Some(webhook) => webhook,
+    None => return Ok(()),
+  };
+
+  let kind = serde_json::to_value(&webhook_event.kind).unwrap_or_default();
+  let body = serde_json::json!({
+    "webhookEventId": webhook_event.webhook_event_id,
+    "webhookId": webhook.webhook_id,
+    "kind": kind,
+    "creationTime": webhook_event.creation_time,
+    "data": serde_json::from_str::<serde_json::Value>(&webhook_event.payload).unwrap_or_default(),
+  })
+  .to_string();
+
+  // the url was checked when the webhook was made, but what its host points to may have changed since
+  let allowed = match allow_private_addresses {
+    true => Ok(()),
+    false => reqwest::Url::parse(&webhook.url)
+      .map_err(|e| e.to_string())
+      .and_then(|url| caldav_client::check_host(&url)),
+  };
+
+  let result = match allowed {
+    Ok(()) => client
+      .post(&webhook.url)
+      .header("Content-Type", "application/json")
+      .header("X-Todo-App-Event", kind.as_str().unwrap_or_default())
+      .header("X-Todo-App-Delivery", webhook_event.webhook_event_id)
+      .header(
+        "X-Todo-App-Signature",
+        format!("sha256={}", sign(&webhook.secret, &body)),
+      )
+      .body(body)
+      .send()
+      .await
+      .map_err(|e| e.to_string()),
+    Err(e) => Err(e),
+  };
+
+  let (status_code, error) = match result {
+    Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16() as i64), None),
+    Ok(resp) => (
+      Some(resp.status().as_u16() as i64),
+      Some(format!("returned {}", resp.status())),
+    ),
+    Err(e) => (None, Some(e)),
+  };
+  let failed = error.is_some();
+
+  let con = &mut **db.get().await?;
+
+  webhook_delivery_service::add(
+    con,
+    webhook_event.creator_user_id,
+    webhook_event.webhook_event_id,
+    status_code,
+    error,
+  )
+  .await?;
+
+  if failed {
+    let latest = webhook_delivery_service::get_latest_by_webhook_id(
+      con,
+      webhook.webhook_id,
+      FAILURES_BEFORE_DISABLE,
+    )
+    .await?;
+
+    if latest.len() as i64 == FAILURES_BEFORE_DISABLE && latest.iter().all(|x| x.error.is_some()) {
+      utils::log(utils::Event {
+        msg: format!(
+          "turning off webhook {} after repeated failures",
+          webhook.webhook_id
+        ),
+        source: Some(format!("webhook delivery: {}", webhook.url)),
+        severity: utils::SeverityKind::Info,
+      });
+      // replaces the webhook, so its remaining events are dropped as well
+      webhook_service::add(
+        con,
+        webhook.creator_user_id,
+        webhook.url,
+        webhook.secret,
+        webhook.event_kinds,
+        false,
+      )
+      .await?;
+    }
+  }
+
+  Ok(())
+}
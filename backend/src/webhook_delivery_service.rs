@@ -0,0 +1,141 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for WebhookDelivery {
+  // select * from webhook_delivery order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> WebhookDelivery {
+    WebhookDelivery {
+      webhook_delivery_id: row.get("webhook_delivery_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      webhook_event_id: row.get("webhook_event_id"),
+      status_code: row.get("status_code"),
+      error: row.get("error"),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  webhook_event_id: i64,
+  status_code: Option<i64>,
+  error: Option<String>,
+) -> Result<WebhookDelivery, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let webhook_delivery_id = con
+    .query_one(
+      "INSERT INTO
+       webhook_delivery(
+           creation_time,
+           creator_user_id,
+           webhook_event_id,
+           status_code,
+           error
+       )
+       VALUES($1, $2, $3, $4, $5)
+       RETURNING webhook_delivery_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &webhook_event_id,
+        &status_code,
+        &error,
+      ],
+    )
+    .await?
+    .get(0);
+
+  // return webhook_delivery
+  Ok(WebhookDelivery {
+    webhook_delivery_id,
+    creation_time,
+    creator_user_id,
+    webhook_event_id,
+    status_code,
+    error,
+  })
+}
+
+// the last few delivery attempts made to a webhook, newest first
+pub async fn get_latest_by_webhook_id(
+  con: &mut impl GenericClient,
+  webhook_id: i64,
+  limit: i64,
+) -> Result<Vec<WebhookDelivery>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT wd.* FROM webhook_delivery wd
+       INNER JOIN webhook_event we ON we.webhook_event_id = wd.webhook_event_id
+       WHERE we.webhook_id = $1
+       ORDER BY wd.webhook_delivery_id DESC
+       LIMIT $2
+      ",
+      &[&webhook_id, &limit],
+    )
+    .await?
+    .into_iter()
+    .map(|row| row.into())
+    .collect();
+
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("wd.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::WebhookDeliveryViewProps,
+) -> Result<Page<WebhookDelivery>, tokio_postgres::Error> {
+  let sql = [
+    "SELECT wd.* FROM webhook_delivery wd",
+    " INNER JOIN webhook_event we ON we.webhook_event_id = wd.webhook_event_id",
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR wd.webhook_delivery_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR wd.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR wd.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR wd.creator_user_id = ANY($4))",
+    " AND ($5::bigint[] IS NULL OR wd.webhook_event_id = ANY($5))",
+    " AND ($6::bigint[] IS NULL OR we.webhook_id = ANY($6))",
+    " AND ($7::bool     IS NULL OR (wd.error IS NULL) = $7)",
+    " AND wd.creator_user_id = $8",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "wd", "webhook_delivery_id", 9);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.webhook_delivery_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &props.webhook_event_id,
+        &props.webhook_id,
+        &props.succeeded,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}
@@ -0,0 +1,113 @@
+use super::db_types::*;
+use super::utils::current_time_millis;
+use std::convert::TryInto;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for WebhookEvent {
+  // select * from webhook_event order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> WebhookEvent {
+    WebhookEvent {
+      webhook_event_id: row.get("webhook_event_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      webhook_id: row.get("webhook_id"),
+      kind: (row.get::<_, i64>("kind") as u8).try_into().unwrap(),
+      payload: row.get("payload"),
+    }
+  }
+}
+
+// queues the payload for every active webhook of the user that asked for this kind of event
+// returns how many were queued
+pub async fn add_for_user(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  kind: request::WebhookEventKind,
+  payload: String,
+) -> Result<u64, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  con
+    .execute(
+      "INSERT INTO
+       webhook_event(
+           creation_time,
+           creator_user_id,
+           webhook_id,
+           kind,
+           payload
+       )
+       SELECT $1, w.creator_user_id, w.webhook_id, $3, $4
+       FROM recent_webhook w
+       WHERE w.creator_user_id = $2
+       AND w.active
+       AND $3 = ANY(w.event_kinds)
+      ",
+      &[&creation_time, &creator_user_id, &(kind as i64), &payload],
+    )
+    .await
+}
+
+pub async fn get_by_webhook_event_id(
+  con: &mut impl GenericClient,
+  webhook_event_id: i64,
+) -> Result<Option<WebhookEvent>, tokio_postgres::Error> {
+  let result = con
+    .query_opt(
+      "SELECT * FROM webhook_event WHERE webhook_event_id=$1",
+      &[&webhook_event_id],
+    )
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+pub async fn get_by_webhook_event_ids(
+  con: &mut impl GenericClient,
+  webhook_event_ids: &[i64],
+) -> Result<Vec<WebhookEvent>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM webhook_event WHERE webhook_event_id = ANY($1)";
+  let results = con
+    .query(sql, &[&webhook_event_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// events created after min_creation_time that haven't been delivered yet and still have attempts left,
+// along with how many attempts were made and when the last one was
+// events of webhooks that were since replaced or turned off are dropped
+pub async fn get_undelivered(
+  con: &mut impl GenericClient,
+  min_creation_time: i64,
+  max_attempts: i64,
+) -> Result<Vec<(WebhookEvent, i64, Option<i64>)>, tokio_postgres::Error> {
+  let results = con
+    .query(
+      "SELECT we.*,
+         count(wd.webhook_delivery_id) attempts,
+         max(wd.creation_time) last_attempt_time
+       FROM webhook_event we
+       INNER JOIN recent_webhook w ON w.webhook_id = we.webhook_id
+       LEFT JOIN webhook_delivery wd ON wd.webhook_event_id = we.webhook_event_id
+       WHERE we.creation_time >= $1
+       AND w.active
+       GROUP BY we.webhook_event_id
+       HAVING count(wd.webhook_delivery_id) FILTER (WHERE wd.error IS NULL) = 0
+       AND count(wd.webhook_delivery_id) < $2
+       ORDER BY we.webhook_event_id
+      ",
+      &[&min_creation_time, &max_attempts],
+    )
+    .await?
+    .into_iter()
+    .map(|row| (row.get("attempts"), row.get("last_attempt_time"), row))
+    .map(|(attempts, last_attempt_time, row)| (row.into(), attempts, last_attempt_time))
+    .collect();
+
+  Ok(results)
+}
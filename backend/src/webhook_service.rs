@@ -0,0 +1,157 @@
+use super::db_types::*;
+use super::page::{Page, PageQuery};
+use super::utils::current_time_millis;
+use std::convert::TryInto;
+use todo_app_service_api::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for Webhook {
+  // select * from webhook order only, otherwise it will fail
+  fn from(row: tokio_postgres::row::Row) -> Webhook {
+    Webhook {
+      webhook_id: row.get("webhook_id"),
+      creation_time: row.get("creation_time"),
+      creator_user_id: row.get("creator_user_id"),
+      url: row.get("url"),
+      secret: row.get("secret"),
+      event_kinds: row
+        .get::<_, Vec<i64>>("event_kinds")
+        .into_iter()
+        .map(|x| (x as u8).try_into().unwrap())
+        .collect(),
+      active: row.get("active"),
+    }
+  }
+}
+
+pub async fn add(
+  con: &mut impl GenericClient,
+  creator_user_id: i64,
+  url: String,
+  secret: String,
+  event_kinds: Vec<request::WebhookEventKind>,
+  active: bool,
+) -> Result<Webhook, tokio_postgres::Error> {
+  let creation_time = current_time_millis();
+
+  let webhook_id = con
+    .query_one(
+      "INSERT INTO
+       webhook(
+           creation_time,
+           creator_user_id,
+           url,
+           secret,
+           event_kinds,
+           active
+       )
+       VALUES($1, $2, $3, $4, $5, $6)
+       RETURNING webhook_id
+      ",
+      &[
+        &creation_time,
+        &creator_user_id,
+        &url,
+        &secret,
+        &event_kinds
+          .iter()
+          .map(|x| x.clone() as i64)
+          .collect::<Vec<i64>>(),
+        &active,
+      ],
+    )
+    .await?
+    .get(0);
+
+  // return webhook
+  Ok(Webhook {
+    webhook_id,
+    creation_time,
+    creator_user_id,
+    url,
+    secret,
+    event_kinds,
+    active,
+  })
+}
+
+pub async fn get_by_webhook_id(
+  con: &mut impl GenericClient,
+  webhook_id: i64,
+) -> Result<Option<Webhook>, tokio_postgres::Error> {
+  let result = con
+    .query_opt("SELECT * FROM webhook WHERE webhook_id=$1", &[&webhook_id])
+    .await?
+    .map(|x| x.into());
+
+  Ok(result)
+}
+
+pub async fn get_by_webhook_ids(
+  con: &mut impl GenericClient,
+  webhook_ids: &[i64],
+) -> Result<Vec<Webhook>, tokio_postgres::Error> {
+  let sql = "SELECT * FROM webhook WHERE webhook_id = ANY($1)";
+  let results = con
+    .query(sql, &[&webhook_ids])
+    .await?
+    .into_iter()
+    .map(|x| x.into())
+    .collect();
+  Ok(results)
+}
+
+// the expressions query results can be sorted by
+pub fn sort_key(sort: &request::ViewSortKind) -> Option<&'static str> {
+  match sort {
+    request::ViewSortKind::CreationTime => Some("w.creation_time"),
+    _ => None,
+  }
+}
+
+pub async fn query(
+  con: &mut impl GenericClient,
+  user_id: i64,
+  page: &PageQuery,
+  props: request::WebhookViewProps,
+) -> Result<Page<Webhook>, tokio_postgres::Error> {
+  let sql = [
+    if props.only_recent {
+      "SELECT w.* FROM recent_webhook w"
+    } else {
+      "SELECT w.* FROM webhook w"
+    },
+    " WHERE 1 = 1",
+    " AND ($1::bigint[] IS NULL OR w.webhook_id = ANY($1))",
+    " AND ($2::bigint   IS NULL OR w.creation_time >= $2)",
+    " AND ($3::bigint   IS NULL OR w.creation_time <= $3)",
+    " AND ($4::bigint[] IS NULL OR w.creator_user_id = ANY($4))",
+    " AND ($5::text[]   IS NULL OR w.url = ANY($5))",
+    " AND ($6::bool     IS NULL OR w.active = $6)",
+    " AND w.creator_user_id = $7",
+  ]
+  .join("\n");
+  let sql = page.wrap(&sql, "w", "webhook_id", 8);
+
+  let stmnt = con.prepare(&sql).await?;
+
+  let rows = con
+    .query(
+      &stmnt,
+      &[
+        &props.webhook_id,
+        &props.min_creation_time,
+        &props.max_creation_time,
+        &props.creator_user_id,
+        &props.url,
+        &props.active,
+        &user_id,
+        &page.after_key,
+        &page.after_id,
+        &page.fetch_limit,
+      ],
+    )
+    .await?;
+
+  Ok(page.page(rows))
+}